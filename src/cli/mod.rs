@@ -0,0 +1,120 @@
+mod repl;
+mod run;
+mod serve;
+mod tui;
+mod validate;
+
+use crate::core::errors::CoreError;
+use crate::core::registry::RegistryLimits;
+use crate::core::world_config::WorldCfg;
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about = "Agent based simulator with scriptable behaviours, served over MCP")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve MCP tools, over stdio unless --http is given. Default without a command.
+    Serve {
+        /// Serve over streamable HTTP on this address, e.g. 127.0.0.1:8000
+        #[arg(long, value_name = "ADDR")]
+        http: Option<SocketAddr>,
+        /// Allow serving HTTP on an address other than loopback. Clients are not authenticated, anyone reaching the
+        /// address can create worlds and run scripts
+        #[arg(long, requires = "http")]
+        allow_remote: bool,
+        /// Maximum number of worlds, unlimited if omitted
+        #[arg(long, value_name = "COUNT")]
+        max_worlds: Option<usize>,
+    },
+    /// Run a world headless and write its results
+    Run(run::RunArgs),
+    /// Step and inspect a world interactively
+    Repl {
+        /// World configuration, YAML or JSON (.json)
+        config: PathBuf,
+    },
+    /// Watch and control a run in a terminal dashboard
+    Tui(tui::TuiArgs),
+    /// Check a world configuration and its scripts without running it
+    Validate {
+        /// World configuration, YAML or JSON (.json)
+        config: PathBuf,
+    },
+}
+
+impl Cli {
+    pub async fn execute(self) -> Result<(), String> {
+        match self.command.unwrap_or(Command::Serve {
+            http: None,
+            allow_remote: false,
+            max_worlds: None,
+        }) {
+            Command::Serve {
+                http,
+                allow_remote,
+                max_worlds,
+            } => {
+                let limits = RegistryLimits { max_worlds };
+                match http {
+                    Some(addr) => {
+                        check_http_addr(addr, allow_remote)?;
+                        serve::serve_http(addr, limits).await
+                    }
+                    None => serve::serve_stdio(limits).await,
+                }
+            }
+            Command::Run(args) => run::run(args),
+            Command::Repl { config } => repl::repl(&config),
+            Command::Tui(args) => tui::tui(args),
+            Command::Validate { config } => validate::validate(&config),
+        }
+    }
+}
+
+// Load a world configuration, JSON if the file has the .json extension, YAML otherwise. It is validated when the
+// world is created.
+fn load_world_cfg(path: &std::path::Path) -> Result<WorldCfg, CoreError> {
+    let path_str = path.to_string_lossy();
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => WorldCfg::from_json_file(&path_str),
+        _ => WorldCfg::from_yaml_file(&path_str),
+    }
+}
+
+// Clients are not authenticated, serving beyond loopback must be asked for explicitly
+fn check_http_addr(addr: SocketAddr, allow_remote: bool) -> Result<(), String> {
+    if addr.ip().is_loopback() || allow_remote {
+        return Ok(());
+    }
+    Err(format!(
+        "Refusing to serve on {}, which is not a loopback address. Pass --allow-remote to serve unauthenticated clients on it",
+        addr
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_http_addr() {
+        let addr = |addr: &str| addr.parse::<SocketAddr>().unwrap();
+
+        assert!(check_http_addr(addr("127.0.0.1:8000"), false).is_ok());
+        assert!(check_http_addr(addr("[::1]:8000"), false).is_ok());
+        assert!(check_http_addr(addr("0.0.0.0:8000"), false).is_err());
+        assert!(check_http_addr(addr("192.168.1.10:8000"), false).is_err());
+        assert!(check_http_addr(addr("0.0.0.0:8000"), true).is_ok());
+
+        // The flag requires --http
+        assert!(Cli::try_parse_from(["vivarium", "serve", "--allow-remote"]).is_err());
+        assert!(Cli::try_parse_from(["vivarium", "serve", "--http", "0.0.0.0:8000", "--allow-remote"]).is_ok());
+    }
+}
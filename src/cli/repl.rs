@@ -0,0 +1,330 @@
+use crate::cli::load_world_cfg;
+use crate::core::World;
+use crate::core::messaging::{JSONObject, Message, MessageReceiver};
+use crate::core::snapshot::WorldSnapshot;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const HELP: &str = "\
+Commands:
+  step [steps] [duration]          Advance the simulation, 1 step of duration 1 by default
+  messages                         Show the messages delivered by the last step
+  list [script_id]                 List entities and their scripts
+  state <id>                       Show the state of an entity
+  set <id> <json>                  Replace the state of an entity, e.g. set sheep_1 {\"energy\": 5}
+  send <receiver> <kind> [json]    Send a message, the receiver is an entity ID, tag:<tag>, group:<name>, topic:<topic> or neighbors:<id>
+  globals                          Show the global variables
+  metrics [name]                   List metrics with their last value, or show the statistics of one
+  snapshot [name]                  Keep a snapshot of the world in memory, 'default' if no name is given
+  restore [name]                   Restore a snapshot taken with 'snapshot'
+  save <path>                      Write a YAML snapshot of the world to a file
+  help                             Show this help
+  quit                             Exit";
+
+// Interactive session on a single world, the same operations as the MCP tools for a human at a terminal
+struct Repl {
+    world: World,
+    snapshots: BTreeMap<String, WorldSnapshot>,
+    last_messages: Vec<Message>,
+}
+
+pub fn repl(path: &Path) -> Result<(), String> {
+    let world_cfg = load_world_cfg(path).map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    let world = World::new(&world_cfg).map_err(|e| format!("Failed to create world: {}", e))?;
+    println!(
+        "World '{}' with {} entities. Type 'help' for commands.",
+        world_cfg.name,
+        world.get_entities_count()
+    );
+
+    let mut repl = Repl { world, snapshots: BTreeMap::new(), last_messages: Vec::new() };
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("[t={}]> ", repl.world.get_simulation_time());
+        let _ = std::io::stdout().flush();
+
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+        let line = line.map_err(|e| format!("Failed to read input: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "quit" || line == "exit" {
+            return Ok(());
+        }
+
+        // Errors of a command are reported and the session goes on
+        match repl.execute(line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
+
+impl Repl {
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match command {
+            "help" => Ok(HELP.to_string()),
+            "step" => self.step(rest),
+            "messages" => self.messages(),
+            "list" => Ok(self.list(rest)),
+            "state" => {
+                let id = required(rest, "state <id>")?;
+                let state = self.world.get_entity_state(id).map_err(|e| e.to_string())?;
+                to_json(&state)
+            }
+            "set" => {
+                let (id, json) = rest.split_once(char::is_whitespace).ok_or("Usage: set <id> <json>")?;
+                let state = parse_object(json)?;
+                self.world.set_entity_state(id, state).map_err(|e| e.to_string())?;
+                Ok(String::new())
+            }
+            "send" => self.send(rest),
+            "globals" => to_json(self.world.get_state_ref().get_globals()),
+            "metrics" => self.metrics(rest),
+            "snapshot" => {
+                let name = if rest.is_empty() { "default" } else { rest };
+                let snapshot = self.world.create_snapshot().map_err(|e| e.to_string())?;
+                self.snapshots.insert(name.to_string(), snapshot);
+                Ok(format!("Snapshot '{}' taken at simulation time {}", name, self.world.get_simulation_time()))
+            }
+            "restore" => {
+                let name = if rest.is_empty() { "default" } else { rest };
+                let snapshot = self.snapshots.get(name).ok_or_else(|| format!("Snapshot '{}' not found", name))?;
+                self.world = World::new_from_snapshot(snapshot.clone()).map_err(|e| e.to_string())?;
+                self.last_messages.clear();
+                Ok(format!("Restored snapshot '{}' at simulation time {}", name, self.world.get_simulation_time()))
+            }
+            "save" => {
+                let path = required(rest, "save <path>")?;
+                self.world
+                    .create_snapshot()
+                    .and_then(|snapshot| snapshot.to_yaml_file(path))
+                    .map_err(|e| e.to_string())?;
+                Ok(format!("Snapshot written to {}", path))
+            }
+            _ => Err(format!("Unknown command '{}', type 'help' for commands", command)),
+        }
+    }
+
+    fn step(&mut self, args: &str) -> Result<String, String> {
+        let mut args = args.split_whitespace();
+        let steps: u32 = parse_number(args.next(), 1)?;
+        let duration: u64 = parse_number(args.next(), 1)?;
+
+        self.last_messages.clear();
+        let mut command_errors = Vec::new();
+        for _ in 0..steps {
+            let result = self.world.update(duration).map_err(|e| e.to_string())?;
+            self.last_messages = result.delivered_messages;
+            command_errors.extend(result.command_errors);
+            if self.world.get_stop_reason().is_some() {
+                break;
+            }
+        }
+
+        let mut output = format!(
+            "Simulation time {}, {} entities, {} messages delivered in the last step",
+            self.world.get_simulation_time(),
+            self.world.get_entities_count(),
+            self.last_messages.len()
+        );
+        for error in command_errors {
+            output.push_str(&format!("\n{}", error));
+        }
+        if let Some(reason) = self.world.get_stop_reason() {
+            output.push_str(&format!("\nStopped by director: {}", reason));
+        }
+        Ok(output)
+    }
+
+    fn messages(&self) -> Result<String, String> {
+        let lines: Result<Vec<String>, String> = self
+            .last_messages
+            .iter()
+            .map(|message| {
+                let content = serde_json::to_string(&message.content).map_err(|e| e.to_string())?;
+                Ok(format!("{} -> {:?}: {} {}", message.sender, message.receiver, message.kind, content))
+            })
+            .collect();
+        Ok(lines?.join("\n"))
+    }
+
+    fn list(&self, script_id: &str) -> String {
+        let state = self.world.get_state_ref();
+        let mut entities: Vec<(&String, String)> = state
+            .get_entities()
+            .iter()
+            .map(|(id, entity)| (id, entity.borrow().get_script_id().clone()))
+            .filter(|(_, entity_script_id)| script_id.is_empty() || entity_script_id == script_id)
+            .collect();
+        entities.sort();
+
+        entities.iter().map(|(id, script_id)| format!("{} ({})", id, script_id)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn send(&mut self, args: &str) -> Result<String, String> {
+        let mut args = args.splitn(3, char::is_whitespace);
+        let usage = "Usage: send <receiver> <kind> [json]";
+        let receiver = args.next().filter(|receiver| !receiver.is_empty()).ok_or(usage)?;
+        let kind = args.next().ok_or(usage)?;
+        let content = match args.next() {
+            Some(json) => parse_object(json)?,
+            None => JSONObject::new(),
+        };
+
+        let receiver = match receiver.split_once(':') {
+            Some(("tag", tag)) => MessageReceiver::Tag { tag: tag.to_string() },
+            Some(("group", name)) => MessageReceiver::Group { name: name.to_string() },
+            Some(("topic", topic)) => MessageReceiver::Topic { topic: topic.to_string() },
+            Some(("neighbors", id)) => MessageReceiver::Neighbors { id: id.to_string() },
+            _ => MessageReceiver::Entity { id: receiver.to_string() },
+        };
+
+        self.world
+            .send_message("external", receiver, kind.to_string(), content, 0)
+            .map_err(|e| e.to_string())?;
+        Ok(String::from("Message delivered in the next step"))
+    }
+
+    fn metrics(&self, name: &str) -> Result<String, String> {
+        let metrics = self.world.get_metrics_ref();
+        if name.is_empty() {
+            let mut names = metrics.list_metric_names();
+            names.sort();
+            let lines: Vec<String> = names
+                .iter()
+                .map(|name| format!("{} = {}", name, metrics.get_last_value(name).unwrap_or_default()))
+                .collect();
+            return Ok(lines.join("\n"));
+        }
+
+        let stats = metrics.compute_metric_stats(name).ok_or_else(|| format!("Metric '{}' not found", name))?;
+        Ok(format!(
+            "{}: last {}, average {}, min {}, max {}, total {}, {} points",
+            stats.name,
+            metrics.get_last_value(name).unwrap_or_default(),
+            stats.average,
+            stats.min,
+            stats.max,
+            stats.total,
+            stats.count
+        ))
+    }
+}
+
+fn required<'a>(arg: &'a str, usage: &str) -> Result<&'a str, String> {
+    if arg.is_empty() { Err(format!("Usage: {}", usage)) } else { Ok(arg) }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: Option<&str>, default: T) -> Result<T, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("'{}' is not a valid number", arg)),
+        None => Ok(default),
+    }
+}
+
+fn parse_object(json: &str) -> Result<JSONObject, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid JSON object: {}", e))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::world_config::{EntityCfg, WorldCfg};
+
+    fn create_repl() -> Repl {
+        let mut world_cfg = WorldCfg::new("repl_world".to_string());
+        world_cfg.add_script(
+            "sheep".to_string(),
+            r#"
+            local energy = 0
+            function update(current_time, msgs) end
+            function get_state() return {energy = energy} end
+            function set_state(state) energy = state.energy end
+            "#
+            .to_string(),
+        );
+        world_cfg.entities.push(EntityCfg {
+            id: "sheep_1".to_string(),
+            script_id: "sheep".to_string(),
+            tags: vec!["flock".to_string()],
+            ..Default::default()
+        });
+
+        Repl {
+            world: World::new(&world_cfg).unwrap(),
+            snapshots: BTreeMap::new(),
+            last_messages: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let mut repl = create_repl();
+        repl.execute("send sheep_1 ping").unwrap();
+        repl.execute("send tag:flock shock {\"level\": 2}").unwrap();
+        repl.execute("send group:flock shock").unwrap();
+        repl.execute("send topic:news headline").unwrap();
+        repl.execute("send neighbors:sheep_1 hello").unwrap();
+        repl.execute("step").unwrap();
+
+        let receivers: Vec<String> = repl.last_messages.iter().map(|message| format!("{:?}", message.receiver)).collect();
+        assert_eq!(
+            receivers,
+            vec![
+                r#"Entity { id: "sheep_1" }"#,
+                r#"Tag { tag: "flock" }"#,
+                r#"Group { name: "flock" }"#,
+                r#"Topic { topic: "news" }"#,
+                r#"Neighbors { id: "sheep_1" }"#,
+            ]
+        );
+        assert_eq!(repl.last_messages[1].kind, "shock");
+        assert_eq!(repl.last_messages[1].content["level"], 2);
+
+        assert!(repl.execute("send").is_err());
+        assert!(repl.execute("send sheep_1").is_err());
+        assert!(repl.execute("send sheep_1 ping {\"level\":").is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut repl = create_repl();
+        // The JSON may contain spaces, only the ID is split off
+        repl.execute("set sheep_1 {\"energy\": 5, \"unused\": [1, 2]}").unwrap();
+        assert_eq!(repl.world.get_entity_state("sheep_1").unwrap()["energy"], 5);
+
+        assert!(repl.execute("set sheep_1").is_err());
+        assert!(repl.execute("set sheep_1 [1, 2]").is_err());
+        assert!(repl.execute("set wolf_1 {\"energy\": 5}").is_err());
+    }
+
+    #[test]
+    fn test_step() {
+        let mut repl = create_repl();
+        repl.execute("step").unwrap();
+        assert_eq!(repl.world.get_simulation_time(), 1);
+        repl.execute("step 3 2").unwrap();
+        assert_eq!(repl.world.get_simulation_time(), 7);
+        repl.execute("step   2").unwrap();
+        assert_eq!(repl.world.get_simulation_time(), 9);
+
+        assert!(repl.execute("step x").is_err());
+        assert!(repl.execute("step 1 -1").is_err());
+        assert_eq!(repl.world.get_simulation_time(), 9);
+        assert!(repl.execute("jump").is_err());
+    }
+}
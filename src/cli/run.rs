@@ -0,0 +1,80 @@
+use crate::cli::load_world_cfg;
+use crate::core::World;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct RunArgs {
+    /// World configuration, YAML or JSON (.json)
+    config: PathBuf,
+    /// Number of steps to run
+    #[arg(long)]
+    steps: u32,
+    /// Duration of each step
+    #[arg(long, default_value_t = 1)]
+    step_duration: u64,
+    /// Seed of the world, overrides the seed of the configuration
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Write a YAML snapshot of the world after the run
+    #[arg(long, value_name = "PATH")]
+    snapshot_out: Option<PathBuf>,
+    /// Write all recorded metric points as CSV (metric,time,value)
+    #[arg(long, value_name = "PATH")]
+    metrics_out: Option<PathBuf>,
+}
+
+// Run the steps, stopping early if the director ends the run, and write the requested outputs
+pub fn run(args: RunArgs) -> Result<(), String> {
+    let mut world_cfg = load_world_cfg(&args.config).map_err(|e| format!("Failed to load {}: {}", args.config.display(), e))?;
+    if args.seed.is_some() {
+        world_cfg.seed = args.seed;
+    }
+
+    let mut world = World::new(&world_cfg).map_err(|e| format!("Failed to create world: {}", e))?;
+
+    let mut steps = 0;
+    let mut number_of_messages = 0;
+    let mut rejected_messages = 0;
+    let mut rejected_metric_points = 0;
+    while steps < args.steps && world.get_stop_reason().is_none() {
+        let result = world
+            .update(args.step_duration)
+            .map_err(|e| format!("Step {} failed at simulation time {}: {}", steps + 1, world.get_simulation_time(), e))?;
+        steps += 1;
+        number_of_messages += result.delivered_messages.len();
+        rejected_messages += result.rejected_messages;
+        rejected_metric_points += result.rejected_metric_points;
+    }
+
+    println!(
+        "World '{}': {} steps, simulation time {}, {} entities, {} messages delivered",
+        world_cfg.name,
+        steps,
+        world.get_simulation_time(),
+        world.get_state_ref().get_entities().len(),
+        number_of_messages
+    );
+    if rejected_messages > 0 {
+        println!("{} messages dropped by quotas", rejected_messages);
+    }
+    if rejected_metric_points > 0 {
+        println!("{} metric points dropped by max_metric_points", rejected_metric_points);
+    }
+    if let Some(reason) = world.get_stop_reason() {
+        println!("Stopped by director: {}", reason);
+    }
+
+    if let Some(path) = &args.snapshot_out {
+        world
+            .create_snapshot()
+            .and_then(|snapshot| snapshot.to_yaml_file(&path.to_string_lossy()))
+            .map_err(|e| format!("Failed to write snapshot to {}: {}", path.display(), e))?;
+    }
+
+    if let Some(path) = &args.metrics_out {
+        std::fs::write(path, world.get_metrics_ref().to_csv())
+            .map_err(|e| format!("Failed to write metrics to {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
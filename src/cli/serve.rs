@@ -0,0 +1,45 @@
+use crate::core::registry::{Registry, RegistryLimits};
+use crate::mcp::VivariumToolServer;
+use rmcp::transport::streamable_http_server::{StreamableHttpServerConfig, StreamableHttpService, session::local::LocalSessionManager};
+use rmcp::{ServiceExt, transport::stdio};
+use std::net::SocketAddr;
+
+// Path of the MCP endpoint of the HTTP transport
+const HTTP_MCP_PATH: &str = "/mcp";
+
+fn create_tool_server(limits: RegistryLimits) -> VivariumToolServer {
+    VivariumToolServer::new(Registry::new(limits))
+}
+
+pub async fn serve_stdio(limits: RegistryLimits) -> Result<(), String> {
+    let service = create_tool_server(limits).serve(stdio()).await
+        .map_err(|e| format!("Server error: {}", e))?;
+
+    if let Err(e) = service.waiting().await {
+        return Err(format!("Service error: {}", e));
+    }
+
+    Ok(())
+}
+
+// All clients share the worlds and jobs of one registry, until interrupted with Ctrl+C
+pub async fn serve_http(addr: SocketAddr, limits: RegistryLimits) -> Result<(), String> {
+    let tool_server = create_tool_server(limits);
+    let service = StreamableHttpService::new(
+        move || Ok(tool_server.clone()),
+        LocalSessionManager::default().into(),
+        StreamableHttpServerConfig::default(),
+    );
+    let router = axum::Router::new().nest_service(HTTP_MCP_PATH, service);
+
+    let listener = tokio::net::TcpListener::bind(addr).await
+        .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+    eprintln!("Serving MCP over streamable HTTP at http://{}{}", addr, HTTP_MCP_PATH);
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .map_err(|e| format!("Server error: {}", e))
+}
@@ -0,0 +1,187 @@
+mod ui;
+
+use crate::cli::load_world_cfg;
+use crate::core::World;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Number of steps kept for the throughput chart and lines kept in the log
+const HISTORY_SIZE: usize = 200;
+// Delivered messages logged per step, the rest are summarized
+const LOGGED_MESSAGES_PER_STEP: usize = 10;
+// Bounds of the time between two steps while running, in milliseconds
+const MIN_INTERVAL_MS: u64 = 10;
+const MAX_INTERVAL_MS: u64 = 5000;
+
+#[derive(clap::Args)]
+pub struct TuiArgs {
+    /// World configuration, YAML or JSON (.json)
+    config: PathBuf,
+    /// Metric to chart, may be repeated. The first metrics by name are charted if none is given.
+    #[arg(long = "metric", value_name = "NAME")]
+    metrics: Vec<String>,
+    /// Duration of each step
+    #[arg(long, default_value_t = 1)]
+    step_duration: u64,
+    /// Time between two steps while running, in milliseconds
+    #[arg(long, default_value_t = 200)]
+    interval: u64,
+    /// Seed of the world, overrides the seed of the configuration
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+// Dashboard state, read by the UI on every frame
+struct App {
+    world: World,
+    world_name: String,
+    metrics: Vec<String>,
+    step_duration: u64,
+    interval: Duration,
+    paused: bool,
+    steps: u64,
+    throughput: VecDeque<u64>, // Messages delivered per step, most recent last
+    log: VecDeque<String>,
+}
+
+pub fn tui(args: TuiArgs) -> Result<(), String> {
+    let mut world_cfg = load_world_cfg(&args.config).map_err(|e| format!("Failed to load {}: {}", args.config.display(), e))?;
+    if args.seed.is_some() {
+        world_cfg.seed = args.seed;
+    }
+    let world = World::new(&world_cfg).map_err(|e| format!("Failed to create world: {}", e))?;
+
+    let mut app = App {
+        world,
+        world_name: world_cfg.name.clone(),
+        metrics: args.metrics,
+        step_duration: args.step_duration,
+        interval: Duration::from_millis(args.interval.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS)),
+        paused: false,
+        steps: 0,
+        throughput: VecDeque::new(),
+        log: VecDeque::new(),
+    };
+
+    // The terminal is restored also if the UI panics
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut ratatui::DefaultTerminal) -> Result<(), String> {
+        let mut next_step = Instant::now();
+        loop {
+            terminal.draw(|frame| ui::draw(frame, self)).map_err(|e| format!("Failed to draw: {}", e))?;
+
+            let timeout = if self.paused { Duration::from_millis(250) } else { next_step.saturating_duration_since(Instant::now()) };
+            if event::poll(timeout).map_err(|e| format!("Failed to read input: {}", e))?
+                && let Event::Key(key) = event::read().map_err(|e| format!("Failed to read input: {}", e))?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char(' ') => self.toggle_pause(),
+                    KeyCode::Char('s') | KeyCode::Right => {
+                        self.paused = true;
+                        self.step();
+                    }
+                    KeyCode::Char('+') => self.set_interval(self.interval.as_millis() as u64 / 2),
+                    KeyCode::Char('-') => self.set_interval(self.interval.as_millis() as u64 * 2),
+                    _ => {}
+                }
+            }
+
+            if !self.paused && Instant::now() >= next_step {
+                self.step();
+                next_step = Instant::now() + self.interval;
+            }
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        if self.paused && self.world.get_stop_reason().is_some() {
+            self.push_log("The director has stopped the run".to_string());
+            return;
+        }
+        self.paused = !self.paused;
+    }
+
+    fn set_interval(&mut self, interval_ms: u64) {
+        self.interval = Duration::from_millis(interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS));
+    }
+
+    // Simulate one step and record its throughput and log lines. Failures and the end of the run pause the dashboard.
+    fn step(&mut self) {
+        if let Some(reason) = self.world.get_stop_reason() {
+            let line = format!("Stopped by director: {}", reason);
+            self.push_log(line);
+            self.paused = true;
+            return;
+        }
+
+        let entities_before = self.world.get_entities_count();
+        let result = match self.world.update(self.step_duration) {
+            Ok(result) => result,
+            Err(e) => {
+                self.push_log(format!("Step failed: {}", e));
+                self.paused = true;
+                return;
+            }
+        };
+        self.steps += 1;
+
+        let time = self.world.get_simulation_time();
+        let messages = result.delivered_messages;
+        for message in messages.iter().take(LOGGED_MESSAGES_PER_STEP) {
+            self.push_log(format!("t={} {} -> {:?}: {}", time, message.sender, message.receiver, message.kind));
+        }
+        if messages.len() > LOGGED_MESSAGES_PER_STEP {
+            self.push_log(format!("t={} ... and {} more messages", time, messages.len() - LOGGED_MESSAGES_PER_STEP));
+        }
+
+        for error in &result.command_errors {
+            self.push_log(format!("t={} {}", time, error));
+        }
+
+        let entities_after = self.world.get_entities_count();
+        if entities_after != entities_before {
+            self.push_log(format!("t={} entities: {} -> {}", time, entities_before, entities_after));
+        }
+
+        if let Some(reason) = self.world.get_stop_reason() {
+            let line = format!("t={} stopped by director: {}", time, reason);
+            self.push_log(line);
+            self.paused = true;
+        }
+
+        push_bounded(&mut self.throughput, messages.len() as u64);
+    }
+
+    fn push_log(&mut self, line: String) {
+        push_bounded(&mut self.log, line);
+    }
+
+    // Metrics to chart, the first ones by name until some are selected
+    fn charted_metrics(&self) -> Vec<String> {
+        if !self.metrics.is_empty() {
+            return self.metrics.clone();
+        }
+
+        let mut names = self.world.get_metrics_ref().list_metric_names();
+        names.sort();
+        names.truncate(4);
+        names
+    }
+}
+
+fn push_bounded<T>(values: &mut VecDeque<T>, value: T) {
+    if values.len() == HISTORY_SIZE {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
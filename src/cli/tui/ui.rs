@@ -0,0 +1,178 @@
+use super::App;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::Line;
+use ratatui::widgets::canvas::{Canvas, Points};
+use ratatui::widgets::{Block, List, Paragraph, Sparkline};
+use std::collections::BTreeMap;
+
+// Colors of scripts in the space view, assigned in order of script IDs
+const SCRIPT_COLORS: [Color; 6] = [Color::Green, Color::Yellow, Color::Cyan, Color::Magenta, Color::Red, Color::Blue];
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [header, main, log, footer] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(8), Constraint::Length(10), Constraint::Length(1)]).areas(frame.area());
+    let [left, space] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+    let [counts, throughput, metrics] =
+        Layout::vertical([Constraint::Percentage(40), Constraint::Length(4), Constraint::Min(0)]).areas(left);
+
+    let script_counts = count_entities_by_script(app);
+
+    draw_header(frame, app, header);
+    draw_counts(frame, &script_counts, counts);
+    draw_throughput(frame, app, throughput);
+    draw_metrics(frame, app, metrics);
+    draw_space(frame, app, &script_counts, space);
+    draw_log(frame, app, log);
+
+    let keys = Line::from(" space: pause/run  s: step  +/-: faster/slower  q: quit").dark_gray();
+    frame.render_widget(keys, footer);
+}
+
+fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
+    let status = match (app.world.get_stop_reason(), app.paused) {
+        (Some(_), _) => "stopped".red(),
+        (None, true) => "paused".yellow(),
+        (None, false) => "running".green(),
+    };
+
+    let line = Line::from(vec![
+        format!(" World '{}'  ", app.world_name).bold(),
+        status,
+        format!(
+            "  time {}  steps {}  entities {}  pending messages {}  step duration {}  interval {}ms",
+            app.world.get_simulation_time(),
+            app.steps,
+            app.world.get_entities_count(),
+            app.world.get_pending_messages_count(),
+            app.step_duration,
+            app.interval.as_millis()
+        )
+        .into(),
+    ]);
+    frame.render_widget(Paragraph::new(line).block(Block::bordered()), area);
+}
+
+fn draw_counts(frame: &mut Frame, script_counts: &BTreeMap<String, usize>, area: Rect) {
+    let items: Vec<Line> = script_counts
+        .iter()
+        .enumerate()
+        .map(|(index, (script_id, count))| {
+            Line::from(vec![
+                "■ ".fg(SCRIPT_COLORS[index % SCRIPT_COLORS.len()]),
+                format!("{}: {}", script_id, count).into(),
+            ])
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(Block::bordered().title(" Entities by script ")), area);
+}
+
+fn draw_throughput(frame: &mut Frame, app: &App, area: Rect) {
+    let data = last_points(app.throughput.iter().copied().collect(), area.width);
+    let title = format!(" Messages per step: {} ", app.throughput.back().copied().unwrap_or_default());
+    let sparkline = Sparkline::default().block(Block::bordered().title(title)).data(&data).style(Style::new().cyan());
+    frame.render_widget(sparkline, area);
+}
+
+fn draw_metrics(frame: &mut Frame, app: &App, area: Rect) {
+    let names = app.charted_metrics();
+    if names.is_empty() {
+        let placeholder = Paragraph::new(" No metrics recorded yet").block(Block::bordered().title(" Metrics "));
+        frame.render_widget(placeholder, area);
+        return;
+    }
+
+    let areas = Layout::vertical(names.iter().map(|_| Constraint::Ratio(1, names.len() as u32))).split(area);
+    for (name, area) in names.iter().zip(areas.iter()) {
+        let Some(stats) = app.world.get_metrics_ref().compute_metric_stats(name) else {
+            let placeholder = Paragraph::new(" Not recorded yet").block(Block::bordered().title(format!(" {} ", name)));
+            frame.render_widget(placeholder, *area);
+            continue;
+        };
+
+        // Sparklines show non-negative bars, values are scaled between the minimum and maximum of the metric
+        let range = stats.max - stats.min;
+        let values: Vec<u64> = stats
+            .values_over_time
+            .iter()
+            .map(|(_, value)| if range > 0.0 { ((value - stats.min) / range * 100.0) as u64 + 1 } else { 1 })
+            .collect();
+        let data = last_points(values, area.width);
+
+        let last = stats.values_over_time.last().map(|(_, value)| *value).unwrap_or_default();
+        let title = format!(" {}: {} (min {}, max {}) ", name, last, stats.min, stats.max);
+        let sparkline = Sparkline::default().block(Block::bordered().title(title)).data(&data).style(Style::new().green());
+        frame.render_widget(sparkline, *area);
+    }
+}
+
+fn draw_space(frame: &mut Frame, app: &App, script_counts: &BTreeMap<String, usize>, area: Rect) {
+    let state = app.world.get_state_ref();
+    let space = state.get_space();
+    let block = Block::bordered().title(" Space ");
+
+    // Positions grouped by script so each script has its own color
+    let mut positions: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+    for (id, body) in space.get_bodies() {
+        if let Some(entity) = state.get_entities().get(id) {
+            positions.entry(entity.borrow().get_script_id().clone()).or_default().push((body.x, body.y));
+        }
+    }
+
+    if positions.is_empty() {
+        frame.render_widget(Paragraph::new(" No positioned entities").block(block), area);
+        return;
+    }
+
+    // Bounded spaces are shown whole, otherwise the view fits the entities
+    let (x_bounds, y_bounds) = match space.get_bounds() {
+        Some(bounds) => ([0.0, bounds.width], [0.0, bounds.height]),
+        None => {
+            let points = positions.values().flatten();
+            let (mut min_x, mut max_x, mut min_y, mut max_y) = (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY);
+            for (x, y) in points {
+                min_x = min_x.min(*x);
+                max_x = max_x.max(*x);
+                min_y = min_y.min(*y);
+                max_y = max_y.max(*y);
+            }
+            ([min_x - 1.0, max_x + 1.0], [min_y - 1.0, max_y + 1.0])
+        }
+    };
+
+    let canvas = Canvas::default()
+        .block(block)
+        .marker(Marker::Braille)
+        .x_bounds(x_bounds)
+        .y_bounds(y_bounds)
+        .paint(|ctx| {
+            for (script_id, coords) in &positions {
+                let index = script_counts.keys().position(|id| id == script_id).unwrap_or_default();
+                ctx.draw(&Points { coords, color: SCRIPT_COLORS[index % SCRIPT_COLORS.len()] });
+            }
+        });
+    frame.render_widget(canvas, area);
+}
+
+fn draw_log(frame: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = app.log.iter().skip(app.log.len().saturating_sub(visible)).map(|line| Line::from(line.as_str())).collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Log ")), area);
+}
+
+fn count_entities_by_script(app: &App) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for entity in app.world.get_state_ref().get_entities().values() {
+        *counts.entry(entity.borrow().get_script_id().clone()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// Most recent points that fit inside a bordered chart of the width
+fn last_points(mut values: Vec<u64>, width: u16) -> Vec<u64> {
+    let visible = width.saturating_sub(2) as usize;
+    values.drain(..values.len().saturating_sub(visible));
+    values
+}
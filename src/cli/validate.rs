@@ -0,0 +1,82 @@
+use crate::cli::load_world_cfg;
+use crate::core::World;
+use crate::core::{DiagnosticSeverity, ScriptDiagnostic};
+use std::path::Path;
+
+// Check every script, then the configuration and that the world can be created. Each script runs once, warnings
+// do not fail the validation.
+pub fn validate(path: &Path) -> Result<(), String> {
+    let world_cfg = load_world_cfg(path).map_err(|e| format!("{} is invalid: {}", path.display(), e))?;
+
+    let mut script_ids: Vec<&String> = world_cfg.script_library.keys().collect();
+    script_ids.sort();
+    let mut invalid_scripts = Vec::new();
+    for script_id in script_ids {
+        let diagnostics = world_cfg.script_library[script_id].validate_code(&world_cfg.globals);
+        print_diagnostics(path, script_id, &diagnostics);
+        if diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error) {
+            invalid_scripts.push(format!("'{}'", script_id));
+        }
+    }
+    if !invalid_scripts.is_empty() {
+        return Err(format!("Invalid scripts in {}: {}", path.display(), invalid_scripts.join(", ")));
+    }
+
+    World::new_with_checked_scripts(&world_cfg).map_err(|e| format!("{} is invalid: {}", path.display(), e))?;
+
+    println!("{} is valid", path.display());
+    Ok(())
+}
+
+// One line per diagnostic: file: script 'id' line:column: severity [check] message
+fn print_diagnostics(path: &Path, script_id: &str, diagnostics: &[ScriptDiagnostic]) {
+    for diagnostic in diagnostics {
+        let location = match (diagnostic.line, diagnostic.column) {
+            (Some(line), Some(column)) => format!(" {}:{}", line, column),
+            (Some(line), None) => format!(" {}", line),
+            _ => String::new(),
+        };
+        let severity = match diagnostic.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+        };
+
+        eprintln!(
+            "{}: script '{}'{}: {} [{}] {}",
+            path.display(),
+            script_id,
+            location,
+            severity,
+            diagnostic.check,
+            diagnostic.message
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let path = std::env::temp_dir().join(format!("vivarium_validate_{}.yaml", std::process::id()));
+        let write_world = |scripts: &[(&str, &str)]| {
+            let mut config = "name: validated_world\nentities: []\nscript_library:\n".to_string();
+            for (id, update) in scripts {
+                config.push_str(&format!(
+                    "  {id}:\n    id: {id}\n    kind: lua\n    script: |\n      function update(current_time, msgs) {update} end\n      function get_state() return {{}} end\n      function set_state(state) end\n"
+                ));
+            }
+            std::fs::write(&path, config).unwrap();
+        };
+
+        write_world(&[("sheep", ""), ("wolf", "")]);
+        assert_eq!(validate(&path), Ok(()));
+
+        // Every invalid script is reported, in order of their IDs
+        write_world(&[("wolf", "return +"), ("sheep", ""), ("dog", "local x = ")]);
+        assert_eq!(validate(&path).unwrap_err(), format!("Invalid scripts in {}: 'dog', 'wolf'", path.display()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
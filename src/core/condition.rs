@@ -0,0 +1,290 @@
+use crate::core::errors::CoreError;
+use crate::core::scripting::lua::LuaPredicate;
+use crate::core::world::World;
+use rmcp::schemars;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[schemars(description = "Condition over the world that ends a run when it holds.")]
+pub enum StopConditionCfg {
+    #[schemars(description = "Compares the last recorded value of a metric. Holds only if the metric was recorded.")]
+    Metric { name: String, op: Comparison, value: f64 },
+    #[schemars(
+        description = "Compares a value in the state of an entity selected by a JSONPath, e.g. '$.energy' or '$.inventory[0].name'. Holds only if the entity and the value exist."
+    )]
+    EntityState {
+        entity_id: String,
+        path: String,
+        op: Comparison,
+        value: Value,
+    },
+    #[schemars(description = "Compares the number of entities, optionally only those with the script and/or the tag.")]
+    EntityCount {
+        #[serde(default)]
+        script_id: Option<String>,
+        #[serde(default)]
+        tag: Option<String>,
+        op: Comparison,
+        value: usize,
+    },
+    #[schemars(description = "Holds when no messages are waiting for delivery.")]
+    NoPendingMessages,
+    #[schemars(
+        description = "Lua expression or chunk returning a value, holds unless it is nil or false. It can use the read-only world API, e.g. '#world.find_entities({tag = \"sheep\"}) == 0'."
+    )]
+    Lua { predicate: String },
+}
+
+impl StopConditionCfg {
+    pub fn describe(&self) -> String {
+        match self {
+            StopConditionCfg::Metric { name, op, value } => format!("metric '{}' {} {}", name, op.symbol(), value),
+            StopConditionCfg::EntityState { entity_id, path, op, value } => {
+                format!("state of entity '{}' at {} {} {}", entity_id, path, op.symbol(), value)
+            }
+            StopConditionCfg::EntityCount { script_id, tag, op, value } => {
+                let mut selection = Vec::new();
+                if let Some(script_id) = script_id {
+                    selection.push(format!("script '{}'", script_id));
+                }
+                if let Some(tag) = tag {
+                    selection.push(format!("tag '{}'", tag));
+                }
+
+                match selection.is_empty() {
+                    true => format!("number of entities {} {}", op.symbol(), value),
+                    false => format!("number of entities with {} {} {}", selection.join(" and "), op.symbol(), value),
+                }
+            }
+            StopConditionCfg::NoPendingMessages => "no pending messages".to_string(),
+            StopConditionCfg::Lua { predicate } => format!("predicate '{}'", predicate),
+        }
+    }
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+
+    fn compare_numbers(&self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+
+    // Numbers and strings are ordered, other values can only be compared for equality
+    fn compare_values(&self, left: &Value, right: &Value) -> bool {
+        if let (Some(left), Some(right)) = (left.as_f64(), right.as_f64()) {
+            return self.compare_numbers(left, right);
+        }
+
+        match (self, left, right) {
+            (Comparison::Eq, left, right) => left == right,
+            (Comparison::Ne, left, right) => left != right,
+            (_, Value::String(left), Value::String(right)) => match self {
+                Comparison::Lt => left < right,
+                Comparison::Le => left <= right,
+                Comparison::Gt => left > right,
+                _ => left >= right,
+            },
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// Parse a JSONPath with names and indices only: $.a.b, $['a'][0]
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, CoreError> {
+    let invalid = |reason: &str| CoreError::DeserializationError(format!("Invalid JSONPath '{}': {}", path, reason));
+
+    let mut rest = path.strip_prefix('$').ok_or_else(|| invalid("must start with '$'"))?;
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            if end == 0 {
+                return Err(invalid("empty name"));
+            }
+            segments.push(PathSegment::Key(after_dot[..end].to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(|| invalid("missing ']'"))?;
+            let selector = &after_bracket[..end];
+            let quoted = selector
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| selector.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+
+            match quoted {
+                Some(key) => segments.push(PathSegment::Key(key.to_string())),
+                None => segments.push(PathSegment::Index(
+                    selector.parse().map_err(|_| invalid("only names and indices are supported"))?,
+                )),
+            }
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(invalid("expected '.' or '['"));
+        }
+    }
+
+    Ok(segments)
+}
+
+fn select<'a>(value: &'a Value, path: &[PathSegment]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, segment| match segment {
+        PathSegment::Key(key) => value.get(key),
+        PathSegment::Index(index) => value.get(index),
+    })
+}
+
+enum StopCondition {
+    Metric { name: String, op: Comparison, value: f64 },
+    EntityState { entity_id: String, path: Vec<PathSegment>, op: Comparison, value: Value },
+    EntityCount { script_id: Option<String>, tag: Option<String>, op: Comparison, value: usize },
+    NoPendingMessages,
+    Lua(LuaPredicate),
+}
+
+// Conditions prepared for repeated checks against a world
+pub struct StopConditions {
+    conditions: Vec<(StopConditionCfg, StopCondition)>,
+}
+
+impl StopConditions {
+    pub fn new(world: &World, cfgs: Vec<StopConditionCfg>) -> Result<Self, CoreError> {
+        let mut conditions = Vec::new();
+        for cfg in cfgs {
+            let condition = match &cfg {
+                StopConditionCfg::Metric { name, op, value } => StopCondition::Metric {
+                    name: name.clone(),
+                    op: *op,
+                    value: *value,
+                },
+                StopConditionCfg::EntityState { entity_id, path, op, value } => StopCondition::EntityState {
+                    entity_id: entity_id.clone(),
+                    path: parse_path(path)?,
+                    op: *op,
+                    value: value.clone(),
+                },
+                StopConditionCfg::EntityCount { script_id, tag, op, value } => StopCondition::EntityCount {
+                    script_id: script_id.clone(),
+                    tag: tag.clone(),
+                    op: *op,
+                    value: *value,
+                },
+                StopConditionCfg::NoPendingMessages => StopCondition::NoPendingMessages,
+                StopConditionCfg::Lua { predicate } => StopCondition::Lua(world.create_predicate(predicate)?),
+            };
+            conditions.push((cfg, condition));
+        }
+
+        Ok(StopConditions { conditions })
+    }
+
+    // Index of the first condition that holds, if any
+    pub fn check(&self, world: &World) -> Result<Option<usize>, CoreError> {
+        for (index, (_, condition)) in self.conditions.iter().enumerate() {
+            if holds(condition, world)? {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get_cfg(&self, index: usize) -> Option<&StopConditionCfg> {
+        self.conditions.get(index).map(|(cfg, _)| cfg)
+    }
+}
+
+fn holds(condition: &StopCondition, world: &World) -> Result<bool, CoreError> {
+    let result = match condition {
+        StopCondition::Metric { name, op, value } => world
+            .get_metrics_ref()
+            .get_last_value(name)
+            .is_some_and(|last| op.compare_numbers(last, *value)),
+        StopCondition::EntityState { entity_id, path, op, value } => {
+            if !world.get_state_ref().get_entities().contains_key(entity_id) {
+                return Ok(false);
+            }
+
+            let state = Value::Object(world.get_entity_state(entity_id)?);
+            select(&state, path).is_some_and(|selected| op.compare_values(selected, value))
+        }
+        StopCondition::EntityCount { script_id, tag, op, value } => {
+            let state = world.get_state_ref();
+            let count = match (script_id, tag) {
+                (None, Some(tag)) => state.get_tagged_entities(tag).len(),
+                _ => state
+                    .filter_entities(|(_, entity)| {
+                        let entity = entity.borrow();
+                        script_id.as_ref().is_none_or(|script_id| entity.get_script_id() == script_id)
+                            && tag.as_ref().is_none_or(|tag| entity.has_tag(tag))
+                    })
+                    .len(),
+            };
+            op.compare_numbers(count as f64, *value as f64)
+        }
+        StopCondition::NoPendingMessages => world.get_pending_messages_count() == 0,
+        StopCondition::Lua(predicate) => world.evaluate_predicate(predicate)?,
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paths() {
+        let state = json!({"energy": 3, "inventory": [{"name": "wool"}], "odd key": true});
+
+        let path = parse_path("$.inventory[0]['name']").unwrap();
+        assert_eq!(select(&state, &path), Some(&json!("wool")));
+        assert_eq!(select(&state, &parse_path("$['odd key']").unwrap()), Some(&json!(true)));
+        assert_eq!(select(&state, &parse_path("$.missing").unwrap()), None);
+
+        assert!(parse_path("energy").is_err());
+        assert!(parse_path("$.inventory[*]").is_err());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(Comparison::Eq.compare_values(&json!(1), &json!(1.0)));
+        assert!(Comparison::Lt.compare_values(&json!("a"), &json!("b")));
+        assert!(Comparison::Ne.compare_values(&json!([1]), &json!([2])));
+        assert!(!Comparison::Gt.compare_values(&json!(true), &json!(false)));
+    }
+}
@@ -3,13 +3,25 @@ use crate::core::messaging::Command;
 use crate::core::messaging::{JSONObject, Message};
 use crate::core::scripting::lua::LuaScriptController;
 use crate::core::world::WorldState;
-use crate::core::world_config::ScriptCfg;
+use crate::core::world_config::{EntityCfg, ScriptCfg};
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::rc::Rc;
 
+// Read-only view of an entity, used for state queries of other entities
+#[derive(Debug, Clone)]
+pub struct EntityView {
+    pub script_id: String,
+    pub tags: Vec<String>,
+    pub state: JSONObject,
+}
+
 pub struct Entity {
     script_id: String,
+    parameters: JSONObject, // Overrides of parameters declared by the script
     lua_controller: LuaScriptController,
+    subscriptions: BTreeSet<String>, // Topics the entity is subscribed to, indexed by the message bus
+    tags: BTreeSet<String>,          // Tags (roles, groups) used for addressing messages
 }
 
 impl Entity {
@@ -17,10 +29,16 @@ impl Entity {
         id: String,
         script_id: String,
         script: ScriptCfg,
+        parameters: JSONObject,
         initial_state: Option<JSONObject>,
         world_state: Rc<RefCell<WorldState>>,
     ) -> Result<Self, CoreError> {
-        let controller_result = LuaScriptController::new(id.clone(), &script.script, world_state);
+        let controller_result = LuaScriptController::new(
+            id.clone(),
+            &script.script,
+            &script.resolve_parameters(&parameters),
+            world_state,
+        );
         if let Err(e) = &controller_result {
             return Err(CoreError::EntityCreation {
                 id,
@@ -36,7 +54,10 @@ impl Entity {
 
         Ok(Entity {
             script_id: script_id.clone(),
+            parameters,
             lua_controller,
+            subscriptions: BTreeSet::new(),
+            tags: BTreeSet::new(),
         })
     }
 
@@ -56,7 +77,67 @@ impl Entity {
         &mut self.lua_controller
     }
 
+    // Replace the script of the entity, messages not processed yet are kept
+    pub fn change_script(&mut self, script_id: String, parameters: JSONObject, mut lua_controller: LuaScriptController) {
+        for message in self.lua_controller.take_messages() {
+            lua_controller.push_message(message);
+        }
+
+        self.script_id = script_id;
+        self.parameters = parameters;
+        self.lua_controller = lua_controller;
+    }
+
+    pub fn get_parameters(&self) -> &JSONObject {
+        &self.parameters
+    }
+
     pub fn get_script_id(&self) -> &String {
         &self.script_id
     }
+
+    pub fn subscribe(&mut self, topic: &str) {
+        self.subscriptions.insert(topic.to_string());
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str) {
+        self.subscriptions.remove(topic);
+    }
+
+    pub fn add_tag(&mut self, tag: &str) {
+        self.tags.insert(tag.to_string());
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.remove(tag);
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn get_tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn create_view(&self) -> Result<EntityView, CoreError> {
+        Ok(EntityView {
+            script_id: self.script_id.clone(),
+            tags: self.tags.iter().cloned().collect(),
+            state: self.lua_controller.get_state()?,
+        })
+    }
+
+    // Create configuration of the entity with its current state, used for snapshots
+    pub fn create_cfg(&self, id: &str) -> Result<EntityCfg, CoreError> {
+        Ok(EntityCfg {
+            id: id.to_string(),
+            script_id: self.script_id.clone(),
+            initial_state: Some(self.lua_controller.get_state()?),
+            subscriptions: self.subscriptions.iter().cloned().collect(),
+            tags: self.tags.iter().cloned().collect(),
+            parameters: self.parameters.clone(),
+            ..Default::default()
+        })
+    }
 }
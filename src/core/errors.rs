@@ -1,3 +1,5 @@
+use crate::core::scripting::lua::ScriptDiagnostic;
+use std::collections::BTreeMap;
 use std::fmt;
 
 #[derive(Debug)]
@@ -14,7 +16,17 @@ pub enum CoreError {
     SerializationError(String),
     DeserializationError(String),
     SnapshotError(String),
-    WorldCapacityExceeded { capacity: usize },
+    SimulationStopped { reason: String },
+    TimeOverflow { time: u64, delay: u64 },
+    QuotaExceeded { quota: String, limit: usize },
+    ScriptNotFound { script_id: String },
+    ScriptAlreadyExists { script_id: String },
+    ScriptValidation { script_id: String, diagnostics: Vec<ScriptDiagnostic> },
+    ScriptInUse { script_id: String, users: Vec<String> },
+    ScriptMigration { script_id: String, failures: BTreeMap<String, String> }, // Entity ID to its failure
+    JobNotFound { id: String },
+    InvalidJobState { job_id: String, message: String },
+    WorldThread { message: String }, // The thread owning a world failed to run an operation
 }
 
 impl fmt::Display for CoreError {
@@ -31,7 +43,33 @@ impl fmt::Display for CoreError {
             CoreError::SnapshotError(message) => write!(f, "Snapshot error: {}", message),
             CoreError::WorldAlreadyExists => write!(f, "World already exists"),
             CoreError::WorldNotFound { name } => write!(f, "World '{}' not found", name),
-            CoreError::WorldCapacityExceeded { capacity } => write!(f, "World capacity exceeded: {}", capacity),
+            CoreError::SimulationStopped { reason } => write!(f, "Simulation has ended: {}", reason),
+            CoreError::TimeOverflow { time, delay } => write!(f, "Delay {} from simulation time {} is beyond the largest time", delay, time),
+            CoreError::QuotaExceeded { quota, limit } => {
+                write!(f, "Quota '{}' exceeded: limit is {}", quota, limit)
+            }
+            CoreError::ScriptNotFound { script_id } => write!(f, "Script '{}' not found in script library", script_id),
+            CoreError::ScriptAlreadyExists { script_id } => write!(f, "Script '{}' already exists in script library", script_id),
+            CoreError::ScriptValidation { script_id, diagnostics } => {
+                let diagnostics: Vec<String> = diagnostics
+                    .iter()
+                    .map(|d| match d.line {
+                        Some(line) => format!("line {}: {}", line, d.message),
+                        None => d.message.clone(),
+                    })
+                    .collect();
+                write!(f, "Script '{}' is invalid: {}", script_id, diagnostics.join("; "))
+            }
+            CoreError::ScriptInUse { script_id, users } => {
+                write!(f, "Script '{}' is still used by: {}", script_id, users.join(", "))
+            }
+            CoreError::ScriptMigration { script_id, failures } => {
+                let failures: Vec<String> = failures.iter().map(|(id, message)| format!("{}: {}", id, message)).collect();
+                write!(f, "Failed to migrate entities of script '{}', no changes were made: {}", script_id, failures.join("; "))
+            }
+            CoreError::JobNotFound { id } => write!(f, "Job '{}' not found", id),
+            CoreError::InvalidJobState { job_id, message } => write!(f, "Job '{}': {}", job_id, message),
+            CoreError::WorldThread { message } => write!(f, "World thread error: {}", message),
         }
     }
 }
@@ -0,0 +1,329 @@
+use crate::core::errors::CoreError;
+use crate::core::world_config::{FieldCfg, GridCfg, Neighborhood};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub type Cell = (u32, u32);
+
+struct Field {
+    values: Vec<f64>, // Values of cells, row by row
+    diffusion: f64,
+    decay: f64,
+}
+
+// Discrete 2D grid where entities occupy cells and cells hold numeric fields.
+pub struct Grid {
+    width: u32,
+    height: u32,
+    wrap: bool,
+    neighborhood: Neighborhood,
+    fields: BTreeMap<String, Field>,
+    neighbor_indices: Vec<Vec<usize>>, // Indices of neighbor cells of each cell, used for diffusion
+    positions: HashMap<String, Cell>,         // Entity ID to its cell
+    occupants: HashMap<Cell, BTreeSet<String>>, // Cell to IDs of entities in it
+}
+
+impl Grid {
+    pub fn from_cfg(cfg: &GridCfg) -> Result<Self, CoreError> {
+        let cells_count = cfg.validate()?;
+        let mut fields = BTreeMap::new();
+
+        for (name, field_cfg) in &cfg.fields {
+            let values = match &field_cfg.values {
+                Some(values) => values.clone(),
+                None => vec![field_cfg.initial; cells_count],
+            };
+
+            fields.insert(
+                name.clone(),
+                Field {
+                    values,
+                    diffusion: field_cfg.diffusion,
+                    decay: field_cfg.decay,
+                },
+            );
+        }
+
+        let mut grid = Grid {
+            width: cfg.width,
+            height: cfg.height,
+            wrap: cfg.wrap,
+            neighborhood: cfg.neighborhood.clone(),
+            fields,
+            neighbor_indices: Vec::new(),
+            positions: HashMap::new(),
+            occupants: HashMap::new(),
+        };
+
+        grid.neighbor_indices = (0..grid.height)
+            .flat_map(|y| (0..grid.width).map(move |x| (x, y)))
+            .map(|cell| {
+                grid.get_neighbor_cells(cell)
+                    .into_iter()
+                    .map(|neighbor| grid.index(neighbor))
+                    .collect()
+            })
+            .collect();
+
+        Ok(grid)
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    // Normalize coordinates to a cell, wrapping around if enabled. Returns None if out of bounds.
+    pub fn normalize(&self, x: i64, y: i64) -> Option<Cell> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        if self.wrap {
+            return Some((x.rem_euclid(width) as u32, y.rem_euclid(height) as u32));
+        }
+
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return None;
+        }
+
+        Some((x as u32, y as u32))
+    }
+
+    // Cells adjacent to the given cell according to the neighborhood
+    pub fn get_neighbor_cells(&self, cell: Cell) -> Vec<Cell> {
+        let offsets: &[(i64, i64)] = match self.neighborhood {
+            Neighborhood::VonNeumann => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighborhood::Moore => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        };
+
+        let mut neighbors = Vec::new();
+        for (dx, dy) in offsets {
+            if let Some(neighbor) = self.normalize(cell.0 as i64 + dx, cell.1 as i64 + dy)
+                && neighbor != cell
+                && !neighbors.contains(&neighbor)
+            {
+                neighbors.push(neighbor);
+            }
+        }
+        neighbors
+    }
+
+    // Cells are within the grid and the number of cells is capped, so the index fits
+    fn index(&self, cell: Cell) -> usize {
+        cell.1 as usize * self.width as usize + cell.0 as usize
+    }
+
+    pub fn get_values(&self, cell: Cell) -> BTreeMap<String, f64> {
+        let index = self.index(cell);
+        self.fields
+            .iter()
+            .map(|(name, field)| (name.clone(), field.values[index]))
+            .collect()
+    }
+
+    // Set value of a field in a cell. Unknown fields are created with zero values.
+    pub fn set_value(&mut self, cell: Cell, field: &str, value: f64) {
+        let index = self.index(cell);
+        let cells_count = self.width as usize * self.height as usize;
+
+        self.fields
+            .entry(field.to_string())
+            .or_insert_with(|| Field {
+                values: vec![0.0; cells_count],
+                diffusion: 0.0,
+                decay: 0.0,
+            })
+            .values[index] = value;
+    }
+
+    pub fn get_field(&self, field: &str) -> Option<&Vec<f64>> {
+        self.fields.get(field).map(|f| &f.values)
+    }
+
+    pub fn list_field_names(&self) -> Vec<String> {
+        self.fields.keys().cloned().collect()
+    }
+
+    pub fn get_position(&self, entity_id: &str) -> Option<Cell> {
+        self.positions.get(entity_id).copied()
+    }
+
+    pub fn get_occupants(&self, cell: Cell) -> Vec<String> {
+        self.occupants
+            .get(&cell)
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn move_entity(&mut self, entity_id: &str, cell: Cell) {
+        self.remove_entity(entity_id);
+        self.positions.insert(entity_id.to_string(), cell);
+        self.occupants
+            .entry(cell)
+            .or_default()
+            .insert(entity_id.to_string());
+    }
+
+    pub fn remove_entity(&mut self, entity_id: &str) {
+        if let Some(cell) = self.positions.remove(entity_id)
+            && let Some(ids) = self.occupants.get_mut(&cell)
+        {
+            ids.remove(entity_id);
+            if ids.is_empty() {
+                self.occupants.remove(&cell);
+            }
+        }
+    }
+
+    // Apply decay and diffusion rules of all fields
+    pub fn step(&mut self) {
+        let neighbors = &self.neighbor_indices;
+
+        for field in self.fields.values_mut() {
+            if field.decay > 0.0 {
+                for value in field.values.iter_mut() {
+                    *value *= 1.0 - field.decay;
+                }
+            }
+
+            if field.diffusion > 0.0 {
+                // Each cell spreads part of its value evenly to its neighbors
+                let mut diffused = field.values.clone();
+                for (index, value) in field.values.iter().enumerate() {
+                    if neighbors[index].is_empty() {
+                        continue;
+                    }
+
+                    let outflow = value * field.diffusion;
+                    diffused[index] -= outflow;
+                    for neighbor in &neighbors[index] {
+                        diffused[*neighbor] += outflow / neighbors[index].len() as f64;
+                    }
+                }
+                field.values = diffused;
+            }
+        }
+    }
+
+    // Create configuration with current values of the fields, used for snapshots
+    pub fn create_cfg(&self) -> GridCfg {
+        GridCfg {
+            width: self.width,
+            height: self.height,
+            wrap: self.wrap,
+            neighborhood: self.neighborhood.clone(),
+            fields: self
+                .fields
+                .iter()
+                .map(|(name, field)| {
+                    (
+                        name.clone(),
+                        FieldCfg {
+                            initial: 0.0,
+                            values: Some(field.values.clone()),
+                            diffusion: field.diffusion,
+                            decay: field.decay,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_grid(wrap: bool, neighborhood: Neighborhood, diffusion: f64, decay: f64) -> Grid {
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            "pheromone".to_string(),
+            FieldCfg {
+                initial: 0.0,
+                values: None,
+                diffusion,
+                decay,
+            },
+        );
+
+        Grid::from_cfg(&GridCfg {
+            width: 5,
+            height: 5,
+            wrap,
+            neighborhood,
+            fields,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_neighborhoods() {
+        let bounded = create_grid(false, Neighborhood::Moore, 0.0, 0.0);
+        assert_eq!(bounded.get_neighbor_cells((0, 0)).len(), 3);
+        assert_eq!(bounded.get_neighbor_cells((2, 2)).len(), 8);
+        assert_eq!(bounded.normalize(5, 0), None);
+
+        let wrapped = create_grid(true, Neighborhood::VonNeumann, 0.0, 0.0);
+        assert_eq!(wrapped.get_neighbor_cells((0, 0)), vec![(0, 4), (4, 0), (1, 0), (0, 1)]);
+        assert_eq!(wrapped.normalize(-1, 6), Some((4, 1)));
+    }
+
+    #[test]
+    fn test_diffusion_and_decay() {
+        let mut grid = create_grid(false, Neighborhood::VonNeumann, 0.4, 0.0);
+        grid.set_value((2, 2), "pheromone", 10.0);
+        grid.step();
+
+        assert!((grid.get_values((2, 2))["pheromone"] - 6.0).abs() < 1e-9);
+        assert!((grid.get_values((2, 1))["pheromone"] - 1.0).abs() < 1e-9);
+
+        // Diffusion conserves total amount
+        let total: f64 = grid.get_field("pheromone").unwrap().iter().sum();
+        assert!((total - 10.0).abs() < 1e-9);
+
+        let mut grid = create_grid(false, Neighborhood::Moore, 0.0, 0.5);
+        grid.set_value((0, 0), "pheromone", 10.0);
+        grid.step();
+        assert!((grid.get_values((0, 0))["pheromone"] - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_occupants() {
+        let mut grid = create_grid(false, Neighborhood::Moore, 0.0, 0.0);
+        grid.move_entity("sheep_1", (1, 1));
+        grid.move_entity("sheep_2", (1, 1));
+        grid.move_entity("sheep_1", (2, 1));
+
+        assert_eq!(grid.get_occupants((1, 1)), vec!["sheep_2"]);
+        assert_eq!(grid.get_position("sheep_1"), Some((2, 1)));
+
+        grid.remove_entity("sheep_2");
+        assert!(grid.get_occupants((1, 1)).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_grids() {
+        let cfg = |width, height, diffusion| GridCfg {
+            width,
+            height,
+            wrap: false,
+            neighborhood: Neighborhood::Moore,
+            fields: BTreeMap::from([("grass".to_string(), FieldCfg { initial: 0.0, values: None, diffusion, decay: 0.0 })]),
+        };
+
+        assert!(Grid::from_cfg(&cfg(u32::MAX, u32::MAX, 0.0)).is_err());
+        assert!(Grid::from_cfg(&cfg(0, 5, 0.0)).is_err());
+        assert!(Grid::from_cfg(&cfg(5, 5, 1.5)).is_err());
+        assert!(Grid::from_cfg(&cfg(5, 5, f64::NAN)).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+use crate::core::condition::{StopConditionCfg, StopConditions};
+use crate::core::errors::CoreError;
+use crate::core::registry::{SharedWorld, panic_message};
+use crate::core::world::World;
+use rmcp::schemars;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+thread_local! {
+    // Stop conditions of the jobs of the world owning this thread, by job ID. Lua predicates share the state of the
+    // world, so they stay on its thread between the steps of a job.
+    static JOB_CONDITIONS: RefCell<HashMap<String, StopConditions>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunOptions {
+    #[schemars(description = "The duration of each step in seconds")]
+    pub step_duration: u64,
+    #[schemars(description = "The number of steps to run")]
+    pub steps: u32,
+    #[serde(default)]
+    #[schemars(description = "Conditions checked before each step and after the last one, the run ends when any of them holds")]
+    pub conditions: Vec<StopConditionCfg>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Paused,    // Waits before the next step until resumed
+    Completed, // All steps run, a condition holds or the director ended the run
+    Cancelled,
+    Failed, // A step failed, see error
+}
+
+impl JobState {
+    fn is_finished(&self) -> bool {
+        matches!(self, JobState::Completed | JobState::Cancelled | JobState::Failed)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, schemars::JsonSchema)]
+pub struct JobStatus {
+    #[schemars(description = "ID of the job")]
+    pub job_id: String,
+    #[schemars(description = "Name of the world the job runs")]
+    pub world_name: String,
+    #[schemars(description = "State of the job: running, paused, completed, cancelled or failed")]
+    pub state: JobState,
+    #[schemars(description = "Number of steps run so far")]
+    pub steps: u32,
+    #[schemars(description = "Number of steps requested")]
+    pub total_steps: u32,
+    #[schemars(description = "Simulation time after the last step run")]
+    pub simulation_time: u64,
+    #[schemars(description = "Number of messages delivered during the job")]
+    pub number_of_messages: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Why the job completed before running all steps")]
+    pub stop_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Error of the failed step")]
+    pub error: Option<String>,
+}
+
+struct Job {
+    status: Mutex<JobStatus>,
+    cancel_requested: AtomicBool,
+    state_changed: Condvar, // Wakes a paused job on resume or cancel
+}
+
+impl Job {
+    // Wait while the job is paused, true if it was cancelled
+    fn wait_until_runnable(&self) -> bool {
+        let mut status = self.status.lock().unwrap();
+        loop {
+            if self.cancel_requested.load(Ordering::SeqCst) {
+                return true;
+            }
+            if status.state != JobState::Paused {
+                return false;
+            }
+            status = self.state_changed.wait(status).unwrap();
+        }
+    }
+
+    fn record_step(&self, simulation_time: u64, delivered_messages: usize) {
+        let mut status = self.status.lock().unwrap();
+        status.steps += 1;
+        status.simulation_time = simulation_time;
+        status.number_of_messages += delivered_messages;
+    }
+
+    fn finish(&self, result: Result<(JobState, Option<String>), CoreError>) {
+        let mut status = self.status.lock().unwrap();
+        match result {
+            Ok((state, stop_reason)) => {
+                status.state = state;
+                status.stop_reason = stop_reason;
+            }
+            Err(e) => {
+                status.state = JobState::Failed;
+                status.error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+// Runs of worlds in background threads, controlled by job ID
+pub struct JobManager {
+    jobs: RwLock<HashMap<String, Arc<Job>>>,
+    next_job_id: AtomicU64,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        JobManager {
+            jobs: RwLock::new(HashMap::new()),
+            next_job_id: AtomicU64::new(1),
+        }
+    }
+
+    // Start running the world in the background, a world can be run by one job at a time
+    pub fn start(&self, world_name: &str, world: Arc<SharedWorld>, options: RunOptions) -> Result<String, CoreError> {
+        let mut jobs = self.jobs.write().unwrap();
+        if let Some((job_id, _)) = jobs.iter().find(|(_, job)| {
+            let status = job.status.lock().unwrap();
+            status.world_name == world_name && !status.state.is_finished()
+        }) {
+            return Err(CoreError::InvalidJobState {
+                job_id: job_id.clone(),
+                message: format!("World '{}' is already run by this job", world_name),
+            });
+        }
+
+        // Invalid conditions are reported now rather than as a failed job
+        let job_id = format!("job-{}", self.next_job_id.fetch_add(1, Ordering::Relaxed));
+        let (conditions_job_id, conditions) = (job_id.clone(), options.conditions.clone());
+        let simulation_time = world.with(move |world| {
+            let conditions = StopConditions::new(world, conditions)?;
+            JOB_CONDITIONS.with_borrow_mut(|jobs| jobs.insert(conditions_job_id, conditions));
+            Ok::<_, CoreError>(world.get_simulation_time())
+        })??;
+
+        let job = Arc::new(Job {
+            status: Mutex::new(JobStatus {
+                job_id: job_id.clone(),
+                world_name: world_name.to_string(),
+                state: JobState::Running,
+                steps: 0,
+                total_steps: options.steps,
+                simulation_time,
+                number_of_messages: 0,
+                stop_reason: None,
+                error: None,
+            }),
+            cancel_requested: AtomicBool::new(false),
+            state_changed: Condvar::new(),
+        });
+
+        let (thread_job, thread_job_id) = (job.clone(), job_id.clone());
+        let thread_world = world.clone();
+        let started = std::thread::Builder::new()
+            .name(job_id.clone())
+            .spawn(move || thread_job.finish(run_job(&thread_job, &thread_job_id, &thread_world, options)));
+        if let Err(e) = started {
+            release_conditions(&world, &job_id);
+            return Err(CoreError::InvalidJobState {
+                job_id,
+                message: format!("Failed to start job thread: {}", e),
+            });
+        }
+
+        jobs.insert(job_id.clone(), job);
+        Ok(job_id)
+    }
+
+    pub fn get_status(&self, job_id: &str) -> Result<JobStatus, CoreError> {
+        Ok(self.get_job(job_id)?.status.lock().unwrap().clone())
+    }
+
+    // The job pauses before its next step
+    pub fn pause(&self, job_id: &str) -> Result<JobStatus, CoreError> {
+        self.change_state(job_id, JobState::Running, JobState::Paused)
+    }
+
+    pub fn resume(&self, job_id: &str) -> Result<JobStatus, CoreError> {
+        self.change_state(job_id, JobState::Paused, JobState::Running)
+    }
+
+    // The job stops before its next step, steps already run are kept
+    pub fn cancel(&self, job_id: &str) -> Result<JobStatus, CoreError> {
+        let job = self.get_job(job_id)?;
+        let status = job.status.lock().unwrap();
+        if status.state.is_finished() {
+            return Err(CoreError::InvalidJobState {
+                job_id: job_id.to_string(),
+                message: format!("Job has already finished as {:?}", status.state),
+            });
+        }
+
+        job.cancel_requested.store(true, Ordering::SeqCst);
+        job.state_changed.notify_all();
+        Ok(status.clone())
+    }
+
+    fn change_state(&self, job_id: &str, from: JobState, to: JobState) -> Result<JobStatus, CoreError> {
+        let job = self.get_job(job_id)?;
+        let mut status = job.status.lock().unwrap();
+        if status.state != from || job.cancel_requested.load(Ordering::SeqCst) {
+            return Err(CoreError::InvalidJobState {
+                job_id: job_id.to_string(),
+                message: format!("Job is {:?}, expected {:?}", status.state, from),
+            });
+        }
+
+        status.state = to;
+        job.state_changed.notify_all();
+        Ok(status.clone())
+    }
+
+    fn get_job(&self, job_id: &str) -> Result<Arc<Job>, CoreError> {
+        self.jobs
+            .read()
+            .unwrap()
+            .get(job_id)
+            .cloned()
+            .ok_or_else(|| CoreError::JobNotFound { id: job_id.to_string() })
+    }
+}
+
+// A panic of the job thread fails the job like an error of a step
+fn run_job(job: &Job, job_id: &str, world: &SharedWorld, options: RunOptions) -> Result<(JobState, Option<String>), CoreError> {
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| run_steps(job, job_id, world, options.step_duration, options.steps)))
+        .unwrap_or_else(|panic| {
+            Err(CoreError::InvalidJobState {
+                job_id: job_id.to_string(),
+                message: format!("Job panicked: {}", panic_message(panic.as_ref())),
+            })
+        });
+
+    release_conditions(world, job_id);
+    result
+}
+
+fn release_conditions(world: &SharedWorld, job_id: &str) {
+    let job_id = job_id.to_string();
+    // Fails only if the world thread has ended, its conditions are gone with it
+    let _ = world.with(move |_| JOB_CONDITIONS.with_borrow_mut(|jobs| jobs.remove(&job_id)).map(drop));
+}
+
+enum StepOutcome {
+    Stop(Option<String>), // Reason why the job completed, None if all steps were run
+    Ran { simulation_time: u64, delivered_messages: usize },
+}
+
+fn run_steps(job: &Job, job_id: &str, world: &SharedWorld, step_duration: u64, steps: u32) -> Result<(JobState, Option<String>), CoreError> {
+    for step in 0..=steps {
+        if job.wait_until_runnable() {
+            return Ok((JobState::Cancelled, None));
+        }
+
+        // One step per operation, so that the world can be queried while the job runs
+        let job_id = job_id.to_string();
+        match world.with(move |world| run_step(world, &job_id, step_duration, step == steps))?? {
+            StepOutcome::Stop(reason) => return Ok((JobState::Completed, reason)),
+            StepOutcome::Ran { simulation_time, delivered_messages } => job.record_step(simulation_time, delivered_messages),
+        }
+    }
+
+    Ok((JobState::Completed, None))
+}
+
+// Check the conditions and run a step unless the run ends, on the thread of the world
+fn run_step(world: &mut World, job_id: &str, step_duration: u64, is_last: bool) -> Result<StepOutcome, CoreError> {
+    let condition = JOB_CONDITIONS.with_borrow(|jobs| match jobs.get(job_id) {
+        Some(conditions) => Ok(conditions
+            .check(world)?
+            .and_then(|index| conditions.get_cfg(index))
+            .map(|cfg| cfg.describe())),
+        None => Err(CoreError::InvalidJobState {
+            job_id: job_id.to_string(),
+            message: "Stop conditions of the job are missing".to_string(),
+        }),
+    })?;
+    if let Some(description) = condition {
+        return Ok(StepOutcome::Stop(Some(format!("Condition holds: {}", description))));
+    }
+    if let Some(reason) = world.get_stop_reason() {
+        return Ok(StepOutcome::Stop(Some(format!("Stopped by director: {}", reason))));
+    }
+    if is_last {
+        return Ok(StepOutcome::Stop(None));
+    }
+
+    let result = world.update(step_duration)?;
+    Ok(StepOutcome::Ran {
+        simulation_time: world.get_simulation_time(),
+        delivered_messages: result.delivered_messages.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::world_config::{EntityCfg, WorldCfg};
+    use std::time::{Duration, Instant};
+
+    fn create_world() -> Arc<SharedWorld> {
+        let mut world_cfg = WorldCfg::new("job_world".to_string());
+        world_cfg.add_script(
+            "counter".to_string(),
+            r#"
+            local count = 0
+            function update(current_time, msgs) count = count + 1 end
+            function get_state() return {count = count} end
+            function set_state(state) count = state.count end
+            "#
+            .to_string(),
+        );
+        world_cfg.entities.push(EntityCfg {
+            id: "counter".to_string(),
+            script_id: "counter".to_string(),
+            ..Default::default()
+        });
+
+        Arc::new(SharedWorld::spawn(move || World::new(&world_cfg)).unwrap())
+    }
+
+    fn wait_for_finish(jobs: &JobManager, job_id: &str) -> JobStatus {
+        let start = Instant::now();
+        loop {
+            let status = jobs.get_status(job_id).unwrap();
+            if status.state.is_finished() || start.elapsed() > Duration::from_secs(10) {
+                return status;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_job_lifecycle() {
+        let jobs = JobManager::new();
+        let world = create_world();
+        let options = RunOptions {
+            step_duration: 1,
+            steps: 1_000_000,
+            conditions: Vec::new(),
+        };
+
+        let job_id = jobs.start("job_world", world.clone(), options.clone()).unwrap();
+        assert!(matches!(
+            jobs.start("job_world", world.clone(), options.clone()),
+            Err(CoreError::InvalidJobState { .. })
+        ));
+
+        jobs.pause(&job_id).unwrap();
+        assert!(jobs.pause(&job_id).is_err());
+        std::thread::sleep(Duration::from_millis(20));
+        let paused_steps = jobs.get_status(&job_id).unwrap().steps;
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(jobs.get_status(&job_id).unwrap().steps, paused_steps);
+
+        jobs.resume(&job_id).unwrap();
+        jobs.cancel(&job_id).unwrap();
+        let status = wait_for_finish(&jobs, &job_id);
+        assert_eq!(status.state, JobState::Cancelled);
+        assert!(jobs.cancel(&job_id).is_err());
+        assert_eq!(world.with(|world| world.get_simulation_time()).unwrap(), status.simulation_time);
+
+        assert!(matches!(jobs.get_status("job-0"), Err(CoreError::JobNotFound { .. })));
+    }
+
+    #[test]
+    fn test_job_conditions() {
+        let jobs = JobManager::new();
+        let conditions = serde_json::from_value(serde_json::json!([
+            {"kind": "entity_state", "entity_id": "counter", "path": "$.count", "op": "ge", "value": 5}
+        ]))
+        .unwrap();
+
+        let options = RunOptions {
+            step_duration: 2,
+            steps: 100,
+            conditions,
+        };
+        let job_id = jobs.start("job_world", create_world(), options).unwrap();
+        let status = wait_for_finish(&jobs, &job_id);
+        assert_eq!(status.state, JobState::Completed);
+        assert_eq!((status.steps, status.simulation_time), (5, 10));
+        assert!(status.stop_reason.unwrap().starts_with("Condition holds"));
+
+        let options = RunOptions {
+            step_duration: 1,
+            steps: 3,
+            conditions: Vec::new(),
+        };
+        let job_id = jobs.start("job_world", create_world(), options).unwrap();
+        let status = wait_for_finish(&jobs, &job_id);
+        assert_eq!((status.state, status.steps, status.stop_reason), (JobState::Completed, 3, None));
+    }
+}
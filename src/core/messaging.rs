@@ -1,39 +1,67 @@
-use std::{collections::BinaryHeap};
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
 
 use rmcp::schemars;
 use serde_json::{Map, Value};   
 pub type JSONObject = serde_json::Map<String, serde_json::Value>;
 
+// Senders of messages that are not entities, entity IDs cannot take them
+pub const DIRECTOR_ID: &str = "director";
+pub const TIMELINE_ID: &str = "timeline";
+pub const RESERVED_SENDER_IDS: [&str; 2] = [DIRECTOR_ID, TIMELINE_ID];
+
 pub struct MessageBus {
     messages: BinaryHeap<Message>,
+    subscriptions: HashMap<String, BTreeSet<String>>, // Topic to IDs of subscribed entities
+    next_message_id: u64,
 }
 
 impl MessageBus {
     pub fn new() -> Self {
         MessageBus {
             messages: BinaryHeap::new(),
+            subscriptions: HashMap::new(),
+            next_message_id: 1,
         }
     }
 
     pub fn schedule_message(
         &mut self,
-        sender: &String,
+        sender: &str,
         receiver: MessageReceiver,
         kind: String,
         content: JSONObject,
+        sent_at: u64,
         receive_at: u64,
     ) {
         let message = Message {
-            sender: sender.clone(),
+            id: self.next_message_id,
+            sender: sender.to_string(),
             receiver,
             content,
             kind,
+            sent_step: sent_at,
             receive_step: receive_at,
         };
 
+        self.next_message_id += 1;
+        self.messages.push(message);
+    }
+
+    // Schedule a message of a snapshot, keeping its ID
+    pub fn restore_message(&mut self, message: Message) {
+        self.next_message_id = self.next_message_id.max(message.id + 1);
         self.messages.push(message);
     }
 
+    // ID of the next scheduled message, IDs are unique within the world
+    pub fn get_next_message_id(&self) -> u64 {
+        self.next_message_id
+    }
+
+    pub fn set_next_message_id(&mut self, id: u64) {
+        self.next_message_id = self.next_message_id.max(id);
+    }
+
     // Retrieve one message scheduled for delivery at the current step
     // Returns None if no messages are deliverable at this step
     pub fn pop_deliverable_message(&mut self, current_time: u64) -> Option<Message> {
@@ -52,28 +80,69 @@ impl MessageBus {
     pub fn get_pending_messages_count(&self) -> usize {
         self.messages.len()
     }
+
+    pub fn subscribe(&mut self, topic: &str, entity_id: &str) {
+        self.subscriptions
+            .entry(topic.to_string())
+            .or_default()
+            .insert(entity_id.to_string());
+    }
+
+    pub fn unsubscribe(&mut self, topic: &str, entity_id: &str) {
+        if let Some(subscribers) = self.subscriptions.get_mut(topic) {
+            subscribers.remove(entity_id);
+            if subscribers.is_empty() {
+                self.subscriptions.remove(topic);
+            }
+        }
+    }
+
+    // Remove the entity from all topics, e.g. when it is destroyed
+    pub fn unsubscribe_all(&mut self, entity_id: &str) {
+        self.subscriptions.retain(|_, subscribers| {
+            subscribers.remove(entity_id);
+            !subscribers.is_empty()
+        });
+    }
+
+    // Get IDs of entities subscribed to the topic, in stable order
+    pub fn get_subscribers(&self, topic: &str) -> Vec<String> {
+        self.subscriptions
+            .get(topic)
+            .map(|subscribers| subscribers.iter().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Message {
+    #[serde(default)]
+    pub id: u64, // Unique within the world, in order of scheduling
     pub sender: String,
     pub receiver: MessageReceiver,
     pub content: Map<String, Value>,
     pub kind: String, // Kind of message (e.g., "HealthStatus", "TradeRequest", etc.)
+    #[serde(default)]
+    pub sent_step: u64, // Step at which the message was sent
     pub receive_step: u64, // Step at which the message should be received
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum MessageReceiver {
     Entity { id: String },               // Entity ID and Component TypeId
-    Radius2D { x: f32, y: f32, radius: f32 }, // Broadcast to all components of a given type within radius
+    Radius2D { x: f32, y: f32, radius: f32 }, // Broadcast to all positioned entities within radius, except the sender
+    Topic { topic: String },                  // Publish to all entities subscribed to the topic
+    Tag { tag: String },                      // Send to all entities with the tag
+    Group { name: String },                   // Send to all members of the group, groups are tags
+    Neighbors { id: String },                 // Send to all network neighbors of the entity
 }
 
 impl Eq for Message {}
 
+// Messages are identified by receive step and ID, consistent with their order
 impl PartialEq for Message {
     fn eq(&self, other: &Self) -> bool {
-        self.receive_step == other.receive_step
+        self.receive_step == other.receive_step && self.id == other.id
     }
 }
 
@@ -85,8 +154,8 @@ impl PartialOrd for Message {
 
 impl Ord for Message {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // Reverse order for min-heap behavior
-        other.receive_step.cmp(&self.receive_step)
+        // Reverse order for min-heap behavior, messages received in the same step are delivered in order of scheduling
+        other.receive_step.cmp(&self.receive_step).then(other.id.cmp(&self.id))
     }
 }
 
@@ -103,6 +172,21 @@ pub enum Command {
         delay: u64,
     },
     RecordMetric { name: String, value: f64 },
+    Subscribe { entity_id: String, topic: String },
+    Unsubscribe { entity_id: String, topic: String },
+    AddTag { entity_id: String, tag: String },
+    RemoveTag { entity_id: String, tag: String },
+    AddEdge { from: String, to: String },
+    RemoveEdge { from: String, to: String },
+    MoveTo { entity_id: String, x: i64, y: i64 },
+    SetCell { x: i64, y: i64, field: String, value: f64 },
+    SetPosition { entity_id: String, x: f64, y: f64 },
+    SetVelocity { entity_id: String, vx: f64, vy: f64 },
+    SetEntityState { entity_id: String, state: JSONObject },
+    StopSimulation { reason: String },
+    SetGlobal { key: String, value: serde_json::Value },
+    SpawnPopulation { population: crate::core::world_config::PopulationCfg },
+    ChangeScript { entity_id: String, script_id: String, state: Option<JSONObject> },
 }
 
 #[cfg(test)]
@@ -125,6 +209,7 @@ mod tests {
             MessageReceiver::Entity { id: "agent_1".to_string() },
             String::from("Greeting"),
             make_json("Hello"),
+            0,
             3,
         );
         bus.schedule_message(
@@ -132,6 +217,7 @@ mod tests {
             MessageReceiver::Entity { id: "agent_2".to_string() },
             String::from("Greeting"),
             make_json("Hi"),
+            0,
             3,
         );
         
@@ -140,6 +226,7 @@ mod tests {
             MessageReceiver::Entity { id: "agent_2".to_string() },
             String::from("Greeting"),
             make_json("Hi, again"),
+            1,
             2,
         );
 
@@ -149,15 +236,34 @@ mod tests {
         // At step 2, the message scheduled for step 2 should be deliverable
         let msg1 = bus.pop_deliverable_message(2).unwrap();
         assert_eq!(msg1.receive_step, 2);
+        assert_eq!((msg1.id, msg1.sent_step), (3, 1));
 
-        // At step 3, the two messages scheduled for step 3 should be deliverable
+        // At step 3, the two messages scheduled for step 3 should be deliverable in order of scheduling
         let msg2 = bus.pop_deliverable_message(3).unwrap();
-        assert_eq!(msg2.receive_step, 3);
+        assert_eq!((msg2.receive_step, msg2.id), (3, 1));
 
         let msg3 = bus.pop_deliverable_message(3).unwrap();
-        assert_eq!(msg3.receive_step, 3);
+        assert_eq!((msg3.receive_step, msg3.id), (3, 2));
 
         // No more messages should be deliverable
         assert!(bus.pop_deliverable_message(5).is_none());
     }
+
+    #[test]
+    fn test_topic_subscriptions() {
+        let mut bus = MessageBus::new();
+
+        bus.subscribe("market", "trader_2");
+        bus.subscribe("market", "trader_1");
+        bus.subscribe("news", "trader_1");
+
+        assert_eq!(bus.get_subscribers("market"), vec!["trader_1", "trader_2"]);
+
+        bus.unsubscribe("market", "trader_2");
+        assert_eq!(bus.get_subscribers("market"), vec!["trader_1"]);
+
+        bus.unsubscribe_all("trader_1");
+        assert!(bus.get_subscribers("market").is_empty());
+        assert!(bus.get_subscribers("news").is_empty());
+    }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use crate::core::errors::CoreError;
 use crate::core::snapshot::MetricsSnapshot;
 use rmcp::{
     schemars
@@ -29,37 +30,61 @@ pub struct MetricStats {
 
 pub struct Metrics {
     metrics: HashMap<String, Vec<Metric>>,
+    max_points: Option<usize>, // Maximum number of points over all metrics
+    points_count: usize,
 }
 
 impl Metrics {
-    pub fn new() -> Self {
+    pub fn new(max_points: Option<usize>) -> Self {
         Metrics {
             metrics: HashMap::new(),
+            max_points,
+            points_count: 0,
         }
     }
 
-    pub fn new_from_snapshot(snapshot: &MetricsSnapshot) -> Self {
+    pub fn new_from_snapshot(snapshot: &MetricsSnapshot, max_points: Option<usize>) -> Self {
         let mut metrics = HashMap::new();
         for (name, values) in &snapshot.metrics {
             let metric_values: Vec<Metric> = values.iter().map(|(timestamp, value)| Metric { timestamp: *timestamp, value: *value }).collect();
             metrics.insert(name.clone(), metric_values);
         }
 
+        let points_count = metrics.values().map(|values: &Vec<Metric>| values.len()).sum();
+
         Metrics {
             metrics,
+            max_points,
+            points_count,
         }
     }
 
-    pub fn record_metric(&mut self, current_time: u64, name : &str, value: f64) {
+    pub fn record_metric(&mut self, current_time: u64, name : &str, value: f64) -> Result<(), CoreError> {
+        let updates_last = self
+            .metrics
+            .get(name)
+            .and_then(|metric_list| metric_list.last())
+            .is_some_and(|m| m.timestamp == current_time);
+
+        if let Some(limit) = self.max_points
+            && !updates_last
+            && self.points_count >= limit
+        {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_metric_points".to_string(),
+                limit,
+            });
+        }
+
         match self.metrics.get_mut(name) {
             Some(metric_list) => {
 
                 // Update existing metric for this timestamp
-                if metric_list.last().map_or(false, |m| m.timestamp == current_time) {
+                if updates_last {
                    if let Some(last_metric) = metric_list.last_mut() {
                        last_metric.value += value;
                    }
-                   return;
+                   return Ok(());
                 }
 
                 metric_list.push(Metric {
@@ -74,6 +99,9 @@ impl Metrics {
                 }]);
             }
         }
+
+        self.points_count += 1;
+        Ok(())
     }
 
     pub fn compute_metric_stats(&self, name : &str) -> Option<MetricStats> {
@@ -97,6 +125,11 @@ impl Metrics {
         })
     }
 
+    // Most recently recorded value of the metric
+    pub fn get_last_value(&self, name: &str) -> Option<f64> {
+        self.metrics.get(name).and_then(|values| values.last()).map(|m| m.value)
+    }
+
     pub fn create_snapshot(&self) -> MetricsSnapshot {
         let mut snapshot = HashMap::new();
         for (name, metrics) in &self.metrics {
@@ -107,8 +140,29 @@ impl Metrics {
         MetricsSnapshot { metrics: snapshot}
     }
 
+    // All recorded points as CSV with one row per point, ordered by metric name and time
+    pub fn to_csv(&self) -> String {
+        let mut names: Vec<&String> = self.metrics.keys().collect();
+        names.sort();
+
+        let mut csv = String::from("metric,time,value\n");
+        for name in names {
+            for metric in &self.metrics[name] {
+                csv.push_str(&format!("{},{},{}\n", escape_csv(name), metric.timestamp, metric.value));
+            }
+        }
+        csv
+    }
+
     pub fn list_metric_names(&self) -> Vec<String> {
         self.metrics.keys().cloned().collect()
     }
 }
 
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,13 +1,22 @@
 mod entity;
+mod grid;
+mod network;
+mod population;
 mod world;
 mod scripting;
+mod space;
+pub mod condition;
 pub mod messaging;
 pub mod metrics;
 pub mod snapshot;
 pub mod errors;
+pub mod jobs;
 pub mod registry;
 pub mod world_config;
 
+#[cfg(test)]
 mod tests;
 
-pub use entity::Entity;
\ No newline at end of file
+pub use entity::Entity;
+pub use world::World;
+pub use scripting::lua::{DiagnosticSeverity, ScriptDiagnostic};
@@ -0,0 +1,368 @@
+use crate::core::errors::CoreError;
+use crate::core::world_config::{NetworkCfg, NetworkGeneratorCfg};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Graph of entities, used for neighbor-only interactions.
+pub struct Network {
+    directed: bool,
+    adjacency: BTreeMap<String, BTreeSet<String>>, // Entity ID to IDs of its (outgoing) neighbors
+}
+
+impl Network {
+    pub fn new(directed: bool) -> Self {
+        Network {
+            directed,
+            adjacency: BTreeMap::new(),
+        }
+    }
+
+    // Build network from configuration. Generators connect the given nodes in their order.
+    pub fn from_cfg(cfg: &NetworkCfg, nodes: &[String]) -> Result<Self, CoreError> {
+        let mut network = Network::new(cfg.directed);
+
+        for (from, to) in &cfg.edges {
+            network.add_edge(from, to);
+        }
+
+        if let Some(generator) = &cfg.generator {
+            let mut rng = match cfg.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_os_rng(),
+            };
+
+            let edges = match generator {
+                NetworkGeneratorCfg::ErdosRenyi { probability } => {
+                    erdos_renyi(nodes.len(), *probability, cfg.directed, &mut rng)?
+                }
+                NetworkGeneratorCfg::WattsStrogatz { k, beta } => {
+                    watts_strogatz(nodes.len(), *k, *beta, &mut rng)?
+                }
+                NetworkGeneratorCfg::BarabasiAlbert { m } => barabasi_albert(nodes.len(), *m, &mut rng)?,
+                NetworkGeneratorCfg::Lattice { width, periodic } => lattice(nodes.len(), *width, *periodic)?,
+            };
+
+            // Other generators create undirected links, a directed network gets both directions of each link
+            let symmetric = !matches!(generator, NetworkGeneratorCfg::ErdosRenyi { .. });
+            for (from, to) in edges {
+                network.add_edge(&nodes[from], &nodes[to]);
+                if cfg.directed && symmetric {
+                    network.add_edge(&nodes[to], &nodes[from]);
+                }
+            }
+        }
+
+        Ok(network)
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str) {
+        if from == to {
+            return;
+        }
+
+        self.adjacency
+            .entry(from.to_string())
+            .or_default()
+            .insert(to.to_string());
+
+        if !self.directed {
+            self.adjacency
+                .entry(to.to_string())
+                .or_default()
+                .insert(from.to_string());
+        }
+    }
+
+    pub fn remove_edge(&mut self, from: &str, to: &str) {
+        if let Some(neighbors) = self.adjacency.get_mut(from) {
+            neighbors.remove(to);
+        }
+
+        if !self.directed
+            && let Some(neighbors) = self.adjacency.get_mut(to)
+        {
+            neighbors.remove(from);
+        }
+    }
+
+    // Remove all edges from and to the node
+    pub fn remove_node(&mut self, id: &str) {
+        self.adjacency.remove(id);
+        for neighbors in self.adjacency.values_mut() {
+            neighbors.remove(id);
+        }
+    }
+
+    pub fn get_neighbors(&self, id: &str) -> Vec<String> {
+        self.adjacency
+            .get(id)
+            .map(|neighbors| neighbors.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // List of edges, each undirected edge is listed once
+    pub fn get_edges(&self) -> Vec<(String, String)> {
+        let mut edges = Vec::new();
+        for (from, neighbors) in &self.adjacency {
+            for to in neighbors {
+                if self.directed || from < to {
+                    edges.push((from.clone(), to.clone()));
+                }
+            }
+        }
+        edges
+    }
+
+    // Create configuration with explicit edges, used for snapshots
+    pub fn create_cfg(&self) -> NetworkCfg {
+        NetworkCfg {
+            directed: self.directed,
+            edges: self.get_edges(),
+            generator: None,
+            seed: None,
+        }
+    }
+}
+
+// Each pair of nodes is connected with given probability
+fn erdos_renyi(n: usize, probability: f64, directed: bool, rng: &mut StdRng) -> Result<Vec<(usize, usize)>, CoreError> {
+    check_probability("Erdos-Renyi", "probability", probability)?;
+
+    let mut edges = Vec::new();
+    for i in 0..n {
+        let start = if directed { 0 } else { i + 1 };
+        for j in start..n {
+            if i != j && rng.random::<f64>() < probability {
+                edges.push((i, j));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+// Ring lattice where each node is connected to k nearest nodes, edges are rewired with probability beta
+fn watts_strogatz(n: usize, k: usize, beta: f64, rng: &mut StdRng) -> Result<Vec<(usize, usize)>, CoreError> {
+    if !k.is_multiple_of(2) || k >= n {
+        return Err(CoreError::DeserializationError(format!(
+            "Watts-Strogatz generator requires even k smaller than number of nodes ({}), got {}",
+            n, k
+        )));
+    }
+    check_probability("Watts-Strogatz", "beta", beta)?;
+
+    let mut adjacency: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+    for i in 0..n {
+        for j in 1..=k / 2 {
+            let neighbor = (i + j) % n;
+            adjacency[i].insert(neighbor);
+            adjacency[neighbor].insert(i);
+        }
+    }
+
+    for i in 0..n {
+        for j in 1..=k / 2 {
+            let neighbor = (i + j) % n;
+            if rng.random::<f64>() >= beta || !adjacency[i].contains(&neighbor) {
+                continue;
+            }
+
+            // Node is already connected to all others
+            if adjacency[i].len() >= n - 1 {
+                continue;
+            }
+
+            let mut target = rng.random_range(0..n);
+            while target == i || adjacency[i].contains(&target) {
+                target = rng.random_range(0..n);
+            }
+
+            adjacency[i].remove(&neighbor);
+            adjacency[neighbor].remove(&i);
+            adjacency[i].insert(target);
+            adjacency[target].insert(i);
+        }
+    }
+
+    Ok(undirected_edges(&adjacency))
+}
+
+// Preferential attachment, each new node connects to m existing nodes with probability proportional to their degree
+fn barabasi_albert(n: usize, m: usize, rng: &mut StdRng) -> Result<Vec<(usize, usize)>, CoreError> {
+    if m == 0 || m >= n {
+        return Err(CoreError::DeserializationError(format!(
+            "Barabasi-Albert generator requires m between 1 and number of nodes ({}), got {}",
+            n, m
+        )));
+    }
+
+    let mut adjacency: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+    // Node is repeated once for each of its edges
+    let mut repeated_nodes: Vec<usize> = Vec::new();
+
+    // Start with a star of m + 1 nodes so that every node has non-zero degree
+    for i in 0..m {
+        adjacency[i].insert(m);
+        adjacency[m].insert(i);
+        repeated_nodes.push(i);
+        repeated_nodes.push(m);
+    }
+
+    for new_node in (m + 1)..n {
+        let mut targets = BTreeSet::new();
+        while targets.len() < m {
+            targets.insert(repeated_nodes[rng.random_range(0..repeated_nodes.len())]);
+        }
+
+        for target in targets {
+            adjacency[new_node].insert(target);
+            adjacency[target].insert(new_node);
+            repeated_nodes.push(new_node);
+            repeated_nodes.push(target);
+        }
+    }
+
+    Ok(undirected_edges(&adjacency))
+}
+
+// 2D grid with von Neumann neighborhood, nodes are placed row by row
+fn lattice(n: usize, width: usize, periodic: bool) -> Result<Vec<(usize, usize)>, CoreError> {
+    if width == 0 {
+        return Err(CoreError::DeserializationError(
+            "Lattice generator requires width greater than 0".to_string(),
+        ));
+    }
+
+    let height = n.div_ceil(width);
+    let mut edges = Vec::new();
+
+    for i in 0..n {
+        let (x, y) = (i % width, i / width);
+
+        let right = if x + 1 < width {
+            Some(i + 1)
+        } else if periodic && width > 2 {
+            Some(i + 1 - width)
+        } else {
+            None
+        };
+
+        let down = if y + 1 < height {
+            Some(i + width)
+        } else if periodic && height > 2 {
+            Some(x)
+        } else {
+            None
+        };
+
+        for neighbor in [right, down].into_iter().flatten() {
+            if neighbor < n && neighbor != i {
+                edges.push((i, neighbor));
+            }
+        }
+    }
+
+    Ok(edges)
+}
+
+fn check_probability(generator: &str, name: &str, value: f64) -> Result<(), CoreError> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(CoreError::DeserializationError(format!(
+            "{} generator requires {} between 0 and 1, got {}",
+            generator, name, value
+        )));
+    }
+    Ok(())
+}
+
+fn undirected_edges(adjacency: &[BTreeSet<usize>]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    for (i, neighbors) in adjacency.iter().enumerate() {
+        for &j in neighbors {
+            if i < j {
+                edges.push((i, j));
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("node_{}", i)).collect()
+    }
+
+    fn generated(generator: NetworkGeneratorCfg, n: usize, seed: u64) -> Network {
+        generated_network(generator, false, n, seed).unwrap()
+    }
+
+    fn generated_network(generator: NetworkGeneratorCfg, directed: bool, n: usize, seed: u64) -> Result<Network, CoreError> {
+        let cfg = NetworkCfg {
+            directed,
+            edges: Vec::new(),
+            generator: Some(generator),
+            seed: Some(seed),
+        };
+        Network::from_cfg(&cfg, &nodes(n))
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut network = Network::new(false);
+        network.add_edge("a", "b");
+        network.add_edge("b", "c");
+        assert_eq!(network.get_neighbors("b"), vec!["a", "c"]);
+
+        network.remove_edge("c", "b");
+        assert_eq!(network.get_neighbors("b"), vec!["a"]);
+
+        network.remove_node("a");
+        assert!(network.get_neighbors("b").is_empty());
+
+        let mut directed = Network::new(true);
+        directed.add_edge("a", "b");
+        assert_eq!(directed.get_neighbors("a"), vec!["b"]);
+        assert!(directed.get_neighbors("b").is_empty());
+    }
+
+    #[test]
+    fn test_generators_are_seeded() {
+        let generator = NetworkGeneratorCfg::ErdosRenyi { probability: 0.3 };
+        let first = generated(generator.clone(), 30, 7).get_edges();
+        let second = generated(generator, 30, 7).get_edges();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_generator_degrees() {
+        // Rewiring keeps the number of edges of the ring lattice
+        let network = generated(NetworkGeneratorCfg::WattsStrogatz { k: 4, beta: 0.2 }, 20, 1);
+        assert_eq!(network.get_edges().len(), 20 * 4 / 2);
+
+        let network = generated(NetworkGeneratorCfg::BarabasiAlbert { m: 2 }, 20, 1);
+        assert_eq!(network.get_edges().len(), 2 + (20 - 3) * 2);
+
+        let network = generated(NetworkGeneratorCfg::Lattice { width: 3, periodic: false }, 9, 1);
+        assert_eq!(network.get_neighbors("node_4").len(), 4);
+        assert_eq!(network.get_neighbors("node_0").len(), 2);
+    }
+
+    #[test]
+    fn test_directed_generators() {
+        // Links of undirected generators are followed both ways
+        let network = generated_network(NetworkGeneratorCfg::BarabasiAlbert { m: 2 }, true, 20, 1).unwrap();
+        assert_eq!(network.get_edges().len(), 2 * (2 + (20 - 3) * 2));
+        for (from, to) in network.get_edges() {
+            assert!(network.get_neighbors(&to).contains(&from));
+        }
+
+        let network = generated_network(NetworkGeneratorCfg::Lattice { width: 3, periodic: false }, true, 9, 1).unwrap();
+        assert_eq!(network.get_neighbors("node_4").len(), 4);
+
+        assert!(generated_network(NetworkGeneratorCfg::ErdosRenyi { probability: 1.5 }, false, 5, 1).is_err());
+        assert!(generated_network(NetworkGeneratorCfg::WattsStrogatz { k: 2, beta: f64::NAN }, false, 5, 1).is_err());
+    }
+}
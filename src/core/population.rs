@@ -0,0 +1,161 @@
+use crate::core::errors::CoreError;
+use crate::core::messaging::JSONObject;
+use crate::core::world_config::{EntityCfg, PopulationCfg, ValueGeneratorCfg};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+
+// Create configurations of entities of a population with the given IDs and generated initial states
+pub fn generate_entities(cfg: &PopulationCfg, ids: Vec<String>) -> Result<Vec<EntityCfg>, CoreError> {
+    let mut rng = match cfg.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+
+    let mut entities = Vec::new();
+    for id in ids {
+        let mut state = JSONObject::new();
+        for (name, generator) in &cfg.initial_state {
+            state.insert(name.clone(), generate_value(generator, &mut rng)?);
+        }
+
+        entities.push(EntityCfg {
+            id,
+            script_id: cfg.script_id.clone(),
+            initial_state: if state.is_empty() { None } else { Some(state) },
+            tags: cfg.tags.clone(),
+            parameters: cfg.parameters.clone(),
+            ..Default::default()
+        });
+    }
+
+    Ok(entities)
+}
+
+fn generate_value(generator: &ValueGeneratorCfg, rng: &mut StdRng) -> Result<Value, CoreError> {
+    match generator {
+        ValueGeneratorCfg::Constant { value } => Ok(value.clone()),
+        ValueGeneratorCfg::Uniform { min, max, integer } => {
+            if min > max {
+                return Err(CoreError::DeserializationError(format!("Uniform generator requires min <= max, got {} and {}", min, max)));
+            }
+
+            if *integer {
+                let (low, high) = (min.ceil() as i64, max.floor() as i64);
+                if low > high {
+                    return Err(CoreError::DeserializationError(format!("Uniform integer generator has no integer between {} and {}", min, max)));
+                }
+                return Ok(Value::from(rng.random_range(low..=high)));
+            }
+
+            Ok(Value::from(min + rng.random::<f64>() * (max - min)))
+        }
+        ValueGeneratorCfg::Normal { mean, std_dev } => {
+            if *std_dev < 0.0 {
+                return Err(CoreError::DeserializationError(format!("Normal generator requires non-negative std_dev, got {}", std_dev)));
+            }
+
+            // Box-Muller transform
+            let u1 = 1.0 - rng.random::<f64>();
+            let u2 = rng.random::<f64>();
+            let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+            Ok(Value::from(mean + z * std_dev))
+        }
+        ValueGeneratorCfg::Choice { values, weights } => {
+            if values.is_empty() {
+                return Err(CoreError::DeserializationError("Choice generator requires at least one value".to_string()));
+            }
+
+            let Some(weights) = weights else {
+                return Ok(values[rng.random_range(0..values.len())].clone());
+            };
+
+            let total: f64 = weights.iter().sum();
+            if weights.len() != values.len() || weights.iter().any(|w| *w < 0.0) || total <= 0.0 {
+                return Err(CoreError::DeserializationError(format!(
+                    "Choice generator requires {} non-negative weights with positive sum",
+                    values.len()
+                )));
+            }
+
+            let mut target = rng.random::<f64>() * total;
+            for (value, weight) in values.iter().zip(weights) {
+                if target < *weight {
+                    return Ok(value.clone());
+                }
+                target -= weight;
+            }
+            Ok(values[values.len() - 1].clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn population(initial_state: BTreeMap<String, ValueGeneratorCfg>, seed: u64) -> PopulationCfg {
+        PopulationCfg {
+            script_id: "sheep".to_string(),
+            count: 100,
+            id_pattern: "sheep_{i}".to_string(),
+            initial_state,
+            tags: vec!["flock".to_string()],
+            parameters: JSONObject::new(),
+            seed: Some(seed),
+        }
+    }
+
+    #[test]
+    fn test_generators() {
+        let mut initial_state = BTreeMap::new();
+        initial_state.insert("kind".to_string(), ValueGeneratorCfg::Constant { value: Value::from("sheep") });
+        initial_state.insert("age".to_string(), ValueGeneratorCfg::Uniform { min: 1.0, max: 3.0, integer: true });
+        initial_state.insert("weight".to_string(), ValueGeneratorCfg::Normal { mean: 50.0, std_dev: 5.0 });
+        initial_state.insert(
+            "color".to_string(),
+            ValueGeneratorCfg::Choice {
+                values: vec![Value::from("white"), Value::from("black")],
+                weights: Some(vec![1.0, 0.0]),
+            },
+        );
+
+        let cfg = population(initial_state, 3);
+        let entities = generate_entities(&cfg, cfg.entity_ids()).unwrap();
+        assert_eq!(entities.len(), 100);
+        assert_eq!(entities[99].id, "sheep_99");
+
+        let mut weight_sum = 0.0;
+        for entity in &entities {
+            let state = entity.initial_state.as_ref().unwrap();
+            assert_eq!(state["kind"], "sheep");
+            assert_eq!(state["color"], "white");
+            assert!((1..=3).contains(&state["age"].as_i64().unwrap()));
+            weight_sum += state["weight"].as_f64().unwrap();
+        }
+        assert!((weight_sum / 100.0 - 50.0).abs() < 2.0);
+
+        // Integer bounds are inclusive
+        for age in 1..=3 {
+            assert!(entities.iter().any(|entity| entity.initial_state.as_ref().unwrap()["age"] == age));
+        }
+
+        // Same seed generates same states
+        let again = generate_entities(&cfg, cfg.entity_ids()).unwrap();
+        assert_eq!(entities[7].initial_state, again[7].initial_state);
+    }
+
+    #[test]
+    fn test_invalid_generator() {
+        let mut initial_state = BTreeMap::new();
+        initial_state.insert("age".to_string(), ValueGeneratorCfg::Uniform { min: 3.0, max: 1.0, integer: false });
+        let cfg = population(initial_state, 1);
+        assert!(generate_entities(&cfg, cfg.entity_ids()).is_err());
+
+        let mut initial_state = BTreeMap::new();
+        initial_state.insert("age".to_string(), ValueGeneratorCfg::Uniform { min: 1.2, max: 1.8, integer: true });
+        let cfg = population(initial_state, 1);
+        assert!(generate_entities(&cfg, cfg.entity_ids()).is_err());
+    }
+}
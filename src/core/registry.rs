@@ -1,38 +1,121 @@
 use std::collections::HashMap;
 use crate::core::world::World;
 use crate::core::snapshot::WorldSnapshot;
-use std::sync::{RwLock, Arc};
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::sync::{Arc, RwLock};
 use crate::core::world_config::WorldCfg;
 use crate::core::errors::CoreError;
 
 
 // Registry for managing multiple simulations.
 pub struct Registry {
-    worlds: RwLock<HashMap<String, Arc<RwLock<World>>>>,
+    worlds: RwLock<HashMap<String, Arc<SharedWorld>>>,
+    limits: RegistryLimits,
+}
+
+// Limits shared by all worlds of the registry
+#[derive(Debug, Clone, Default)]
+pub struct RegistryLimits {
+    pub max_worlds: Option<usize>, // Unlimited if None
+}
+
+// Operation on a world, run on the thread of the world
+type WorldTask = Box<dyn FnOnce(&mut World) + Send>;
+
+// World of the registry, used by the tool calls and background jobs of all threads.
+// Worlds are not Send (Lua VMs and the Rc handles between their parts), so every world is created on its own thread
+// and never leaves it. Other threads send operations to the thread, which runs them one at a time in order.
+pub struct SharedWorld {
+    tasks: mpsc::Sender<WorldTask>,
+}
+
+impl SharedWorld {
+    // Create the world on a new thread, the thread ends once all handles of the world are dropped
+    pub fn spawn(create: impl FnOnce() -> Result<World, CoreError> + Send + 'static) -> Result<Self, CoreError> {
+        let (tasks, receiver) = mpsc::channel::<WorldTask>();
+        let (created_sender, created) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("world".to_string())
+            .spawn(move || {
+                let mut world = match create() {
+                    Ok(world) => world,
+                    Err(e) => {
+                        let _ = created_sender.send(Err(e));
+                        return;
+                    }
+                };
+                let _ = created_sender.send(Ok(()));
+
+                for task in receiver {
+                    task(&mut world);
+                }
+            })
+            .map_err(|e| CoreError::WorldThread {
+                message: format!("Failed to start world thread: {}", e),
+            })?;
+
+        created.recv().map_err(|_| CoreError::WorldThread {
+            message: "World thread panicked while creating the world".to_string(),
+        })??;
+        Ok(SharedWorld { tasks })
+    }
+
+    // Run the operation on the thread of the world and wait for its result. A panicking operation fails the call,
+    // the world stays available to other operations.
+    pub fn with<R: Send + 'static>(&self, operation: impl FnOnce(&mut World) -> R + Send + 'static) -> Result<R, CoreError> {
+        let (result_sender, result) = mpsc::channel();
+        let task: WorldTask = Box::new(move |world| {
+            let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| operation(world)));
+            let _ = result_sender.send(outcome.map_err(|panic| panic_message(panic.as_ref())));
+        });
+
+        let thread_ended = || CoreError::WorldThread {
+            message: "World thread has ended".to_string(),
+        };
+        self.tasks.send(task).map_err(|_| thread_ended())?;
+        match result.recv().map_err(|_| thread_ended())? {
+            Ok(result) => Ok(result),
+            Err(message) => Err(CoreError::WorldThread {
+                message: format!("Operation on the world panicked: {}", message),
+            }),
+        }
+    }
+}
+
+// Message of a caught panic, panics usually carry a string
+pub fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "unknown panic".to_string(),
+    }
 }
 
 impl Registry {
-    pub fn new() -> Self {
+    pub fn new(limits: RegistryLimits) -> Self {
         Registry {
             worlds: RwLock::new(HashMap::new()),
+            limits,
         }
     }
 
     pub fn create(&self, config: WorldCfg) -> Result<(), CoreError> {
         let name = config.name.clone(); 
-        let world = World::new(&config)?;
+        let world = SharedWorld::spawn(move || World::new(&config))?;
 
         let mut self_worlds = self.worlds.write().unwrap();
 
         if self_worlds.contains_key(&name) {
             return Err(CoreError::WorldAlreadyExists);
         }
+        self.check_worlds_quota(&self_worlds, &name)?;
 
-        self_worlds.insert(name, Arc::new(RwLock::new(world)));
+        self_worlds.insert(name, Arc::new(world));
         Ok(())
     }
 
-    pub fn get(&self, name: &str) -> Result<Arc<RwLock<World>>, CoreError> {
+    pub fn get(&self, name: &str) -> Result<Arc<SharedWorld>, CoreError> {
         match self.worlds.read().unwrap().get(name) {
             Some(world) => Ok(world.clone()),
             None => Err(CoreError::WorldNotFound { name: name.to_string() }),
@@ -49,17 +132,17 @@ impl Registry {
     pub fn copy(&self, source_name: &str, target_name: &str, replace: bool) -> Result<(), CoreError> {
         let source_world = self.get(source_name)?;
 
-        let source_world_guard = source_world.read().unwrap();
-        let snapshot = source_world_guard.create_snapshot()?;
+        let snapshot = source_world.with(|world| world.create_snapshot())??;
 
         let mut target_worlds = self.worlds.write().unwrap();
 
         if !replace && target_worlds.contains_key(target_name) {
             return Err(CoreError::WorldAlreadyExists);
         }
+        self.check_worlds_quota(&target_worlds, target_name)?;
 
-        let target_world = World::new_from_snapshot(snapshot)?;
-        target_worlds.insert(target_name.to_string(), Arc::new(RwLock::new(target_world)));
+        let target_world = SharedWorld::spawn(move || World::new_from_snapshot(snapshot))?;
+        target_worlds.insert(target_name.to_string(), Arc::new(target_world));
         Ok(())
     }
 
@@ -68,19 +151,31 @@ impl Registry {
     }
 
     pub fn restore_snapshot(&self, world_name: &str, snapshot: WorldSnapshot) -> Result<(), CoreError> {
-        let restored_world = World::new_from_snapshot(snapshot)?;
+        let restored_world = SharedWorld::spawn(move || World::new_from_snapshot(snapshot))?;
 
         let mut worlds = self.worlds.write().unwrap();
-        worlds.insert(world_name.to_string(), Arc::new(RwLock::new(restored_world)));
+        self.check_worlds_quota(&worlds, world_name)?;
+        worlds.insert(world_name.to_string(), Arc::new(restored_world));
+        Ok(())
+    }
+
+    // Adding a world with a new name must not exceed the maximum number of worlds
+    fn check_worlds_quota(&self, worlds: &HashMap<String, Arc<SharedWorld>>, name: &str) -> Result<(), CoreError> {
+        if let Some(limit) = self.limits.max_worlds
+            && !worlds.contains_key(name)
+            && worlds.len() >= limit
+        {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_worlds".to_string(),
+                limit,
+            });
+        }
         Ok(())
     }
 
     pub fn get_snapshot(&self, world_name: &str) -> Result<WorldSnapshot, CoreError> {
         match self.worlds.read().unwrap().get(world_name) {
-            Some(world) => {
-                let world_guard = world.read().unwrap();
-                world_guard.create_snapshot()
-            },
+            Some(world) => world.with(|world| world.create_snapshot())?,
             None => Err(CoreError::WorldNotFound { name: world_name.to_string() }),
         }
     }
@@ -111,3 +206,34 @@ impl Registry {
     //     Ok(())
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_worlds() {
+        let registry = Registry::new(RegistryLimits { max_worlds: Some(1) });
+        registry.create(WorldCfg::new("first".to_string())).unwrap();
+
+        let result = registry.create(WorldCfg::new("second".to_string()));
+        assert!(matches!(result, Err(CoreError::QuotaExceeded { limit: 1, .. })));
+        assert!(registry.copy("first", "second", false).is_err());
+
+        // Replacing an existing world does not add a world
+        registry.copy("first", "first", true).unwrap();
+    }
+
+    #[test]
+    fn test_panicking_operation() {
+        let registry = Registry::new(RegistryLimits::default());
+        registry.create(WorldCfg::new("world".to_string())).unwrap();
+        let world = registry.get("world").unwrap();
+
+        // The panic fails the call, the world keeps serving other operations
+        let result = world.with(|_| -> u64 { panic!("boom") });
+        assert!(matches!(result, Err(CoreError::WorldThread { message }) if message.contains("boom")));
+        world.with(|world| world.update(1).map(|_| ())).unwrap().unwrap();
+        assert_eq!(world.with(|world| world.get_simulation_time()).unwrap(), 1);
+    }
+}
@@ -3,12 +3,16 @@ use crate::core::messaging;
 use crate::core::messaging::Command;
 use crate::core::messaging::JSONObject;
 use crate::core::messaging::Message;
-use crate::core::scripting::lua::convert::{convert_to_json, convert_to_lua_table};
+use crate::core::scripting::lua::convert::{
+    convert_json_to_lua_value, convert_to_json, convert_to_lua_table, lua_to_json_value,
+};
+use crate::core::scripting::lua::sandbox::{INSTRUCTION_LIMIT, create_sandboxed_lua, set_instruction_limit};
+use crate::core::entity::EntityView;
 use crate::core::world::WorldState;
 
 use mlua::Lua;
 use mlua::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 pub struct LuaScriptController {
@@ -19,26 +23,31 @@ pub struct LuaScriptController {
 
     incoming_msgs: Vec<Message>, // Incoming messages to be processed on next update
     command_queue: Rc<RefCell<Vec<Command>>>, // Queue of commands to be executed by the world after update
+    executed_instructions: Rc<Cell<u64>>, // Reset before every call of the script, each call gets the full budget
 }
 
 impl LuaScriptController {
     pub fn new(
         id: String,
-        script: &String,
+        script: &str,
+        parameters: &JSONObject,
         world_state: Rc<RefCell<WorldState>>,
     ) -> Result<Self, mlua::Error> {
-        Self::init_lua(&id, &script, world_state)
+        Self::init_lua(&id, script, parameters, world_state)
     }
 
     fn init_lua(
-        id: &String,
-        script: &String,
+        id: &str,
+        script: &str,
+        parameters: &JSONObject,
         world_state: Rc<RefCell<WorldState>>,
     ) -> LuaResult<LuaScriptController> {
-        let lua = Lua::new();
+        let lua = create_sandboxed_lua()?;
         let command_queue = Rc::new(RefCell::new(Vec::new()));
 
-        register_lua_functions(&lua, id, command_queue.clone(), world_state)?;
+        seed_random(&lua, id, &world_state)?;
+        register_lua_functions(&lua, id, parameters, command_queue.clone(), world_state)?;
+        let executed_instructions = set_instruction_limit(&lua, INSTRUCTION_LIMIT)?;
 
         lua.load(script).exec()?;
 
@@ -59,7 +68,8 @@ impl LuaScriptController {
             get_state_fn: get_state_function_reg,
             set_state_fn: set_state_function_reg,
             incoming_msgs: Vec::new(),
-            command_queue: command_queue,
+            command_queue,
+            executed_instructions,
         })
     }
 
@@ -70,6 +80,7 @@ impl LuaScriptController {
                 message: format!("Error converting JSON to Lua table: {}", e),
             })?;
 
+        self.executed_instructions.set(0);
         let result = self
             .lua_vm
             .registry_value::<LuaFunction>(&self.set_state_fn)
@@ -87,6 +98,7 @@ impl LuaScriptController {
                 message: format!("Error creating messages table: {}", e),
             })?;
 
+        self.executed_instructions.set(0);
         let result = self
             .lua_vm
             .registry_value::<LuaFunction>(&self.update_fn)
@@ -107,6 +119,10 @@ impl LuaScriptController {
 
             msg_table.set("content", convert_to_lua_table(&self.lua_vm, &msg.content)?)?;
             msg_table.set("kind", msg.kind.clone())?;
+            msg_table.set("sender", msg.sender.clone())?;
+            if let crate::core::messaging::MessageReceiver::Topic { topic } = &msg.receiver {
+                msg_table.set("topic", topic.clone())?;
+            }
             msgs_table.push(msg_table)?;
         }
 
@@ -116,6 +132,7 @@ impl LuaScriptController {
     }
 
     pub fn get_state(&self) -> Result<JSONObject, CoreError> {
+        self.executed_instructions.set(0);
         let state = self
             .lua_vm
             .registry_value::<LuaFunction>(&self.get_state_fn)
@@ -124,46 +141,55 @@ impl LuaScriptController {
                 message: format!("Error calling get_state function: {}", e),
             })?;
 
-        return convert_to_json(&self.lua_vm, &state).map_err(|e| CoreError::ScriptState {
+        convert_to_json(&self.lua_vm, &state).map_err(|e| CoreError::ScriptState {
             message: format!("Error serializing state table: {}", e),
-        });
+        })
     }
 
     pub fn push_message(&mut self, msg: Message) {
         self.incoming_msgs.push(msg);
     }
+
+    // Take messages not processed yet, e.g. to hand them over to another controller
+    pub fn take_messages(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.incoming_msgs)
+    }
 }
 
-fn register_lua_functions(
+pub(super) fn register_lua_functions(
     lua: &Lua,
-    id: &String,
+    id: &str,
+    parameters: &JSONObject,
     command_queue: Rc<RefCell<Vec<Command>>>,
     world_state: Rc<RefCell<WorldState>>,
 ) -> LuaResult<()> {
-    register_self_lib(lua, id, command_queue.clone())?;
+    register_self_lib(lua, id, parameters, command_queue.clone(), world_state.clone())?;
     register_world_lib(lua, command_queue, world_state)?;
     Ok(())
 }
 
 fn register_self_lib(
     lua: &Lua,
-    id: &String,
+    id: &str,
+    parameters: &JSONObject,
     command_queue: Rc<RefCell<Vec<Command>>>,
+    world_state: Rc<RefCell<WorldState>>,
 ) -> LuaResult<()> {
     let self_lib = lua.create_table()?;
-    self_lib.set("id", id.clone())?;
+    self_lib.set("id", id.to_string())?;
+    self_lib.set("params", create_read_only_table(lua, parameters)?)?;
 
     // Function to send message to another entity
     let command_queue_clone = command_queue.clone();
-    let id_clone = id.clone();
+    let id_clone = id.to_string();
     let send_msg_fn = lua.create_function(
-        move |lua_ctx, (receiver_id, kind, content, delay): (String, String, LuaTable, u64)| {
+        move |lua_ctx, (receiver_id, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
             command_queue_clone.borrow_mut().push(Command::SendMessage {
                 sender: id_clone.clone(),
                 receiver: crate::core::messaging::MessageReceiver::Entity { id: receiver_id },
                 kind,
                 content: convert_to_json(lua_ctx, &content)?,
-                delay,
+                delay: delay.unwrap_or(0),
             });
 
             Ok(())
@@ -171,7 +197,7 @@ fn register_self_lib(
     )?;
 
     // Broadcast message to entities within a radius
-    let id_clone = id.clone();
+    let id_clone = id.to_string();
     let command_queue_clone = command_queue.clone();
     let broadcast_msg_fn = lua.create_function(move |lua_ctx, (x, y, radius, kind, content)| {
         command_queue_clone.borrow_mut().push(Command::SendMessage {
@@ -199,7 +225,7 @@ fn register_self_lib(
         move |lua_ctx,
               (entity_id, script_id, initial_state): (String, String, Option<LuaTable>)| {
             let initial_state_json = match initial_state {
-                Some(table) => Some(convert_to_json(&lua_ctx, &table)?),
+                Some(table) => Some(convert_to_json(lua_ctx, &table)?),
                 None => None,
             };
 
@@ -214,16 +240,203 @@ fn register_self_lib(
         },
     )?;
 
+    // Switch to another script at the end of the step, keeping ID, tags, subscriptions and messages
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let become_fn = lua.create_function(move |lua_ctx, (script_id, state): (String, Option<LuaTable>)| {
+        let state = match state {
+            Some(table) => Some(convert_to_json(lua_ctx, &table)?),
+            None => None,
+        };
+
+        command_queue_clone.borrow_mut().push(Command::ChangeScript {
+            entity_id: id_clone.clone(),
+            script_id,
+            state,
+        });
+        Ok(())
+    })?;
+
+    // Subscribe to messages published to a topic
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let subscribe_fn = lua.create_function(move |_, topic: String| {
+        command_queue_clone.borrow_mut().push(Command::Subscribe {
+            entity_id: id_clone.clone(),
+            topic,
+        });
+        Ok(())
+    })?;
+
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let unsubscribe_fn = lua.create_function(move |_, topic: String| {
+        command_queue_clone.borrow_mut().push(Command::Unsubscribe {
+            entity_id: id_clone.clone(),
+            topic,
+        });
+        Ok(())
+    })?;
+
+    // Publish message to all entities subscribed to a topic
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let publish_fn = lua.create_function(
+        move |lua_ctx, (topic, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: id_clone.clone(),
+                receiver: crate::core::messaging::MessageReceiver::Topic { topic },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let add_tag_fn = lua.create_function(move |_, tag: String| {
+        command_queue_clone.borrow_mut().push(Command::AddTag {
+            entity_id: id_clone.clone(),
+            tag,
+        });
+        Ok(())
+    })?;
+
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let remove_tag_fn = lua.create_function(move |_, tag: String| {
+        command_queue_clone.borrow_mut().push(Command::RemoveTag {
+            entity_id: id_clone.clone(),
+            tag,
+        });
+        Ok(())
+    })?;
+
+    // Send message to all entities with a tag
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let send_to_tag_fn = lua.create_function(
+        move |lua_ctx, (tag, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: id_clone.clone(),
+                receiver: crate::core::messaging::MessageReceiver::Tag { tag },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    // Send message to all neighbors in the network
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let send_to_neighbors_fn = lua.create_function(
+        move |lua_ctx, (kind, content, delay): (String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: id_clone.clone(),
+                receiver: crate::core::messaging::MessageReceiver::Neighbors { id: id_clone.clone() },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    // Move to a cell of the grid, applied at the end of the step
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let world_state_clone = world_state.clone();
+    let move_to_fn = lua.create_function(move |_, (x, y): (i64, i64)| {
+        let world_state = world_state_clone.borrow();
+        let grid = world_state.get_grid().ok_or_else(no_grid_error)?;
+        if grid.normalize(x, y).is_none() {
+            return Err(LuaError::RuntimeError(format!("Cell ({}, {}) is outside of the grid", x, y)));
+        }
+
+        command_queue_clone.borrow_mut().push(Command::MoveTo {
+            entity_id: id_clone.clone(),
+            x,
+            y,
+        });
+        Ok(())
+    })?;
+
+    // Set position and velocity in continuous space, applied at the end of the step
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let set_position_fn = lua.create_function(move |_, (x, y): (f64, f64)| {
+        if !x.is_finite() || !y.is_finite() {
+            return Err(LuaError::RuntimeError(format!("set_position expects finite coordinates, got ({}, {})", x, y)));
+        }
+        command_queue_clone.borrow_mut().push(Command::SetPosition {
+            entity_id: id_clone.clone(),
+            x,
+            y,
+        });
+        Ok(())
+    })?;
+
+    // Velocity needs a position, either current or set earlier in this step
+    let command_queue_clone = command_queue.clone();
+    let id_clone = id.to_string();
+    let set_velocity_fn = lua.create_function(move |_, (vx, vy): (f64, f64)| {
+        if !vx.is_finite() || !vy.is_finite() {
+            return Err(LuaError::RuntimeError(format!("set_velocity expects a finite velocity, got ({}, {})", vx, vy)));
+        }
+        let positioned = world_state.borrow().get_space().get_body(&id_clone).is_some()
+            || command_queue_clone
+                .borrow()
+                .iter()
+                .any(|command| matches!(command, Command::SetPosition { entity_id, .. } if entity_id == &id_clone));
+        if !positioned {
+            return Err(LuaError::RuntimeError(format!(
+                "set_velocity requires a position, call set_position first for entity '{}'",
+                id_clone
+            )));
+        }
+
+        command_queue_clone.borrow_mut().push(Command::SetVelocity {
+            entity_id: id_clone.clone(),
+            vx,
+            vy,
+        });
+        Ok(())
+    })?;
+
     self_lib.set("destroy", destroy_fn)?;
+    self_lib.set("set_position", set_position_fn)?;
+    self_lib.set("set_velocity", set_velocity_fn)?;
+    self_lib.set("move_to", move_to_fn)?;
+    self_lib.set("send_to_neighbors", send_to_neighbors_fn)?;
+    self_lib.set("add_tag", add_tag_fn)?;
+    self_lib.set("remove_tag", remove_tag_fn)?;
+    self_lib.set("send_to_tag", send_to_tag_fn)?;
+    self_lib.set("subscribe", subscribe_fn)?;
+    self_lib.set("unsubscribe", unsubscribe_fn)?;
+    self_lib.set("publish", publish_fn)?;
     self_lib.set("broadcast_msg", broadcast_msg_fn)?;
     self_lib.set("send_msg", send_msg_fn)?;
     self_lib.set("spawn_entity", spawn_fn)?;
+    self_lib.set("become", become_fn)?;
 
     lua.globals().set("self", self_lib)?;
     Ok(())
 }
 
-fn register_world_lib(
+// Seed math.random if the world is seeded, so that runs of the same world are reproducible
+pub(super) fn seed_random(lua: &Lua, id: &str, world_state: &Rc<RefCell<WorldState>>) -> LuaResult<()> {
+    if let Some(seed) = world_state.borrow().get_random_seed(id) {
+        let math: LuaTable = lua.globals().get("math")?;
+        math.get::<LuaFunction>("randomseed")?.call::<()>(seed as i64)?;
+    }
+    Ok(())
+}
+
+pub(super) fn register_world_lib(
     lua: &Lua,
     command_queue: Rc<RefCell<Vec<Command>>>,
     world_state: Rc<RefCell<WorldState>>,
@@ -238,24 +451,336 @@ fn register_world_lib(
             .borrow()
             .get_entities()
             .keys()
-            .enumerate()
-            .for_each(|(_, entity_id)| {
+            .for_each(|entity_id| {
                 res_table.push(entity_id.clone()).unwrap();
             });
         Ok(res_table)
     })?;
 
+    // List network neighbors of an entity
+    let world_state_clone = world_state.clone();
+    let neighbors_fn = lua.create_function(move |lua_ctx, id: String| {
+        lua_ctx.create_sequence_from(world_state_clone.borrow().get_network().get_neighbors(&id))
+    })?;
+
     // Record a metric
+    let command_queue_clone = command_queue.clone();
     let record_metric_fn = lua.create_function(move |_, (name, value): (String, f64)| {
-        command_queue
+        command_queue_clone
             .borrow_mut()
             .push(Command::RecordMetric { name, value });
         Ok(())
     })?;
 
+    // Add or remove network edges, applied at the end of the step
+    let command_queue_clone = command_queue.clone();
+    let add_edge_fn = lua.create_function(move |_, (from, to): (String, String)| {
+        command_queue_clone.borrow_mut().push(Command::AddEdge { from, to });
+        Ok(())
+    })?;
+
+    let command_queue_clone = command_queue.clone();
+    let remove_edge_fn = lua.create_function(move |_, (from, to): (String, String)| {
+        command_queue_clone.borrow_mut().push(Command::RemoveEdge { from, to });
+        Ok(())
+    })?;
+
+    // Get fields and occupants of a grid cell, nil if the cell is outside of the grid
+    let world_state_clone = world_state.clone();
+    let get_cell_fn = lua.create_function(move |lua_ctx, (x, y): (i64, i64)| {
+        let world_state = world_state_clone.borrow();
+        let grid = world_state.get_grid().ok_or_else(no_grid_error)?;
+
+        match grid.normalize(x, y) {
+            Some(cell) => {
+                let cell_table = create_cell_table(lua_ctx, cell)?;
+                let fields_table = lua_ctx.create_table()?;
+                for (name, value) in grid.get_values(cell) {
+                    fields_table.set(name, value)?;
+                }
+                cell_table.set("fields", fields_table)?;
+                cell_table.set("occupants", lua_ctx.create_sequence_from(grid.get_occupants(cell))?)?;
+                Ok(Some(cell_table))
+            }
+            None => Ok(None),
+        }
+    })?;
+
+    // Set value of a grid cell field, applied at the end of the step
+    let world_state_clone = world_state.clone();
+    let command_queue_clone = command_queue.clone();
+    let set_cell_fn = lua.create_function(move |_, (x, y, field, value): (i64, i64, String, f64)| {
+        let world_state = world_state_clone.borrow();
+        let grid = world_state.get_grid().ok_or_else(no_grid_error)?;
+        if grid.normalize(x, y).is_none() {
+            return Err(LuaError::RuntimeError(format!("Cell ({}, {}) is outside of the grid", x, y)));
+        }
+
+        command_queue_clone.borrow_mut().push(Command::SetCell { x, y, field, value });
+        Ok(())
+    })?;
+
+    // List neighbor cells of a grid cell according to the grid neighborhood
+    let world_state_clone = world_state.clone();
+    let cell_neighbors_fn = lua.create_function(move |lua_ctx, (x, y): (i64, i64)| {
+        let world_state = world_state_clone.borrow();
+        let grid = world_state.get_grid().ok_or_else(no_grid_error)?;
+
+        let res_table = lua_ctx.create_table()?;
+        if let Some(cell) = grid.normalize(x, y) {
+            for neighbor in grid.get_neighbor_cells(cell) {
+                res_table.push(create_cell_table(lua_ctx, neighbor)?)?;
+            }
+        }
+        Ok(res_table)
+    })?;
+
+    // Get the cell occupied by an entity, nil if it has none
+    let world_state_clone = world_state.clone();
+    let get_entity_cell_fn = lua.create_function(move |lua_ctx, id: String| {
+        let world_state = world_state_clone.borrow();
+        let grid = world_state.get_grid().ok_or_else(no_grid_error)?;
+
+        match grid.get_position(&id) {
+            Some(cell) => Ok(Some(create_cell_table(lua_ctx, cell)?)),
+            None => Ok(None),
+        }
+    })?;
+
+    // Get position and velocity of an entity in continuous space, nil if it has no position
+    let world_state_clone = world_state.clone();
+    let get_position_fn = lua.create_function(move |lua_ctx, id: String| {
+        match world_state_clone.borrow().get_space().get_body(&id) {
+            Some(body) => {
+                let body_table = lua_ctx.create_table()?;
+                body_table.set("x", body.x)?;
+                body_table.set("y", body.y)?;
+                body_table.set("vx", body.vx)?;
+                body_table.set("vy", body.vy)?;
+                Ok(Some(body_table))
+            }
+            None => Ok(None),
+        }
+    })?;
+
+    // List IDs of entities within radius of a point, ordered by distance
+    let world_state_clone = world_state.clone();
+    let query_radius_fn = lua.create_function(move |lua_ctx, (x, y, radius): (f64, f64, f64)| {
+        lua_ctx.create_sequence_from(world_state_clone.borrow().get_space().query_radius(x, y, radius))
+    })?;
+
+    // List IDs of k entities nearest to a point, optionally accepted by filter function
+    let world_state_clone = world_state.clone();
+    let nearest_fn = lua.create_function(
+        move |lua_ctx, (x, y, k, filter): (f64, f64, usize, Option<LuaFunction>)| {
+            let res_table = lua_ctx.create_table()?;
+            if k == 0 {
+                return Ok(res_table);
+            }
+
+            world_state_clone.borrow().get_space().visit_nearest(x, y, |id| {
+                let accepted = match &filter {
+                    Some(filter_fn) => filter_fn.call::<bool>(id)?,
+                    None => true,
+                };
+
+                if accepted {
+                    res_table.push(id)?;
+                }
+                Ok::<_, LuaError>(res_table.raw_len() < k)
+            })?;
+            Ok(res_table)
+        },
+    )?;
+
+    // Get state of an entity as of step start, nil if the entity does not exist
+    let world_state_clone = world_state.clone();
+    let get_entity_state_fn = lua.create_function(move |lua_ctx, id: String| {
+        let world_state = world_state_clone.borrow();
+        let views = world_state.get_entity_views().ok_or_else(state_queries_error)?;
+
+        match views.get(&id) {
+            Some(view) => Ok(Some(convert_to_lua_table(lua_ctx, &view.state)?)),
+            None => Ok(None),
+        }
+    })?;
+
+    // Find entities as of step start. Filter is either a function receiving entity view
+    // or a table with optional script_id, tag and state fields that must all match.
+    let world_state_clone = world_state.clone();
+    let find_entities_fn = lua.create_function(move |lua_ctx, filter: Option<LuaValue>| {
+        let world_state = world_state_clone.borrow();
+        let views = world_state.get_entity_views().ok_or_else(state_queries_error)?;
+
+        let mut ids: Vec<&String> = views.keys().collect();
+        ids.sort();
+
+        let res_table = lua_ctx.create_table()?;
+        for id in ids {
+            let view = &views[id];
+            let accepted = match &filter {
+                None | Some(LuaValue::Nil) => true,
+                Some(LuaValue::Function(filter_fn)) => {
+                    filter_fn.call::<bool>(create_view_table(lua_ctx, id, view)?)?
+                }
+                Some(LuaValue::Table(filter_table)) => matches_filter(lua_ctx, view, filter_table)?,
+                Some(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Filter must be a function or a table".to_string(),
+                    ));
+                }
+            };
+
+            if accepted {
+                res_table.push(create_view_table(lua_ctx, id, view)?)?;
+            }
+        }
+        Ok(res_table)
+    })?;
+
+    // Get value of a global variable, nil if it is not set
+    let world_state_clone = world_state.clone();
+    let get_global_fn = lua.create_function(move |lua_ctx, key: String| {
+        match world_state_clone.borrow().get_globals().get(&key) {
+            Some(value) => convert_json_to_lua_value(lua_ctx, value),
+            None => Ok(LuaValue::Nil),
+        }
+    })?;
+
+    // Set value of a global variable, applied at the end of the step. Nil removes the variable.
+    let command_queue_clone = command_queue.clone();
+    let set_global_fn = lua.create_function(move |lua_ctx, (key, value): (String, LuaValue)| {
+        command_queue_clone.borrow_mut().push(Command::SetGlobal {
+            key,
+            value: lua_to_json_value(lua_ctx, &value)?,
+        });
+        Ok(())
+    })?;
+
+    // Spawn entities of a population, applied at the end of the step
+    let command_queue_clone = command_queue.clone();
+    let spawn_population_fn = lua.create_function(move |lua_ctx, population: LuaTable| {
+        let population = serde_json::from_value(serde_json::Value::Object(convert_to_json(lua_ctx, &population)?))
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid population: {}", e)))?;
+
+        command_queue_clone.borrow_mut().push(Command::SpawnPopulation { population });
+        Ok(())
+    })?;
+
     world_lib.set("list_entities", list_entities_fn)?;
+    world_lib.set("spawn_population", spawn_population_fn)?;
+    world_lib.set("get_global", get_global_fn)?;
+    world_lib.set("set_global", set_global_fn)?;
+    world_lib.set("get_entity_state", get_entity_state_fn)?;
+    world_lib.set("find_entities", find_entities_fn)?;
+    world_lib.set("get_position", get_position_fn)?;
+    world_lib.set("query_radius", query_radius_fn)?;
+    world_lib.set("nearest", nearest_fn)?;
+    world_lib.set("get_cell", get_cell_fn)?;
+    world_lib.set("set_cell", set_cell_fn)?;
+    world_lib.set("cell_neighbors", cell_neighbors_fn)?;
+    world_lib.set("get_entity_cell", get_entity_cell_fn)?;
+    world_lib.set("neighbors", neighbors_fn)?;
     world_lib.set("record_metric", record_metric_fn)?;
+    world_lib.set("add_edge", add_edge_fn)?;
+    world_lib.set("remove_edge", remove_edge_fn)?;
 
     lua.globals().set("world", world_lib)?;
     Ok(())
 }
+
+// Proxy table that can be read and iterated but not modified, nested tables are wrapped as well
+fn create_read_only_table(lua: &Lua, object: &JSONObject) -> LuaResult<LuaTable> {
+    match convert_to_lua_table(lua, object)? {
+        LuaValue::Table(values) => wrap_read_only(lua, values),
+        _ => Err(LuaError::RuntimeError("Parameters are not a table".to_string())),
+    }
+}
+
+fn wrap_read_only(lua: &Lua, values: LuaTable) -> LuaResult<LuaTable> {
+    let nested: Vec<(LuaValue, LuaTable)> = values
+        .pairs::<LuaValue, LuaValue>()
+        .filter_map(|pair| match pair {
+            Ok((key, LuaValue::Table(table))) => Some(Ok((key, table))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<LuaResult<_>>()?;
+    for (key, table) in nested {
+        values.raw_set(key, wrap_read_only(lua, table)?)?;
+    }
+
+    let metatable = lua.create_table()?;
+    metatable.set("__index", values.clone())?;
+    metatable.set(
+        "__newindex",
+        lua.create_function(|_, (_, key): (LuaValue, LuaValue)| -> LuaResult<()> {
+            Err(LuaError::RuntimeError(format!("Parameter '{}' is read-only", key.to_string()?)))
+        })?,
+    )?;
+    let values_clone = values.clone();
+    metatable.set("__len", lua.create_function(move |_, _: LuaValue| Ok(values_clone.raw_len()))?)?;
+
+    // Iterate without handing out the table of values, which could be modified
+    let next: LuaFunction = lua.globals().get("next")?;
+    let iterate_fn = lua.create_function(move |_, (_, key): (LuaValue, LuaValue)| {
+        next.call::<LuaMultiValue>((values.clone(), key))
+    })?;
+    metatable.set(
+        "__pairs",
+        lua.create_function(move |_, proxy: LuaValue| Ok((iterate_fn.clone(), proxy, LuaValue::Nil)))?,
+    )?;
+    metatable.set("__metatable", false)?;
+
+    let proxy = lua.create_table()?;
+    proxy.set_metatable(Some(metatable))?;
+    Ok(proxy)
+}
+
+fn create_cell_table(lua: &Lua, cell: (u32, u32)) -> LuaResult<LuaTable> {
+    let cell_table = lua.create_table()?;
+    cell_table.set("x", cell.0)?;
+    cell_table.set("y", cell.1)?;
+    Ok(cell_table)
+}
+
+fn no_grid_error() -> LuaError {
+    LuaError::RuntimeError("World has no grid".to_string())
+}
+
+fn create_view_table(lua: &Lua, id: &str, view: &EntityView) -> LuaResult<LuaTable> {
+    let view_table = lua.create_table()?;
+    view_table.set("id", id)?;
+    view_table.set("script_id", view.script_id.clone())?;
+    view_table.set("tags", lua.create_sequence_from(view.tags.clone())?)?;
+    view_table.set("state", convert_to_lua_table(lua, &view.state)?)?;
+    Ok(view_table)
+}
+
+fn matches_filter(lua: &Lua, view: &EntityView, filter: &LuaTable) -> LuaResult<bool> {
+    if let Some(script_id) = filter.get::<Option<String>>("script_id")?
+        && script_id != view.script_id
+    {
+        return Ok(false);
+    }
+
+    if let Some(tag) = filter.get::<Option<String>>("tag")?
+        && !view.tags.contains(&tag)
+    {
+        return Ok(false);
+    }
+
+    if let Some(state_filter) = filter.get::<Option<LuaTable>>("state")? {
+        for (key, value) in convert_to_json(lua, &state_filter)? {
+            if view.state.get(&key) != Some(&value) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+fn state_queries_error() -> LuaError {
+    LuaError::RuntimeError("State queries are disabled in message-only worlds, entities learn about each other through messages".to_string())
+}
@@ -46,7 +46,7 @@ fn lua_table_to_json(lua: &Lua, table: &LuaTable) -> LuaResult<serde_json::Value
 }
 
 
-fn lua_to_json_value(lua: &Lua, value: &LuaValue) -> LuaResult<serde_json::Value> {
+pub fn lua_to_json_value(lua: &Lua, value: &LuaValue) -> LuaResult<serde_json::Value> {
     let json_value = match value {
         LuaValue::String(s) => serde_json::Value::String(s.to_str()?.to_string()),
         LuaValue::Integer(i) => serde_json::Value::Number((*i).into()),
@@ -88,7 +88,7 @@ pub fn convert_to_lua_table(lua: &Lua, object: &messaging::JSONObject) -> LuaRes
     Ok(LuaValue::Table(table))
 }
 
-fn convert_json_to_lua_value(lua: &Lua, value: &serde_json::Value) -> LuaResult<LuaValue> {
+pub fn convert_json_to_lua_value(lua: &Lua, value: &serde_json::Value) -> LuaResult<LuaValue> {
     let lua_value = match value {
         serde_json::Value::String(s) => LuaValue::String(lua.create_string(s)?),
         serde_json::Value::Bool(b) => LuaValue::Boolean(*b),
@@ -0,0 +1,214 @@
+use crate::core::errors::CoreError;
+use crate::core::messaging::{Command, DIRECTOR_ID, JSONObject, MessageReceiver};
+use crate::core::scripting::lua::controller::{register_world_lib, seed_random};
+use crate::core::scripting::lua::convert::{convert_to_json, convert_to_lua_table};
+use crate::core::scripting::lua::sandbox::{INSTRUCTION_LIMIT, create_sandboxed_lua, set_instruction_limit};
+use crate::core::world::WorldState;
+
+use mlua::Lua;
+use mlua::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// World-level script driving the scenario. All hooks are optional.
+pub struct LuaDirectorController {
+    lua_vm: mlua::Lua,
+    command_queue: Rc<RefCell<Vec<Command>>>, // Queue of commands to be executed by the world after a hook
+    executed_instructions: Rc<Cell<u64>>, // Reset before every call of the script, each call gets the full budget
+}
+
+impl LuaDirectorController {
+    pub fn new(script: &str, world_state: Rc<RefCell<WorldState>>) -> Result<Self, mlua::Error> {
+        let lua = create_sandboxed_lua()?;
+        let command_queue = Rc::new(RefCell::new(Vec::new()));
+
+        seed_random(&lua, DIRECTOR_ID, &world_state)?;
+        register_world_lib(&lua, command_queue.clone(), world_state)?;
+        register_director_lib(&lua, command_queue.clone())?;
+        let executed_instructions = set_instruction_limit(&lua, INSTRUCTION_LIMIT)?;
+
+        lua.load(script).exec()?;
+
+        Ok(LuaDirectorController {
+            lua_vm: lua,
+            command_queue,
+            executed_instructions,
+        })
+    }
+
+    pub fn on_step_begin(&self, simulation_time: u64) -> Result<(), CoreError> {
+        self.call_hook("on_step_begin", simulation_time)
+    }
+
+    pub fn on_step_end(&self, simulation_time: u64) -> Result<(), CoreError> {
+        self.call_hook("on_step_end", simulation_time)
+    }
+
+    pub fn on_entity_spawned(&self, entity_id: &str, script_id: &str) -> Result<(), CoreError> {
+        self.call_hook("on_entity_spawned", (entity_id, script_id))
+    }
+
+    pub fn on_entity_removed(&self, entity_id: &str) -> Result<(), CoreError> {
+        self.call_hook("on_entity_removed", entity_id)
+    }
+
+    // Take commands issued by the hooks called so far
+    pub fn take_commands(&self) -> Vec<Command> {
+        std::mem::take(&mut *self.command_queue.borrow_mut())
+    }
+
+    // State of the director, None if the script does not define get_state
+    pub fn get_state(&self) -> Result<Option<JSONObject>, CoreError> {
+        let get_state_fn: Option<LuaFunction> = self.lua_vm.globals().get("get_state").map_err(|e| {
+            CoreError::ScriptState {
+                message: format!("Error reading director get_state function: {}", e),
+            }
+        })?;
+
+        let Some(get_state_fn) = get_state_fn else {
+            return Ok(None);
+        };
+
+        self.executed_instructions.set(0);
+        let state = get_state_fn.call::<LuaTable>(()).map_err(|e| CoreError::ScriptState {
+            message: format!("Error calling director get_state function: {}", e),
+        })?;
+
+        convert_to_json(&self.lua_vm, &state)
+            .map(Some)
+            .map_err(|e| CoreError::ScriptState {
+                message: format!("Error serializing director state table: {}", e),
+            })
+    }
+
+    pub fn set_state(&self, state: JSONObject) -> Result<(), CoreError> {
+        let state_table = convert_to_lua_table(&self.lua_vm, &state).map_err(|e| CoreError::ScriptState {
+            message: format!("Error converting JSON to Lua table: {}", e),
+        })?;
+
+        self.call_lua_hook("set_state", state_table).map_err(|e| CoreError::ScriptState {
+            message: format!("Error executing director set_state function: {}", e),
+        })
+    }
+
+    fn call_hook(&self, name: &str, args: impl IntoLuaMulti) -> Result<(), CoreError> {
+        self.call_lua_hook(name, args).map_err(|e| CoreError::ScriptExecution {
+            message: format!("Error executing director {} function: {}", name, e),
+        })
+    }
+
+    // Call the hook if the script defines it
+    fn call_lua_hook(&self, name: &str, args: impl IntoLuaMulti) -> LuaResult<()> {
+        self.executed_instructions.set(0);
+        self.lua_vm
+            .globals()
+            .get::<Option<LuaFunction>>(name)
+            .and_then(|hook| match hook {
+                Some(hook) => hook.call::<()>(args),
+                None => Ok(()),
+            })
+    }
+}
+
+// Privileged operations available only to the director
+fn register_director_lib(lua: &Lua, command_queue: Rc<RefCell<Vec<Command>>>) -> LuaResult<()> {
+    let director_lib = lua.create_table()?;
+
+    // Inject message to an entity, a tag or a topic
+    let command_queue_clone = command_queue.clone();
+    let send_msg_fn = lua.create_function(
+        move |lua_ctx, (receiver_id, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: DIRECTOR_ID.to_string(),
+                receiver: MessageReceiver::Entity { id: receiver_id },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    let command_queue_clone = command_queue.clone();
+    let send_to_tag_fn = lua.create_function(
+        move |lua_ctx, (tag, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: DIRECTOR_ID.to_string(),
+                receiver: MessageReceiver::Tag { tag },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    let command_queue_clone = command_queue.clone();
+    let publish_fn = lua.create_function(
+        move |lua_ctx, (topic, kind, content, delay): (String, String, LuaTable, Option<u64>)| {
+            command_queue_clone.borrow_mut().push(Command::SendMessage {
+                sender: DIRECTOR_ID.to_string(),
+                receiver: MessageReceiver::Topic { topic },
+                kind,
+                content: convert_to_json(lua_ctx, &content)?,
+                delay: delay.unwrap_or(0),
+            });
+            Ok(())
+        },
+    )?;
+
+    let command_queue_clone = command_queue.clone();
+    let spawn_fn = lua.create_function(
+        move |lua_ctx, (entity_id, script_id, initial_state): (String, String, Option<LuaTable>)| {
+            let initial_state = match initial_state {
+                Some(table) => Some(convert_to_json(lua_ctx, &table)?),
+                None => None,
+            };
+
+            command_queue_clone.borrow_mut().push(Command::SpawnEntity {
+                entity_id,
+                script_id,
+                initial_state,
+            });
+            Ok(())
+        },
+    )?;
+
+    let command_queue_clone = command_queue.clone();
+    let remove_fn = lua.create_function(move |_, entity_id: String| {
+        command_queue_clone
+            .borrow_mut()
+            .push(Command::RemoveEntity { id: entity_id });
+        Ok(())
+    })?;
+
+    // Overwrite state of an entity, e.g. to apply a shock
+    let command_queue_clone = command_queue.clone();
+    let set_entity_state_fn = lua.create_function(move |lua_ctx, (entity_id, state): (String, LuaTable)| {
+        command_queue_clone.borrow_mut().push(Command::SetEntityState {
+            entity_id,
+            state: convert_to_json(lua_ctx, &state)?,
+        });
+        Ok(())
+    })?;
+
+    // End the run, no further steps are simulated
+    let command_queue_clone = command_queue.clone();
+    let stop_fn = lua.create_function(move |_, reason: Option<String>| {
+        command_queue_clone.borrow_mut().push(Command::StopSimulation {
+            reason: reason.unwrap_or_else(|| "Stopped by director".to_string()),
+        });
+        Ok(())
+    })?;
+
+    director_lib.set("send_msg", send_msg_fn)?;
+    director_lib.set("send_to_tag", send_to_tag_fn)?;
+    director_lib.set("publish", publish_fn)?;
+    director_lib.set("spawn_entity", spawn_fn)?;
+    director_lib.set("remove_entity", remove_fn)?;
+    director_lib.set("set_entity_state", set_entity_state_fn)?;
+    director_lib.set("stop", stop_fn)?;
+
+    lua.globals().set("director", director_lib)?;
+    Ok(())
+}
@@ -1,4 +1,11 @@
 mod controller;
+mod director;
+mod predicate;
+mod sandbox;
+mod validation;
 pub mod convert;
 
 pub use controller::LuaScriptController;
+pub use director::LuaDirectorController;
+pub use predicate::LuaPredicate;
+pub use validation::{DiagnosticSeverity, ScriptDiagnostic, validate_script};
@@ -0,0 +1,66 @@
+use crate::core::errors::CoreError;
+use crate::core::messaging::Command;
+use crate::core::scripting::lua::controller::register_world_lib;
+use crate::core::scripting::lua::sandbox::{INSTRUCTION_LIMIT, create_sandboxed_lua, set_instruction_limit};
+use crate::core::world::WorldState;
+
+use mlua::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// Condition over the world written in Lua, e.g. "#world.find_entities({tag = 'sheep'}) == 0".
+// It can use the world API, changes requested by it are discarded.
+pub struct LuaPredicate {
+    lua_vm: mlua::Lua,
+    predicate_fn: mlua::RegistryKey,
+    command_queue: Rc<RefCell<Vec<Command>>>,
+    executed_instructions: Rc<Cell<u64>>,
+}
+
+impl LuaPredicate {
+    pub fn new(source: &str, world_state: Rc<RefCell<WorldState>>) -> Result<Self, CoreError> {
+        let create_error = |e: LuaError| CoreError::ScriptExecution {
+            message: format!("Failed to compile predicate: {}", e),
+        };
+
+        let lua = create_sandboxed_lua().map_err(create_error)?;
+        let command_queue = Rc::new(RefCell::new(Vec::new()));
+
+        register_world_lib(&lua, command_queue.clone(), world_state).map_err(create_error)?;
+        let executed_instructions = set_instruction_limit(&lua, INSTRUCTION_LIMIT).map_err(create_error)?;
+
+        // Predicate is either an expression or a chunk returning the result
+        let predicate_fn = lua
+            .load(format!("return {}", source))
+            .into_function()
+            .or_else(|_| lua.load(source).into_function())
+            .map_err(create_error)?;
+        let predicate_fn = lua.create_registry_value(predicate_fn).map_err(create_error)?;
+
+        Ok(LuaPredicate {
+            lua_vm: lua,
+            predicate_fn,
+            command_queue,
+            executed_instructions,
+        })
+    }
+
+    // Result of the predicate, following Lua truthiness
+    pub fn evaluate(&self) -> Result<bool, CoreError> {
+        // Every evaluation gets the full instruction budget
+        self.executed_instructions.set(0);
+        let result = self
+            .lua_vm
+            .registry_value::<LuaFunction>(&self.predicate_fn)
+            .and_then(|predicate| predicate.call::<LuaValue>(()));
+
+        self.command_queue.borrow_mut().clear();
+
+        match result {
+            Ok(value) => Ok(!matches!(value, LuaValue::Nil | LuaValue::Boolean(false))),
+            Err(e) => Err(CoreError::ScriptExecution {
+                message: format!("Error evaluating predicate: {}", e),
+            }),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use mlua::prelude::*;
+use mlua::VmState;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Guard against endless loops, checked every INSTRUCTION_CHECK_INTERVAL instructions
+pub const INSTRUCTION_LIMIT: u64 = 10_000_000;
+const INSTRUCTION_CHECK_INTERVAL: u32 = 10_000;
+
+// Create a VM for running scripts. Scripts are untrusted, so only libraries without access to files, processes or
+// native modules are loaded (no io, os, package and debug).
+pub fn create_sandboxed_lua() -> LuaResult<Lua> {
+    let libraries = LuaStdLib::COROUTINE | LuaStdLib::TABLE | LuaStdLib::STRING | LuaStdLib::UTF8 | LuaStdLib::MATH;
+    let lua = Lua::new_with(libraries, LuaOptions::default())?;
+
+    // The base library is always loaded, remove its functions reading files and rawset, which would bypass
+    // read-only tables
+    let globals = lua.globals();
+    for name in ["dofile", "loadfile", "rawset"] {
+        globals.set(name, LuaNil)?;
+    }
+
+    Ok(lua)
+}
+
+// Stop code of the VM with an error once it has run more than the limit of instructions. Returns the count of
+// executed instructions, setting it to 0 renews the budget.
+pub fn set_instruction_limit(lua: &Lua, limit: u64) -> LuaResult<Rc<Cell<u64>>> {
+    let executed = Rc::new(Cell::new(0u64));
+    let executed_clone = executed.clone();
+    lua.set_hook(
+        LuaHookTriggers::new().every_nth_instruction(INSTRUCTION_CHECK_INTERVAL),
+        move |_, _| {
+            executed_clone.set(executed_clone.get() + INSTRUCTION_CHECK_INTERVAL as u64);
+            if executed_clone.get() > limit {
+                return Err(LuaError::RuntimeError(format!(
+                    "instruction limit of {} exceeded, the script may contain an endless loop",
+                    limit
+                )));
+            }
+            Ok(VmState::Continue)
+        },
+    )?;
+    Ok(executed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restricted_libraries() {
+        let lua = create_sandboxed_lua().unwrap();
+        let available: Vec<bool> = ["os", "io", "package", "debug", "dofile", "loadfile", "rawset", "require", "math", "string", "table"]
+            .iter()
+            .map(|name| lua.globals().get::<LuaValue>(*name).unwrap() != LuaNil)
+            .collect();
+        assert_eq!(available, vec![false, false, false, false, false, false, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let lua = create_sandboxed_lua().unwrap();
+        let executed = set_instruction_limit(&lua, 100_000).unwrap();
+        let count_to = lua.load("return function(n) local i = 0 while i < n do i = i + 1 end end").eval::<LuaFunction>().unwrap();
+
+        count_to.call::<()>(10_000).unwrap();
+        assert!(count_to.call::<()>(1_000_000).is_err());
+
+        // Renewed budget allows running again
+        executed.set(0);
+        count_to.call::<()>(10_000).unwrap();
+        assert!(lua.load("while true do end").exec().is_err());
+    }
+}
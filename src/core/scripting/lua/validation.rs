@@ -0,0 +1,239 @@
+use crate::core::messaging::{Command, JSONObject};
+use crate::core::scripting::lua::controller::register_lua_functions;
+use crate::core::scripting::lua::convert::{convert_to_json, convert_to_lua_table};
+use crate::core::scripting::lua::sandbox::{INSTRUCTION_LIMIT, create_sandboxed_lua, set_instruction_limit};
+use crate::core::world::WorldState;
+
+use mlua::prelude::*;
+use mlua::Lua;
+use rmcp::schemars;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Name of the chunk in error messages, used to find the line of a diagnostic
+const CHUNK_NAME: &str = "script";
+const REQUIRED_FUNCTIONS: [&str; 3] = ["update", "get_state", "set_state"];
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticSeverity {
+    Error,   // The script cannot be used
+    Warning, // The script may fail depending on the world
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ScriptDiagnostic {
+    #[schemars(description = "Whether the problem prevents using the script (error) or may depend on the world (warning)")]
+    pub severity: DiagnosticSeverity,
+    #[schemars(description = "Check that found the problem: parameters, syntax, load, required_functions, state_round_trip or update")]
+    pub check: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Line of the script, starting at 1")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Column of the line, starting at 1")]
+    pub column: Option<u32>,
+    #[schemars(description = "Description of the problem")]
+    pub message: String,
+}
+
+// Check the script in a separate VM with an empty world: compile it, run its top level code, check the required
+// functions, round-trip its state and run update once without messages. Commands issued by the script are discarded.
+pub fn validate_script(script: &str, parameters: &JSONObject, globals: &JSONObject) -> Vec<ScriptDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let world_state = Rc::new(RefCell::new(WorldState::new_sandbox(globals.clone())));
+    let command_queue: Rc<RefCell<Vec<Command>>> = Rc::new(RefCell::new(Vec::new()));
+
+    let lua = match create_sandboxed_lua() {
+        Ok(lua) => lua,
+        Err(e) => {
+            diagnostics.push(create_diagnostic(script, DiagnosticSeverity::Error, "load", &e));
+            return diagnostics;
+        }
+    };
+    if let Err(e) = register_lua_functions(&lua, CHUNK_NAME, parameters, command_queue, world_state)
+        .and_then(|_| set_instruction_limit(&lua, INSTRUCTION_LIMIT))
+    {
+        diagnostics.push(create_diagnostic(script, DiagnosticSeverity::Error, "load", &e));
+        return diagnostics;
+    }
+
+    let chunk = match lua.load(script).set_name(format!("={}", CHUNK_NAME)).into_function() {
+        Ok(chunk) => chunk,
+        Err(e) => {
+            diagnostics.push(create_diagnostic(script, DiagnosticSeverity::Error, "syntax", &e));
+            return diagnostics;
+        }
+    };
+
+    if let Err(e) = chunk.call::<()>(()) {
+        diagnostics.push(create_diagnostic(script, DiagnosticSeverity::Error, "load", &e));
+        return diagnostics;
+    }
+
+    for name in REQUIRED_FUNCTIONS {
+        if !matches!(lua.globals().get::<LuaValue>(name), Ok(LuaValue::Function(_))) {
+            diagnostics.push(ScriptDiagnostic {
+                severity: DiagnosticSeverity::Error,
+                check: "required_functions".to_string(),
+                line: None,
+                column: None,
+                message: format!("Script must define function '{}'", name),
+            });
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+
+    if let Err(diagnostic) = check_state_round_trip(&lua, script) {
+        diagnostics.push(diagnostic);
+    }
+
+    let update_result = lua
+        .create_table()
+        .and_then(|msgs| lua.globals().get::<LuaFunction>("update")?.call::<()>((0, msgs)));
+    if let Err(e) = update_result {
+        diagnostics.push(create_diagnostic(script, DiagnosticSeverity::Warning, "update", &e));
+    }
+
+    diagnostics
+}
+
+// State passed to set_state and read back must not change, otherwise snapshots lose state
+fn check_state_round_trip(lua: &Lua, script: &str) -> Result<(), ScriptDiagnostic> {
+    let get_state = || -> LuaResult<JSONObject> {
+        let state = lua.globals().get::<LuaFunction>("get_state")?.call::<LuaTable>(())?;
+        convert_to_json(lua, &state)
+    };
+
+    let state = get_state().map_err(|e| create_diagnostic(script, DiagnosticSeverity::Error, "state_round_trip", &e))?;
+
+    lua.globals()
+        .get::<LuaFunction>("set_state")
+        .and_then(|set_state| set_state.call::<()>(convert_to_lua_table(lua, &state)?))
+        .map_err(|e| create_diagnostic(script, DiagnosticSeverity::Error, "state_round_trip", &e))?;
+
+    let restored = get_state().map_err(|e| create_diagnostic(script, DiagnosticSeverity::Error, "state_round_trip", &e))?;
+    if restored != state {
+        return Err(ScriptDiagnostic {
+            severity: DiagnosticSeverity::Warning,
+            check: "state_round_trip".to_string(),
+            line: None,
+            column: None,
+            message: format!(
+                "get_state returns {} after set_state({}), the state is not restored from snapshots",
+                serde_json::Value::Object(restored),
+                serde_json::Value::Object(state)
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+fn create_diagnostic(script: &str, severity: DiagnosticSeverity, check: &str, error: &LuaError) -> ScriptDiagnostic {
+    let message = error_message(error);
+    let line = parse_line(&message);
+    let column = line.and_then(|line| parse_column(script, line, &message));
+
+    ScriptDiagnostic {
+        severity,
+        check: check.to_string(),
+        line,
+        column,
+        message,
+    }
+}
+
+// Message of the underlying error without traceback
+fn error_message(error: &LuaError) -> String {
+    let message = match error {
+        LuaError::SyntaxError { message, .. } => message.clone(),
+        LuaError::RuntimeError(message) => message.clone(),
+        LuaError::CallbackError { cause, .. } => return error_message(cause),
+        LuaError::WithContext { cause, .. } => return error_message(cause),
+        error => error.to_string(),
+    };
+
+    match message.split_once("\nstack traceback:") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
+
+// Lua reports location as "script:<line>: <message>"
+fn parse_line(message: &str) -> Option<u32> {
+    let (_, rest) = message.split_once(&format!("{}:", CHUNK_NAME))?;
+    let (line, _) = rest.split_once(':')?;
+    line.parse().ok()
+}
+
+// Syntax errors name the token "near '<token>'", its position in the line is the column
+fn parse_column(script: &str, line: u32, message: &str) -> Option<u32> {
+    let (_, rest) = message.split_once("near '")?;
+    let (token, _) = rest.rsplit_once('\'')?;
+    let source_line = script.lines().nth(line.checked_sub(1)? as usize)?;
+    source_line.find(token).map(|index| index as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(script: &str) -> Vec<ScriptDiagnostic> {
+        validate_script(script, &JSONObject::new(), &JSONObject::new())
+    }
+
+    #[test]
+    fn test_valid_script() {
+        let diagnostics = validate(
+            r#"
+            local count = 0
+            function update(current_time, msgs) count = count + world.get_global("step") end
+            function get_state() return {count = count} end
+            function set_state(state) count = state.count end
+            "#,
+        );
+        // Global is missing in the empty world
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[0].check, "update");
+        assert_eq!(diagnostics[0].line, Some(3));
+
+        let globals = serde_json::json!({"step": 1}).as_object().unwrap().clone();
+        let diagnostics = validate_script(
+            "function update() world.get_global('step') end function get_state() return {} end function set_state(s) end",
+            &JSONObject::new(),
+            &globals,
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let diagnostics = validate("function update()\n  local x = 1 +* 2\nend");
+        assert_eq!(diagnostics[0].check, "syntax");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(2), Some(16)));
+
+        let diagnostics = validate("function update() end");
+        let missing: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(missing, vec!["Script must define function 'get_state'", "Script must define function 'set_state'"]);
+
+        let diagnostics = validate("while true do end");
+        assert_eq!(diagnostics[0].check, "load");
+        assert!(diagnostics[0].message.contains("instruction limit"));
+
+        let diagnostics = validate(
+            r#"
+            local count = 0
+            function update() end
+            function get_state() return {count = count} end
+            function set_state(state) count = state.count + 1 end
+            "#,
+        );
+        assert_eq!(diagnostics[0].check, "state_round_trip");
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+    }
+}
@@ -10,6 +10,10 @@ pub struct WorldSnapshot {
     pub simulation_time: u64, // Simulation time at which the snapshot was taken
     pub metrics: MetricsSnapshot,
     pub pending_messages: Vec<Message>,
+    #[serde(default)]
+    pub next_message_id: u64, // Keeps IDs of messages unique after restore
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>, // Set if the run was ended before the snapshot
 }
 
 #[derive(Clone, Serialize, Deserialize, schemars::JsonSchema)]
@@ -22,13 +26,17 @@ impl WorldSnapshot {
         configuration: WorldCfg,
         simulation_time: u64,
         pending_messages: Vec<Message>,
+        next_message_id: u64,
         metrics: MetricsSnapshot,
+        stop_reason: Option<String>,
     ) -> Self {
         WorldSnapshot {
             configuration,
             simulation_time,
             pending_messages,
+            next_message_id,
             metrics,
+            stop_reason,
         }
     }
 
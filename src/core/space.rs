@@ -0,0 +1,388 @@
+use crate::core::world_config::{Boundary, SpaceCfg};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// Cell size of the index of an unbounded space
+const DEFAULT_CELL_SIZE: f64 = 10.0;
+// Cells along the longer side of a bounded space without configured cell size
+const DEFAULT_CELLS_PER_SIDE: f64 = 32.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+}
+
+// Continuous 2D space with entity kinematics. Without bounds the space is infinite.
+// Positions are indexed in a uniform grid of cells so queries only visit entities near the queried point.
+pub struct Space {
+    bounds: Option<SpaceCfg>,
+    bodies: BTreeMap<String, Body>, // Entity ID to its position and velocity
+    cell_size: f64,
+    cells: HashMap<(i64, i64), BTreeSet<String>>, // Cell to IDs of entities positioned in it
+}
+
+impl Space {
+    pub fn new(bounds: Option<SpaceCfg>) -> Self {
+        let cell_size = match &bounds {
+            Some(bounds) => bounds.cell_size.unwrap_or(bounds.width.max(bounds.height) / DEFAULT_CELLS_PER_SIDE),
+            None => DEFAULT_CELL_SIZE,
+        };
+
+        Space {
+            bounds,
+            bodies: BTreeMap::new(),
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn get_body(&self, entity_id: &str) -> Option<Body> {
+        self.bodies.get(entity_id).copied()
+    }
+
+    // Place the entity. Returns false and keeps the entity where it was if a coordinate is not finite.
+    pub fn set_position(&mut self, entity_id: &str, x: f64, y: f64) -> bool {
+        if !x.is_finite() || !y.is_finite() {
+            return false;
+        }
+
+        let previous = self.bodies.get(entity_id).copied();
+        if let Some(previous) = previous {
+            remove_from_cell(&mut self.cells, self.cell_size, entity_id, &previous);
+        }
+
+        let mut body = previous.unwrap_or(Body {
+            x,
+            y,
+            vx: 0.0,
+            vy: 0.0,
+        });
+        body.x = x;
+        body.y = y;
+        apply_bounds(&self.bounds, &mut body);
+
+        self.cells.entry(cell_of(self.cell_size, body.x, body.y)).or_default().insert(entity_id.to_string());
+        self.bodies.insert(entity_id.to_string(), body);
+        true
+    }
+
+    // Set velocity of a positioned entity. Returns false if the entity has no position or a component is not finite.
+    pub fn set_velocity(&mut self, entity_id: &str, vx: f64, vy: f64) -> bool {
+        match self.bodies.get_mut(entity_id) {
+            Some(body) if vx.is_finite() && vy.is_finite() => {
+                body.vx = vx;
+                body.vy = vy;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn remove_entity(&mut self, entity_id: &str) {
+        if let Some(body) = self.bodies.remove(entity_id) {
+            remove_from_cell(&mut self.cells, self.cell_size, entity_id, &body);
+        }
+    }
+
+    // Move all bodies by their velocity over the elapsed time
+    pub fn step(&mut self, delta: u64) {
+        for (id, body) in self.bodies.iter_mut() {
+            let previous_cell = cell_of(self.cell_size, body.x, body.y);
+            body.x += body.vx * delta as f64;
+            body.y += body.vy * delta as f64;
+            apply_bounds(&self.bounds, body);
+
+            let cell = cell_of(self.cell_size, body.x, body.y);
+            if cell != previous_cell {
+                if let Some(ids) = self.cells.get_mut(&previous_cell) {
+                    ids.remove(id);
+                    if ids.is_empty() {
+                        self.cells.remove(&previous_cell);
+                    }
+                }
+                self.cells.entry(cell).or_default().insert(id.clone());
+            }
+        }
+    }
+
+    // Distance between points, shortest way around if the space wraps
+    pub fn distance(&self, ax: f64, ay: f64, bx: f64, by: f64) -> f64 {
+        let mut dx = (ax - bx).abs();
+        let mut dy = (ay - by).abs();
+
+        if let Some(bounds) = &self.bounds
+            && let Boundary::Wrap = bounds.boundary
+        {
+            dx = dx.min(bounds.width - dx);
+            dy = dy.min(bounds.height - dy);
+        }
+
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    // IDs of entities within radius of the point, ordered by distance
+    pub fn query_radius(&self, x: f64, y: f64, radius: f64) -> Vec<String> {
+        self.query_ring(x, y, None, radius).into_iter().map(|(_, id)| id).collect()
+    }
+
+    // Visit entities in order of distance from the point until the visitor returns false. Rings of doubling
+    // radius are searched, so an early stop only visits the index near the point.
+    pub fn visit_nearest<E>(&self, x: f64, y: f64, mut visit: impl FnMut(&str) -> Result<bool, E>) -> Result<(), E> {
+        if !x.is_finite() || !y.is_finite() {
+            return Ok(());
+        }
+
+        let mut inner = None;
+        let mut radius = self.cell_size;
+        let mut visited = 0;
+        while visited < self.bodies.len() {
+            for (_, id) in self.query_ring(x, y, inner, radius) {
+                visited += 1;
+                if !visit(&id)? {
+                    return Ok(());
+                }
+            }
+
+            // Bodies with non-finite positions are never within any radius
+            if radius.is_infinite() {
+                break;
+            }
+            inner = Some(radius);
+            radius *= 2.0;
+        }
+        Ok(())
+    }
+
+    // Entities farther than inner radius (if any) and at most radius from the point, ordered by distance
+    fn query_ring(&self, x: f64, y: f64, inner: Option<f64>, radius: f64) -> Vec<(f64, String)> {
+        let (x, y, wrap) = match &self.bounds {
+            Some(bounds) if matches!(bounds.boundary, Boundary::Wrap) => {
+                (x.rem_euclid(bounds.width), y.rem_euclid(bounds.height), Some((bounds.width, bounds.height)))
+            }
+            _ => (x, y, None),
+        };
+
+        let x_ranges = self.cell_ranges(x, radius, wrap.map(|(width, _)| width));
+        let y_ranges = self.cell_ranges(y, radius, wrap.map(|(_, height)| height));
+        let cells_count: f64 = x_ranges.iter().map(|(lo, hi)| *hi as f64 - *lo as f64 + 1.0).sum::<f64>()
+            * y_ranges.iter().map(|(lo, hi)| *hi as f64 - *lo as f64 + 1.0).sum::<f64>();
+
+        // Large queries scan the occupied cells instead of the covered ones
+        let candidates: Vec<&String> = if cells_count > self.cells.len() as f64 {
+            self.bodies.keys().collect()
+        } else {
+            let mut cells = BTreeSet::new();
+            for (x_lo, x_hi) in &x_ranges {
+                for (y_lo, y_hi) in &y_ranges {
+                    for cell_x in *x_lo..=*x_hi {
+                        for cell_y in *y_lo..=*y_hi {
+                            cells.insert((cell_x, cell_y));
+                        }
+                    }
+                }
+            }
+            cells.iter().filter_map(|cell| self.cells.get(cell)).flatten().collect()
+        };
+
+        let mut entities: Vec<(f64, String)> = candidates
+            .into_iter()
+            .filter_map(|id| {
+                let body = &self.bodies[id];
+                let distance = self.distance(x, y, body.x, body.y);
+                let in_ring = distance <= radius && inner.is_none_or(|inner| distance > inner);
+                in_ring.then(|| (distance, id.clone()))
+            })
+            .collect();
+
+        entities.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        entities
+    }
+
+    // Ranges of cell indices along an axis covering the interval around the center. A wrapping axis of the size
+    // splits the interval at its bounds.
+    fn cell_ranges(&self, center: f64, radius: f64, wrap_size: Option<f64>) -> Vec<(i64, i64)> {
+        let (lo, hi) = (center - radius, center + radius);
+        let intervals = match wrap_size {
+            Some(size) if 2.0 * radius >= size => vec![(0.0, size)],
+            Some(size) if lo < 0.0 => vec![(lo + size, size), (0.0, hi)],
+            Some(size) if hi > size => vec![(lo, size), (0.0, hi - size)],
+            _ => vec![(lo, hi)],
+        };
+
+        intervals
+            .into_iter()
+            .map(|(lo, hi)| (cell_index(self.cell_size, lo), cell_index(self.cell_size, hi)))
+            .collect()
+    }
+
+    pub fn get_bodies(&self) -> &BTreeMap<String, Body> {
+        &self.bodies
+    }
+
+    pub fn get_bounds(&self) -> Option<&SpaceCfg> {
+        self.bounds.as_ref()
+    }
+}
+
+// Cell index along an axis, saturating far away from the origin
+fn cell_index(cell_size: f64, position: f64) -> i64 {
+    (position / cell_size).floor() as i64
+}
+
+fn cell_of(cell_size: f64, x: f64, y: f64) -> (i64, i64) {
+    (cell_index(cell_size, x), cell_index(cell_size, y))
+}
+
+fn remove_from_cell(cells: &mut HashMap<(i64, i64), BTreeSet<String>>, cell_size: f64, entity_id: &str, body: &Body) {
+    let cell = cell_of(cell_size, body.x, body.y);
+    if let Some(ids) = cells.get_mut(&cell) {
+        ids.remove(entity_id);
+        if ids.is_empty() {
+            cells.remove(&cell);
+        }
+    }
+}
+
+fn apply_bounds(bounds: &Option<SpaceCfg>, body: &mut Body) {
+    if let Some(bounds) = bounds {
+        let (x, vx) = bound_axis(body.x, body.vx, bounds.width, &bounds.boundary);
+        let (y, vy) = bound_axis(body.y, body.vy, bounds.height, &bounds.boundary);
+        *body = Body { x, y, vx, vy };
+    }
+}
+
+fn bound_axis(position: f64, velocity: f64, size: f64, boundary: &Boundary) -> (f64, f64) {
+    match boundary {
+        Boundary::Clamp => (position.clamp(0.0, size), velocity),
+        Boundary::Wrap => (position.rem_euclid(size), velocity),
+        Boundary::Bounce => {
+            // Reflect position back into the bounds, direction flips on each reflection
+            let period = 2.0 * size;
+            let folded = position.rem_euclid(period);
+            let reflections = (position / size).floor() as i64;
+            let velocity = if reflections.rem_euclid(2) == 0 { velocity } else { -velocity };
+
+            if folded <= size {
+                (folded, velocity)
+            } else {
+                (period - folded, velocity)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounded(boundary: Boundary) -> Space {
+        Space::new(Some(SpaceCfg {
+            width: 10.0,
+            height: 10.0,
+            boundary,
+            cell_size: Some(2.0),
+        }))
+    }
+
+    #[test]
+    fn test_movement_and_boundaries() {
+        let mut space = bounded(Boundary::Clamp);
+        space.set_position("a", 9.0, 5.0);
+        space.set_velocity("a", 2.0, 1.0);
+        space.step(1);
+        assert_eq!(space.get_body("a").unwrap(), Body { x: 10.0, y: 6.0, vx: 2.0, vy: 1.0 });
+
+        let mut space = bounded(Boundary::Wrap);
+        space.set_position("a", 9.0, 5.0);
+        space.set_velocity("a", 2.0, 0.0);
+        space.step(1);
+        assert_eq!(space.get_body("a").unwrap().x, 1.0);
+
+        let mut space = bounded(Boundary::Bounce);
+        space.set_position("a", 9.0, 5.0);
+        space.set_velocity("a", 2.0, 0.0);
+        space.step(1);
+        assert_eq!(space.get_body("a").unwrap(), Body { x: 9.0, y: 5.0, vx: -2.0, vy: 0.0 });
+        space.step(5);
+        assert_eq!(space.get_body("a").unwrap(), Body { x: 1.0, y: 5.0, vx: 2.0, vy: 0.0 });
+
+        // Velocity requires a position, non-finite values are rejected
+        assert!(!space.set_velocity("b", 1.0, 1.0));
+        assert!(!space.set_velocity("a", f64::NAN, 0.0));
+        assert!(!space.set_position("a", f64::INFINITY, 0.0));
+        assert!(!space.set_position("c", 0.0, f64::NAN));
+        assert_eq!(space.get_body("a").unwrap(), Body { x: 1.0, y: 5.0, vx: 2.0, vy: 0.0 });
+        assert!(space.get_body("c").is_none());
+        assert!(space.query_radius(0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut space = bounded(Boundary::Wrap);
+        space.set_position("a", 1.0, 1.0);
+        space.set_position("b", 9.0, 1.0);
+        space.set_position("c", 5.0, 5.0);
+
+        // "b" is close to "a" around the edge
+        assert_eq!(space.query_radius(1.0, 1.0, 3.0), vec!["a", "b"]);
+
+        let mut nearest = Vec::new();
+        space
+            .visit_nearest(6.0, 6.0, |id| {
+                nearest.push(id.to_string());
+                Ok::<_, ()>(true)
+            })
+            .unwrap();
+        assert_eq!(nearest, vec!["c", "b", "a"]);
+
+        // Visiting stops when the visitor declines
+        let mut nearest = Vec::new();
+        space
+            .visit_nearest(6.0, 6.0, |id| {
+                nearest.push(id.to_string());
+                Ok::<_, ()>(nearest.len() < 2)
+            })
+            .unwrap();
+        assert_eq!(nearest, vec!["c", "b"]);
+
+        // Moved and removed entities leave their cells
+        space.set_position("c", 1.0, 2.0);
+        space.remove_entity("b");
+        assert_eq!(space.query_radius(1.0, 1.0, 3.0), vec!["a", "c"]);
+        space.set_velocity("c", 0.0, 4.0);
+        space.step(1);
+        assert_eq!(space.query_radius(1.0, 1.0, 3.0), vec!["a"]);
+        assert_eq!(space.query_radius(1.0, 6.0, 0.5), vec!["c"]);
+    }
+
+    #[test]
+    fn test_index_matches_scan() {
+        for boundary in [Boundary::Clamp, Boundary::Wrap] {
+            // Queries of a wrapping space are taken around the edge
+            let wrap = |position: f64| match boundary {
+                Boundary::Wrap => position.rem_euclid(10.0),
+                _ => position,
+            };
+            let mut space = bounded(boundary.clone());
+            for i in 0..50 {
+                let x = (i * 37 % 101) as f64 / 10.0;
+                let y = (i * 53 % 103) as f64 / 10.0;
+                space.set_position(&format!("e{}", i), x, y);
+            }
+
+            for (x, y, radius) in [(0.5, 0.5, 1.5), (9.5, 5.0, 3.0), (5.0, 5.0, 0.0), (2.0, 8.0, 20.0), (-1.0, 11.0, 2.5)] {
+                let mut expected: Vec<(f64, String)> = space
+                    .get_bodies()
+                    .iter()
+                    .map(|(id, body)| (space.distance(wrap(x), wrap(y), body.x, body.y), id.clone()))
+                    .filter(|(distance, _)| *distance <= radius)
+                    .collect();
+                expected.sort_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+                let expected: Vec<String> = expected.into_iter().map(|(_, id)| id).collect();
+                assert_eq!(space.query_radius(x, y, radius), expected);
+            }
+        }
+    }
+}
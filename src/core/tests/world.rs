@@ -1,6 +1,10 @@
-use crate::core::snapshot::WorldSnapshot;
+use crate::core::condition::{StopConditionCfg, StopConditions};
+use crate::core::errors::CoreError;
+use crate::core::messaging::{JSONObject, MessageReceiver};
 use crate::core::world::World;
-use crate::core::world_config::WorldCfg;
+use crate::core::world_config::{
+    Boundary, DirectorCfg, EntityCfg, FieldCfg, GridCfg, Neighborhood, NetworkCfg, ScriptCfg, SpaceCfg, WorldCfg,
+};
 
 #[test]
 fn test_load_from_file() {
@@ -51,3 +55,1373 @@ fn test_snapshosts() {
         assert_eq!(entity_cfg.script_id, original_entity_cfg.script_id);
     });
 }
+
+#[test]
+fn test_topic_publish_subscribe() {
+    let mut world_cfg = WorldCfg::new("pubsub_world".to_string());
+    world_cfg.add_script(
+        "publisher".to_string(),
+        r#"
+        function update(current_time, msgs)
+            self.publish("prices", "price", {value = 42}, 0)
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.add_script(
+        "listener".to_string(),
+        r#"
+        local received = 0
+        function update(current_time, msgs)
+            for _, msg in ipairs(msgs) do
+                if msg.topic == "prices" then
+                    received = received + 1
+                end
+            end
+            if received >= 2 then
+                self.unsubscribe("prices")
+            end
+        end
+        function get_state() return {received = received} end
+        function set_state(state) received = state.received end
+        "#
+        .to_string(),
+    );
+
+    world_cfg.entities.push(EntityCfg {
+        id: "market".to_string(),
+        script_id: "publisher".to_string(),
+        ..Default::default()
+    });
+    world_cfg.entities.push(EntityCfg {
+        id: "trader".to_string(),
+        script_id: "listener".to_string(),
+        initial_state: Some(serde_json::json!({"received": 0}).as_object().unwrap().clone()),
+        subscriptions: vec!["prices".to_string()],
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+
+    world.update(1).unwrap();
+    let snapshot = world.create_snapshot().unwrap();
+    let trader_cfg = snapshot.configuration.entities.iter().find(|e| e.id == "trader").unwrap();
+    assert_eq!(trader_cfg.subscriptions, vec!["prices".to_string()]);
+
+    for _ in 0..5 {
+        world.update(1).unwrap();
+    }
+
+    // Trader unsubscribes after receiving two prices
+    let state = world.get_entity_state("trader").unwrap();
+    assert_eq!(state.get("received").unwrap(), 2);
+
+    let snapshot = world.create_snapshot().unwrap();
+    let trader_cfg = snapshot.configuration.entities.iter().find(|e| e.id == "trader").unwrap();
+    assert!(trader_cfg.subscriptions.is_empty());
+}
+
+#[test]
+fn test_tag_addressing() {
+    let mut world_cfg = WorldCfg::new("tag_world".to_string());
+    world_cfg.add_script(
+        "farmer".to_string(),
+        r#"
+        function update(current_time, msgs)
+            if current_time == 1 then
+                self.send_to_tag("wolf", "alarm", {from = self.id}, 0)
+            end
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.add_script(
+        "animal".to_string(),
+        r#"
+        local alarms = 0
+        function update(current_time, msgs)
+            alarms = alarms + #msgs
+            if current_time == 1 and self.id == "dog" then
+                self.add_tag("wolf")
+            end
+        end
+        function get_state() return {alarms = alarms} end
+        function set_state(state) alarms = state.alarms end
+        "#
+        .to_string(),
+    );
+
+    world_cfg.entities.push(EntityCfg {
+        id: "farmer".to_string(),
+        script_id: "farmer".to_string(),
+        ..Default::default()
+    });
+    for (id, tags) in [("wolf_1", vec!["wolf"]), ("wolf_2", vec!["wolf"]), ("sheep", vec!["prey"]), ("dog", vec![])] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "animal".to_string(),
+            tags: tags.into_iter().map(String::from).collect(),
+            ..Default::default()
+        });
+    }
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    world.update(1).unwrap();
+
+    // Dog got its tag after the alarm was sent, but the tag is resolved at delivery time
+    for (id, expected) in [("wolf_1", 1), ("wolf_2", 1), ("sheep", 0), ("dog", 1)] {
+        let state = world.get_entity_state(id).unwrap();
+        assert_eq!(state.get("alarms").unwrap(), expected, "entity {}", id);
+    }
+
+    let snapshot = world.create_snapshot().unwrap();
+    let dog_cfg = snapshot.configuration.entities.iter().find(|e| e.id == "dog").unwrap();
+    assert_eq!(dog_cfg.tags, vec!["wolf".to_string()]);
+
+    // Index of tags follows added tags and removed entities, groups are tags
+    world.remove_entity("wolf_1").unwrap();
+    assert_eq!(world.get_state_ref().get_tagged_entities("wolf"), vec!["dog", "wolf_2"]);
+
+    world
+        .send_message("external", MessageReceiver::Group { name: "wolf".to_string() }, "call".to_string(), JSONObject::new(), 0)
+        .unwrap();
+    world.update(1).unwrap();
+    for (id, expected) in [("wolf_2", 2), ("sheep", 0), ("dog", 2)] {
+        let state = world.get_entity_state(id).unwrap();
+        assert_eq!(state.get("alarms").unwrap(), expected, "entity {}", id);
+    }
+}
+
+#[test]
+fn test_network_neighbors() {
+    let mut world_cfg = WorldCfg::new("network_world".to_string());
+    world_cfg.add_script(
+        "node".to_string(),
+        r#"
+        local infected = false
+        function update(current_time, msgs)
+            if #msgs > 0 then
+                infected = true
+            end
+            if infected then
+                self.send_to_neighbors("infection", {}, 0)
+            end
+            if self.id == "a" and current_time == 1 then
+                world.remove_edge("c", "d")
+            end
+        end
+        function get_state() return {infected = infected, degree = #world.neighbors(self.id)} end
+        function set_state(state) infected = state.infected end
+        "#
+        .to_string(),
+    );
+
+    for id in ["a", "b", "c", "d"] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "node".to_string(),
+            initial_state: Some(serde_json::json!({"infected": id == "a"}).as_object().unwrap().clone()),
+            ..Default::default()
+        });
+    }
+    world_cfg.network = Some(NetworkCfg {
+        directed: false,
+        edges: vec![
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "c".to_string()),
+            ("c".to_string(), "d".to_string()),
+        ],
+        generator: None,
+        seed: None,
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    for _ in 0..5 {
+        world.update(1).unwrap();
+    }
+
+    // Edge to "d" was removed before the infection reached "c"
+    for (id, infected) in [("a", true), ("b", true), ("c", true), ("d", false)] {
+        let state = world.get_entity_state(id).unwrap();
+        assert_eq!(state.get("infected").unwrap(), infected, "entity {}", id);
+    }
+    assert_eq!(world.get_entity_state("d").unwrap().get("degree").unwrap(), 0);
+
+    let snapshot = world.create_snapshot().unwrap();
+    let network_cfg = snapshot.configuration.network.unwrap();
+    assert_eq!(network_cfg.edges.len(), 2);
+}
+
+#[test]
+fn test_grid_world() {
+    let mut world_cfg = WorldCfg::new("grid_world".to_string());
+    world_cfg.add_script(
+        "sheep".to_string(),
+        r#"
+        local eaten = 0
+        function update(current_time, msgs)
+            local pos = world.get_entity_cell(self.id)
+            local cell = world.get_cell(pos.x, pos.y)
+            eaten = eaten + cell.fields.grass
+            world.set_cell(pos.x, pos.y, "grass", 0)
+            self.move_to(pos.x + 1, pos.y)
+        end
+        function get_state() return {eaten = eaten} end
+        function set_state(state) eaten = state.eaten end
+        "#
+        .to_string(),
+    );
+
+    let mut fields = std::collections::BTreeMap::new();
+    fields.insert(
+        "grass".to_string(),
+        FieldCfg { initial: 1.0, values: None, diffusion: 0.0, decay: 0.0 },
+    );
+    world_cfg.grid = Some(GridCfg {
+        width: 3,
+        height: 2,
+        wrap: true,
+        neighborhood: Neighborhood::VonNeumann,
+        fields,
+    });
+    world_cfg.entities.push(EntityCfg {
+        id: "sheep".to_string(),
+        script_id: "sheep".to_string(),
+        cell: Some((0, 1)),
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    for _ in 0..4 {
+        world.update(1).unwrap();
+    }
+
+    // Sheep wrapped around the row and found the first cell already grazed
+    assert_eq!(world.get_entity_state("sheep").unwrap().get("eaten").unwrap(), 3.0);
+
+    let snapshot = world.create_snapshot().unwrap();
+    let grid_cfg = snapshot.configuration.grid.as_ref().unwrap();
+    assert_eq!(grid_cfg.fields["grass"].values.as_ref().unwrap(), &vec![1.0, 1.0, 1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(snapshot.configuration.entities[0].cell, Some((1, 1)));
+
+    let restored = World::new_from_snapshot(snapshot).unwrap();
+    let state = restored.get_state_ref();
+    assert_eq!(state.get_grid().unwrap().get_position("sheep"), Some((1, 1)));
+}
+
+#[test]
+fn test_continuous_space() {
+    let mut world_cfg = WorldCfg::new("space_world".to_string());
+    world_cfg.add_script(
+        "boid".to_string(),
+        r#"
+        local heard = 0
+        local nearest = ""
+        function update(current_time, msgs)
+            heard = heard + #msgs
+            local pos = world.get_position(self.id)
+            local others = world.nearest(pos.x, pos.y, 1, function(id) return id ~= self.id end)
+            nearest = others[1]
+            if self.id == "leader" then
+                self.broadcast_msg(pos.x, pos.y, 3.0, "follow", {})
+            end
+        end
+        function get_state() return {heard = heard, nearest = nearest} end
+        function set_state(state) heard = state.heard end
+        "#
+        .to_string(),
+    );
+
+    world_cfg.space = Some(SpaceCfg { width: 20.0, height: 20.0, boundary: Boundary::Clamp, cell_size: None });
+    for (id, position, velocity) in [
+        ("leader", (0.0, 0.0), (1.0, 0.0)),
+        ("near", (4.0, 0.0), (0.0, 0.0)),
+        ("far", (10.0, 10.0), (0.0, 0.0)),
+    ] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "boid".to_string(),
+            position: Some(position),
+            velocity: Some(velocity),
+            ..Default::default()
+        });
+    }
+
+    let mut world = World::new(&world_cfg).unwrap();
+    for _ in 0..3 {
+        world.update(1).unwrap();
+    }
+
+    // Only the broadcast sent after the leader moved within range reaches "near"
+    assert_eq!(world.get_entity_state("near").unwrap().get("heard").unwrap(), 1);
+    assert_eq!(world.get_entity_state("far").unwrap().get("heard").unwrap(), 0);
+    assert_eq!(world.get_entity_state("leader").unwrap().get("heard").unwrap(), 0);
+    assert_eq!(world.get_entity_state("far").unwrap().get("nearest").unwrap(), "near");
+
+    let snapshot = world.create_snapshot().unwrap();
+    let leader_cfg = snapshot.configuration.entities.iter().find(|e| e.id == "leader").unwrap();
+    assert_eq!(leader_cfg.position, Some((3.0, 0.0)));
+    assert_eq!(leader_cfg.velocity, Some((1.0, 0.0)));
+
+    // Bounds and cells must be positive sizes
+    for (width, cell_size) in [(f64::NAN, None), (f64::INFINITY, None), (20.0, Some(0.0)), (20.0, Some(f64::NAN))] {
+        world_cfg.space = Some(SpaceCfg { width, height: 20.0, boundary: Boundary::Clamp, cell_size });
+        assert!(World::new(&world_cfg).is_err());
+    }
+
+    // Coordinates must be finite and a velocity needs a position
+    world_cfg.space = Some(SpaceCfg { width: 20.0, height: 20.0, boundary: Boundary::Clamp, cell_size: None });
+    world_cfg.entities[0].position = Some((f64::NAN, 0.0));
+    assert!(World::new(&world_cfg).is_err());
+    world_cfg.entities[0].position = Some((0.0, 0.0));
+    world_cfg.entities[0].velocity = Some((0.0, f64::INFINITY));
+    assert!(World::new(&world_cfg).is_err());
+
+    let mut world_cfg = WorldCfg::new("space_world".to_string());
+    world_cfg.space = Some(SpaceCfg { width: 20.0, height: 20.0, boundary: Boundary::Clamp, cell_size: None });
+    for (script_id, body) in [
+        ("placed", "self.set_position(1, 2) self.set_velocity(1, 0)"),
+        ("nan", "self.set_position(0/0, 2)"),
+        ("infinite", "self.set_position(1, 2) self.set_velocity(math.huge, 0)"),
+        ("unplaced", "self.set_velocity(1, 0)"),
+    ] {
+        world_cfg.add_script(
+            script_id.to_string(),
+            format!("function update(current_time, msgs) {} end function get_state() return {{}} end function set_state(state) end", body),
+        );
+        world_cfg.entities = vec![EntityCfg {
+            id: script_id.to_string(),
+            script_id: script_id.to_string(),
+            ..Default::default()
+        }];
+        let mut world = World::new(&world_cfg).unwrap();
+        let result = world.update(1);
+        assert_eq!(result.is_ok(), script_id == "placed", "{}", script_id);
+    }
+}
+
+#[test]
+fn test_entity_state_queries() {
+    let mut world_cfg = WorldCfg::new("query_world".to_string());
+    world_cfg.add_script(
+        "counter".to_string(),
+        r#"
+        local count = 0
+        local seen = -1
+        local wolves = 0
+        function update(current_time, msgs)
+            count = count + 1
+            local other = self.id == "a" and "b" or "a"
+            seen = world.get_entity_state(other).count
+            wolves = #world.find_entities({tag = "wolf", state = {count = count - 1}})
+        end
+        function get_state() return {count = count, seen = seen, wolves = wolves} end
+        function set_state(state) count = state.count end
+        "#
+        .to_string(),
+    );
+
+    for (id, tags) in [("a", vec!["wolf".to_string()]), ("b", vec![])] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "counter".to_string(),
+            initial_state: Some(serde_json::json!({"count": 0}).as_object().unwrap().clone()),
+            tags,
+            ..Default::default()
+        });
+    }
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    world.update(1).unwrap();
+
+    // Both entities see the other's count as of step start, regardless of update order
+    for id in ["a", "b"] {
+        let state = world.get_entity_state(id).unwrap();
+        assert_eq!(state.get("seen").unwrap(), 1);
+        assert_eq!(state.get("wolves").unwrap(), 1);
+    }
+
+    world_cfg.message_only = true;
+    let mut world = World::new(&world_cfg).unwrap();
+    assert!(world.update(1).is_err());
+}
+
+#[test]
+fn test_director() {
+    let mut world_cfg = WorldCfg::new("director_world".to_string());
+    world_cfg.add_script(
+        "sheep".to_string(),
+        r#"
+        local shocks = 0
+        function update(current_time, msgs)
+            for _, msg in ipairs(msgs) do
+                if msg.kind == "shock" then
+                    shocks = shocks + 1
+                end
+            end
+        end
+        function get_state() return {shocks = shocks} end
+        function set_state(state) shocks = state.shocks or 0 end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "sheep_1".to_string(),
+        script_id: "sheep".to_string(),
+        tags: vec!["flock".to_string()],
+        ..Default::default()
+    });
+    world_cfg.director = Some(DirectorCfg {
+        script: r#"
+        local spawned = 0
+        function on_step_begin(current_time)
+            if current_time == 1 then
+                director.spawn_entity("sheep_2", "sheep")
+            elseif current_time == 2 then
+                director.send_to_tag("flock", "shock", {})
+                director.send_msg("sheep_2", "shock", {})
+            end
+        end
+        function on_step_end(current_time)
+            if current_time == 3 then
+                director.stop("end of scenario")
+            end
+        end
+        function on_entity_spawned(id, script_id)
+            spawned = spawned + 1
+        end
+        function get_state() return {spawned = spawned} end
+        function set_state(state) spawned = state.spawned end
+        "#
+        .to_string(),
+        initial_state: Some(serde_json::json!({"spawned": 0}).as_object().unwrap().clone()),
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    for _ in 0..3 {
+        world.update(1).unwrap();
+    }
+
+    // Messages injected at step begin are delivered in the same step
+    for id in ["sheep_1", "sheep_2"] {
+        assert_eq!(world.get_entity_state(id).unwrap().get("shocks").unwrap(), 1);
+    }
+
+    assert_eq!(world.get_stop_reason().unwrap(), "end of scenario");
+    assert!(world.update(1).is_err());
+
+    // Restored worlds stay stopped
+    let snapshot = world.create_snapshot().unwrap();
+    let mut restored = World::new_from_snapshot(snapshot.clone()).unwrap();
+    assert_eq!(restored.get_stop_reason().unwrap(), "end of scenario");
+    assert!(restored.update(1).is_err());
+
+    let director = snapshot.configuration.director.unwrap();
+    assert_eq!(director.initial_state.unwrap().get("spawned").unwrap(), 1);
+
+    // Failing set_state is reported once as a state error
+    world_cfg.director.as_mut().unwrap().script.push_str("\nfunction set_state(state) error('broken') end");
+    let error = World::new(&world_cfg).err().unwrap().to_string();
+    assert!(error.starts_with("Script state error") && !error.contains("Script execution error"), "{}", error);
+
+    // Entities cannot take the sender IDs of the director and the timeline
+    assert!(world.spawn_entity(EntityCfg { id: "director".to_string(), script_id: "sheep".to_string(), ..Default::default() }).is_err());
+    world_cfg.entities[0].id = "timeline".to_string();
+    assert!(World::new(&world_cfg).is_err());
+
+    // A failing hook does not undo the spawn, commands of hooks called outside of steps are applied on request
+    world_cfg.entities[0].id = "sheep_1".to_string();
+    world_cfg.director = Some(DirectorCfg {
+        script: r#"
+        function on_entity_spawned(id, script_id)
+            if id == "lamb" then error("no lambs") end
+        end
+        function on_entity_removed(id)
+            director.spawn_entity(id .. "_clone", "sheep")
+        end
+        "#
+        .to_string(),
+        initial_state: None,
+    });
+    let mut world = World::new(&world_cfg).unwrap();
+    world.spawn_entity(EntityCfg { id: "lamb".to_string(), script_id: "sheep".to_string(), ..Default::default() }).unwrap();
+    let errors = world.apply_director_commands();
+    assert!(errors.len() == 1 && errors[0].contains("no lambs"), "{:?}", errors);
+    assert!(world.get_entity_state("lamb").is_ok());
+
+    world.remove_entity("sheep_1").unwrap();
+    assert!(world.apply_director_commands().is_empty());
+    assert!(world.get_entity_state("sheep_1_clone").is_ok());
+}
+
+#[test]
+fn test_globals() {
+    let mut world_cfg = WorldCfg::new("globals_world".to_string());
+    world_cfg.add_script(
+        "trader".to_string(),
+        r#"
+        local seen = 0
+        function update(current_time, msgs)
+            seen = world.get_global("price")
+            world.set_global("price", seen + 1)
+        end
+        function get_state() return {seen = seen} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "trader".to_string(),
+        script_id: "trader".to_string(),
+        ..Default::default()
+    });
+    world_cfg.globals = serde_json::json!({"price": 10, "weather": "sunny"}).as_object().unwrap().clone();
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    world.update(1).unwrap();
+
+    // Changes are applied at the end of the step
+    assert_eq!(world.get_entity_state("trader").unwrap().get("seen").unwrap(), 11);
+    assert_eq!(world.get_state_ref().get_globals().get("price").unwrap(), 12);
+
+    world.set_globals(serde_json::json!({"weather": null}).as_object().unwrap().clone());
+    let snapshot = world.create_snapshot().unwrap();
+    assert_eq!(snapshot.configuration.globals, serde_json::json!({"price": 12}).as_object().unwrap().clone());
+}
+
+#[test]
+fn test_send_message() {
+    let mut world_cfg = WorldCfg::new("external_world".to_string());
+    world_cfg.add_script(
+        "listener".to_string(),
+        r#"
+        local heard = {}
+        function update(current_time, msgs)
+            for _, msg in ipairs(msgs) do
+                table.insert(heard, msg.sender .. ":" .. msg.kind .. "@" .. current_time)
+            end
+        end
+        function get_state() return {heard = heard} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "listener".to_string(),
+        script_id: "listener".to_string(),
+        tags: vec!["crowd".to_string()],
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world
+        .send_message("analyst", MessageReceiver::Entity { id: "listener".to_string() }, "hint".to_string(), JSONObject::new(), 0)
+        .unwrap();
+    let receive_step = world
+        .send_message("analyst", MessageReceiver::Tag { tag: "crowd".to_string() }, "news".to_string(), JSONObject::new(), 2)
+        .unwrap();
+    assert_eq!(receive_step, 2);
+    world.update(1).unwrap();
+    assert!(matches!(
+        world.send_message("analyst", MessageReceiver::Tag { tag: "crowd".to_string() }, "late".to_string(), JSONObject::new(), u64::MAX),
+        Err(CoreError::TimeOverflow { .. })
+    ));
+    assert!(matches!(
+        world.send_message("analyst", MessageReceiver::Entity { id: "nobody".to_string() }, "hint".to_string(), JSONObject::new(), 0),
+        Err(CoreError::EntityNotFound { .. })
+    ));
+
+    for _ in 0..2 {
+        world.update(1).unwrap();
+    }
+
+    assert_eq!(
+        world.get_entity_state("listener").unwrap().get("heard").unwrap(),
+        &serde_json::json!(["analyst:hint@1", "analyst:news@2"])
+    );
+}
+
+#[test]
+fn test_message_ids() {
+    let mut world_cfg = WorldCfg::new("ids_world".to_string());
+    world_cfg.add_script(
+        "silent".to_string(),
+        "function update() end function get_state() return {} end function set_state(s) end".to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "a".to_string(),
+        script_id: "silent".to_string(),
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    let receiver = MessageReceiver::Entity { id: "a".to_string() };
+    world.send_message("external", receiver.clone(), "first".to_string(), JSONObject::new(), 1).unwrap();
+    world.update(1).unwrap();
+    world.send_message("external", receiver.clone(), "second".to_string(), JSONObject::new(), 5).unwrap();
+
+    // IDs stay unique after restoring a snapshot
+    let mut world = World::new_from_snapshot(world.create_snapshot().unwrap()).unwrap();
+    world.send_message("external", receiver, "third".to_string(), JSONObject::new(), 0).unwrap();
+
+    let mut pending: Vec<(u64, String, u64)> = world
+        .create_snapshot()
+        .unwrap()
+        .pending_messages
+        .into_iter()
+        .map(|m| (m.id, m.kind, m.sent_step))
+        .collect();
+    pending.sort();
+    assert_eq!(pending, vec![(2, "second".to_string(), 1), (3, "third".to_string(), 1)]);
+}
+
+#[test]
+fn test_spawn_and_remove_entities() {
+    let mut world_cfg = WorldCfg::new("spawn_world".to_string());
+    world_cfg.add_script(
+        "counter".to_string(),
+        r#"
+        local count = 0
+        function update(current_time, msgs) count = count + #msgs end
+        function get_state() return {count = count} end
+        function set_state(state) count = (state.count or 0) + 0 end
+        "#
+        .to_string(),
+    );
+    world_cfg.space = Some(SpaceCfg {
+        width: 10.0,
+        height: 10.0,
+        boundary: Boundary::Clamp,
+        cell_size: None,
+    });
+    world_cfg.director = Some(DirectorCfg {
+        script: r#"
+        local spawned, removed = 0, 0
+        function on_entity_spawned(id, script_id) spawned = spawned + 1 end
+        function on_entity_removed(id) removed = removed + 1 end
+        function get_state() return {spawned = spawned, removed = removed} end
+        function set_state(state) spawned, removed = state.spawned, state.removed end
+        "#
+        .to_string(),
+        initial_state: Some(serde_json::json!({"spawned": 0, "removed": 0}).as_object().unwrap().clone()),
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    let entity = |id: &str, script_id: &str| EntityCfg {
+        id: id.to_string(),
+        script_id: script_id.to_string(),
+        subscriptions: vec!["news".to_string()],
+        position: Some((1.0, 1.0)),
+        ..Default::default()
+    };
+
+    world.spawn_entity(entity("a", "counter")).unwrap();
+    assert!(matches!(world.spawn_entity(entity("a", "counter")), Err(CoreError::EntityCreation { .. })));
+    assert!(world.spawn_entity(entity("b", "unknown")).is_err());
+
+    // Placement is checked as in configurations
+    let mut misplaced = entity("b", "counter");
+    misplaced.cell = Some((0, 0));
+    assert!(world.spawn_entity(misplaced).is_err());
+    let mut misplaced = entity("b", "counter");
+    misplaced.position = None;
+    misplaced.velocity = Some((1.0, 0.0));
+    assert!(world.spawn_entity(misplaced.clone()).is_err());
+
+    // Failed bulk spawn leaves no entities behind and the director sees no changes
+    let mut broken = entity("d", "counter");
+    broken.initial_state = Some(serde_json::json!({"count": "many"}).as_object().unwrap().clone());
+    assert!(world.spawn_entities(vec![entity("b", "counter"), entity("c", "unknown")]).is_err());
+    assert!(world.spawn_entities(vec![entity("b", "counter"), entity("b", "counter")]).is_err());
+    assert!(world.spawn_entities(vec![entity("b", "counter"), misplaced]).is_err());
+    assert!(world.spawn_entities(vec![entity("b", "counter"), entity("c", "counter"), broken]).is_err());
+    assert_eq!(world.get_entities_count(), 1);
+    let director_state = world.create_snapshot().unwrap().configuration.director.unwrap().initial_state;
+    assert_eq!(serde_json::Value::Object(director_state.unwrap()), serde_json::json!({"spawned": 1, "removed": 0}));
+
+    let ids = world.spawn_entities(vec![entity("b", "counter"), entity("c", "counter")]).unwrap();
+    assert_eq!(ids, vec!["b", "c"]);
+    assert_eq!(world.get_state_ref().get_space().get_body("c").unwrap().x, 1.0);
+
+    world.remove_entity("b").unwrap();
+    world.send_message("external", MessageReceiver::Topic { topic: "news".to_string() }, "n".to_string(), JSONObject::new(), 0).unwrap();
+    world.update(1).unwrap();
+
+    assert_eq!(world.get_entities_count(), 2);
+    assert_eq!(world.get_entity_state("c").unwrap().get("count").unwrap(), 1);
+}
+
+#[test]
+fn test_script_management() {
+    let counter_script = |increment: u32, max_count: u32| {
+        format!(
+            r#"
+            local count = 0
+            function update(current_time, msgs) count = count + {} end
+            function get_state() return {{count = count}} end
+            function set_state(state)
+                if state.count > {} then error("count too large") end
+                count = state.count
+            end
+            "#,
+            increment, max_count
+        )
+    };
+    let script_cfg = |id: &str, script: String| ScriptCfg {
+        id: id.to_string(),
+        kind: "lua".to_string(),
+        script,
+        parameters: Default::default(),
+    };
+
+    let mut world_cfg = WorldCfg::new("scripts_world".to_string());
+    world_cfg.add_script("counter".to_string(), counter_script(1, 1000));
+    for id in ["a", "b"] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "counter".to_string(),
+            ..Default::default()
+        });
+    }
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    world.set_entity_state("b", serde_json::json!({"count": 50}).as_object().unwrap().clone()).unwrap();
+
+    // New scripts are validated
+    assert!(world.add_script(script_cfg("broken", "function update(".to_string())).is_err());
+    assert!(world.add_script(script_cfg("no_state", "function update() end".to_string())).is_err());
+    assert!(matches!(world.add_script(script_cfg("counter", counter_script(1, 1000))), Err(CoreError::ScriptAlreadyExists { .. })));
+    world.add_script(script_cfg("other", counter_script(1, 1000))).unwrap();
+
+    // Failure of one entity rolls back the whole update
+    match world.update_script(script_cfg("counter", counter_script(10, 10))) {
+        Err(CoreError::ScriptMigration { failures, .. }) => {
+            assert_eq!(failures.keys().collect::<Vec<_>>(), vec!["b"]);
+        }
+        _ => panic!("Expected migration failure"),
+    }
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("a").unwrap().get("count").unwrap(), 2);
+
+    let migrated = world.update_script(script_cfg("counter", counter_script(10, 1000))).unwrap();
+    assert_eq!(migrated, vec!["a", "b"]);
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("a").unwrap().get("count").unwrap(), 12);
+    assert_eq!(world.get_entity_state("b").unwrap().get("count").unwrap(), 61);
+
+    assert!(matches!(world.remove_script("counter"), Err(CoreError::ScriptInUse { .. })));
+    world.remove_script("other").unwrap();
+    assert!(world.get_script("other").is_none());
+
+    // Unknown scripts are reported as such
+    assert!(matches!(world.remove_script("other"), Err(CoreError::ScriptNotFound { .. })));
+    assert!(matches!(world.update_script(script_cfg("other", counter_script(1, 1))), Err(CoreError::ScriptNotFound { .. })));
+    assert!(matches!(world.change_entity_script("a", "other", None), Err(CoreError::ScriptNotFound { .. })));
+    let entity = EntityCfg { id: "c".to_string(), script_id: "other".to_string(), ..Default::default() };
+    assert!(matches!(world.spawn_entity(entity), Err(CoreError::ScriptNotFound { .. })));
+}
+
+#[test]
+fn test_script_validation() {
+    let mut world_cfg = WorldCfg::new("invalid_world".to_string());
+    world_cfg.add_script(
+        "broken".to_string(),
+        "function update(current_time, msgs)\n  count = count +\nend\nfunction get_state() return {} end".to_string(),
+    );
+
+    match World::new(&world_cfg) {
+        Err(CoreError::ScriptValidation { script_id, diagnostics }) => {
+            assert_eq!(script_id, "broken");
+            assert_eq!(diagnostics[0].check, "syntax");
+            assert_eq!(diagnostics[0].line, Some(3));
+        }
+        _ => panic!("Expected validation error"),
+    }
+
+    // Warnings do not prevent creating the world
+    world_cfg.add_script(
+        "broken".to_string(),
+        "function update() local other = world.get_entity_state('other') print(other.count) end function get_state() return {} end function set_state(s) end".to_string(),
+    );
+    assert!(World::new(&world_cfg).is_ok());
+}
+
+#[test]
+fn test_sandboxed_scripts() {
+    // Validation only defines the functions, the libraries are missing when they are called
+    let mut world_cfg = WorldCfg::new("sandbox_world".to_string());
+    world_cfg.add_script(
+        "shell".to_string(),
+        r#"
+        function update(current_time, msgs) os.execute("true") end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "shell".to_string(),
+        script_id: "shell".to_string(),
+        ..Default::default()
+    });
+    let mut world = World::new(&world_cfg).unwrap();
+    assert!(world.update(1).is_err());
+
+    let mut world_cfg = WorldCfg::new("sandbox_world".to_string());
+    world_cfg.director = Some(DirectorCfg {
+        script: r#"function on_step_begin(current_time) io.open("/etc/passwd") end"#.to_string(),
+        initial_state: None,
+    });
+    let mut world = World::new(&world_cfg).unwrap();
+    assert!(world.update(1).is_err());
+
+    let predicate = world.create_predicate("os == nil and io == nil and package == nil and loadfile == nil").unwrap();
+    assert!(world.evaluate_predicate(&predicate).unwrap());
+
+    // Endless loops of entities and the director are stopped, every call gets the full instruction budget
+    let mut world_cfg = WorldCfg::new("sandbox_world".to_string());
+    world_cfg.add_script(
+        "counter".to_string(),
+        r#"
+        function update(current_time, msgs)
+            local i = 0
+            while i < 1000000 do i = i + 1 end
+            if current_time == 4 then while true do end end
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "counter".to_string(),
+        script_id: "counter".to_string(),
+        ..Default::default()
+    });
+    world_cfg.message_only = true; // No entity views, only update runs in every step
+    let mut world = World::new(&world_cfg).unwrap();
+    for _ in 0..3 {
+        world.update(1).unwrap();
+    }
+    assert!(world.update(1).err().unwrap().to_string().contains("instruction limit"));
+
+    world_cfg.entities.clear();
+    world_cfg.director = Some(DirectorCfg {
+        script: "function on_step_end(current_time) while true do end end".to_string(),
+        initial_state: None,
+    });
+    let mut world = World::new(&world_cfg).unwrap();
+    assert!(world.update(1).err().unwrap().to_string().contains("instruction limit"));
+}
+
+#[test]
+fn test_change_entity_script() {
+    let mut world_cfg: WorldCfg = serde_yaml::from_str(
+        r#"
+        name: life_stages
+        script_library:
+          larva:
+            id: larva
+            kind: lua
+            parameters:
+              growth: {type: number, default: 1}
+            script: |
+              local size = 0
+              function update(current_time, msgs)
+                size = size + self.params.growth
+                if size >= 2 then self.become("adult", {size = size}) end
+              end
+              function get_state() return {size = size} end
+              function set_state(state) size = state.size end
+          adult:
+            id: adult
+            kind: lua
+            script: |
+              local size = 0
+              local heard = 0
+              function update(current_time, msgs) heard = heard + #msgs end
+              function get_state() return {size = size, heard = heard, stage = "adult"} end
+              function set_state(state) size = state.size end
+        entities:
+          - id: bug
+            script_id: larva
+            tags: [insects]
+            parameters: {growth: 2}
+        "#,
+    )
+    .unwrap();
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.send_message("external", MessageReceiver::Tag { tag: "insects".to_string() }, "food".to_string(), JSONObject::new(), 2).unwrap();
+    world.update(1).unwrap();
+
+    // Switched at the end of the first step, message of the second step reaches the adult
+    world.update(1).unwrap();
+    let state = world.get_entity_state("bug").unwrap();
+    assert_eq!(state.get("stage").unwrap(), "adult");
+    assert_eq!(state.get("size").unwrap(), 2);
+    assert_eq!(state.get("heard").unwrap(), 1);
+
+    let snapshot = world.create_snapshot().unwrap();
+    let entity_cfg = &snapshot.configuration.entities[0];
+    assert_eq!(entity_cfg.script_id, "adult");
+    assert_eq!(entity_cfg.tags, vec!["insects"]);
+    assert!(entity_cfg.parameters.is_empty());
+
+    world.change_entity_script("bug", "larva", None).unwrap();
+    assert_eq!(world.get_entity_state("bug").unwrap().get("size").unwrap(), 0);
+    assert!(world.change_entity_script("bug", "pupa", None).is_err());
+    assert!(matches!(world.change_entity_script("ant", "larva", None), Err(CoreError::EntityNotFound { .. })));
+
+    // Changing to an unknown script is reported, the step and the entity go on
+    let larva = world_cfg.script_library.get_mut("larva").unwrap();
+    larva.script = larva.script.replace("\"adult\"", "\"adlut\"");
+    let mut world = World::new(&world_cfg).unwrap();
+    let result = world.update(1).unwrap();
+    assert_eq!(result.command_errors.len(), 1);
+    assert!(result.command_errors[0].contains("adlut"));
+    assert_eq!(world.get_entity_state("bug").unwrap().get("size").unwrap(), 2);
+    assert_eq!(world.update(1).unwrap().command_errors.len(), 1);
+}
+
+#[test]
+fn test_event_timeline() {
+    let world_cfg: WorldCfg = serde_yaml::from_str(
+        r#"
+        name: timeline_world
+        script_library:
+          sheep:
+            id: sheep
+            kind: lua
+            script: |
+              local alarms = 0
+              function update(current_time, msgs)
+                for _, msg in ipairs(msgs) do
+                  if msg.kind == "alarm" then alarms = alarms + 1 end
+                end
+              end
+              function get_state() return {alarms = alarms} end
+              function set_state(state) alarms = state.alarms or 0 end
+        entities:
+          - id: sheep_0
+            script_id: sheep
+            tags: [flock]
+        events:
+          - step: 3
+            action: remove_entity
+            entity_id: sheep_0
+          - step: 1
+            action: spawn
+            script_id: sheep
+            count: 2
+            id_pattern: "sheep_{i}"
+          - step: 2
+            action: send_message
+            tag: flock
+            kind: alarm
+          - step: 2
+            action: set_global
+            key: weather
+            value: storm
+        "#,
+    )
+    .unwrap();
+    world_cfg.validate().unwrap();
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    assert_eq!(world.get_entities_count(), 3);
+
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("sheep_0").unwrap().get("alarms").unwrap(), 1);
+    assert_eq!(world.get_state_ref().get_globals().get("weather").unwrap(), "storm");
+
+    // Only events not applied yet are preserved in snapshots
+    let snapshot = world.create_snapshot().unwrap();
+    assert_eq!(snapshot.configuration.events.len(), 1);
+
+    world.update(1).unwrap();
+    assert!(world.get_entity_state("sheep_0").is_err());
+    assert!(world.get_entity_state("sheep_2").is_ok());
+
+    // A spawn over the entity quota creates none of the entities and is reported without stopping the step
+    let mut world_cfg = world_cfg;
+    world_cfg.limits.max_entities = 2;
+    let mut world = World::new(&world_cfg).unwrap();
+    let result = world.update(1).unwrap();
+    assert_eq!(world.get_entities_count(), 1);
+    assert_eq!(world.get_simulation_time(), 1);
+    assert_eq!(result.command_errors.len(), 1);
+    assert!(result.command_errors[0].contains("max_entities"));
+
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("sheep_0").unwrap().get("alarms").unwrap(), 1);
+}
+
+#[test]
+fn test_populations() {
+    let mut world_cfg: WorldCfg = serde_yaml::from_str(
+        r#"
+        name: population_world
+        script_library:
+          sheep:
+            id: sheep
+            kind: lua
+            script: |
+              local energy = 0
+              function update(current_time, msgs)
+                if self.id == "sheep_0" and current_time == 1 then
+                  world.spawn_population({script_id = "sheep", count = 2, id_pattern = "sheep_{i}", initial_state = {energy = {kind = "constant", value = 7}}})
+                end
+              end
+              function get_state() return {energy = energy} end
+              function set_state(state) energy = state.energy or 0 end
+        entities: []
+        populations:
+          - script_id: sheep
+            count: 3
+            id_pattern: "sheep_{i}"
+            tags: [flock]
+            seed: 42
+            initial_state:
+              energy: {kind: uniform, min: 5, max: 10, integer: true}
+        "#,
+    )
+    .unwrap();
+
+    let world = World::new(&world_cfg).unwrap();
+    assert_eq!(world.get_entities_count(), 3);
+    let energy = world.get_entity_state("sheep_2").unwrap().get("energy").unwrap().as_i64().unwrap();
+    assert!((5..=10).contains(&energy));
+
+    // Runtime spawning continues with unused indices
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("sheep_4").unwrap().get("energy").unwrap(), 7);
+
+    // Populations beyond the entity limit fail before any entity is spawned
+    let mut large = world_cfg.populations[0].clone();
+    large.count = usize::MAX;
+    assert!(matches!(world.spawn_population(&large), Err(CoreError::QuotaExceeded { .. })));
+    assert_eq!(world.get_entities_count(), 5);
+
+    // Existing ID without '{i}' is an error
+    large.count = 1;
+    large.id_pattern = "sheep_0".to_string();
+    assert!(matches!(world.spawn_population(&large), Err(CoreError::EntityCreation { .. })));
+
+    world_cfg.populations[0].count = usize::MAX;
+    assert!(matches!(World::new(&world_cfg), Err(CoreError::QuotaExceeded { .. })));
+
+    world_cfg.populations[0].count = 3;
+    world_cfg.populations[0].id_pattern = "sheep".to_string();
+    assert!(World::new(&world_cfg).is_err());
+}
+
+#[test]
+fn test_script_parameters() {
+    let mut world_cfg: WorldCfg = serde_yaml::from_str(
+        r#"
+        name: parameters_world
+        script_library:
+          walker:
+            id: walker
+            kind: lua
+            parameters:
+              speed: {type: number, default: 1.0, minimum: 0}
+              mode: {type: string, default: walk, enum: [walk, run]}
+              bounds: {type: object, default: {min: 0, max: 10}}
+            script: |
+              local distance = 0
+              local protected = false
+              function update(current_time, msgs)
+                local bounds = 0
+                for _, bound in pairs(self.params.bounds) do bounds = bounds + bound end
+                protected = bounds == 10 and rawset == nil
+                  and not pcall(function() self.params.bounds.max = 5 end)
+                  and self.params.bounds.max == 10
+                distance = distance + self.params.speed
+                if current_time == 2 then
+                  self.params.speed = 100
+                end
+              end
+              function get_state() return {distance = distance, mode = self.params.mode, protected = protected} end
+              function set_state(state) end
+        entities:
+          - id: slow
+            script_id: walker
+          - id: fast
+            script_id: walker
+            parameters: {speed: 3, mode: run}
+        "#,
+    )
+    .unwrap();
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    assert_eq!(world.get_entity_state("slow").unwrap().get("distance").unwrap(), 1.0);
+    assert_eq!(world.get_entity_state("fast").unwrap().get("distance").unwrap(), 3);
+    assert_eq!(world.get_entity_state("fast").unwrap().get("mode").unwrap(), "run");
+    assert_eq!(world.get_entity_state("fast").unwrap().get("protected").unwrap(), true);
+
+    // Overrides are preserved in snapshots
+    let snapshot = world.create_snapshot().unwrap();
+    let fast = snapshot.configuration.entities.iter().find(|e| e.id == "fast").unwrap();
+    assert_eq!(fast.parameters.get("speed").unwrap(), 3);
+    assert_eq!(snapshot.configuration.script_library["walker"].parameters.len(), 3);
+
+    // Parameters are read-only, including nested tables
+    assert!(world.update(1).is_err());
+
+    world_cfg.entities[1].parameters.insert("speed".to_string(), serde_json::json!(-1));
+    assert!(World::new(&world_cfg).is_err());
+    world_cfg.entities[1].parameters = serde_json::json!({"unknown": 1}).as_object().unwrap().clone();
+    assert!(World::new(&world_cfg).is_err());
+}
+
+#[test]
+fn test_quotas() {
+    let mut world_cfg = WorldCfg::new("quota_world".to_string());
+    world_cfg.add_script(
+        "spammer".to_string(),
+        r#"
+        function update(current_time, msgs)
+            for i = 1, 3 do
+                self.send_msg(self.id, "spam", {}, 10)
+            end
+            world.record_metric("spam_" .. current_time, 1)
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "spammer".to_string(),
+        script_id: "spammer".to_string(),
+        ..Default::default()
+    });
+
+    // Messages over quotas are dropped and reported, steps go on
+    let rejected_messages = |world_cfg: &WorldCfg| -> (Vec<usize>, usize) {
+        let mut world = World::new(world_cfg).unwrap();
+        let rejected = (0..5).map(|_| world.update(1).unwrap().rejected_messages).collect();
+        (rejected, world.get_pending_messages_count())
+    };
+
+    world_cfg.limits.max_messages_per_entity_per_step = Some(2);
+    assert_eq!(rejected_messages(&world_cfg), (vec![1, 1, 1, 1, 1], 10));
+
+    world_cfg.limits.max_messages_per_entity_per_step = None;
+    world_cfg.limits.max_pending_messages = Some(10);
+    assert_eq!(rejected_messages(&world_cfg), (vec![0, 0, 0, 2, 3], 10));
+
+    world_cfg.limits.max_pending_messages = None;
+    world_cfg.limits.max_metric_points = Some(3);
+    let mut world = World::new(&world_cfg).unwrap();
+    let rejected: Vec<usize> = (0..5).map(|_| world.update(1).unwrap().rejected_metric_points).collect();
+    assert_eq!(rejected, vec![0, 0, 0, 1, 1]);
+
+    world_cfg.limits.max_metric_points = None;
+    world_cfg.limits.max_entities = 1;
+    let mut world = World::new(&world_cfg).unwrap();
+    let spammer = EntityCfg { id: "spammer_2".to_string(), script_id: "spammer".to_string(), ..Default::default() };
+    assert!(matches!(world.spawn_entity(spammer), Err(CoreError::QuotaExceeded { limit: 1, .. })));
+
+    // Spawns by scripts over the quota are reported, the step goes on
+    world_cfg.add_script(
+        "spawner".to_string(),
+        r#"
+        function update(current_time, msgs)
+            self.spawn_entity("spawned_" .. current_time, "spawner")
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities[0].script_id = "spawner".to_string();
+    let mut world = World::new(&world_cfg).unwrap();
+    let result = world.update(1).unwrap();
+    assert_eq!(world.get_entities_count(), 1);
+    assert_eq!(result.command_errors.len(), 1);
+    assert!(result.command_errors[0].contains("max_entities"));
+
+    world_cfg.limits.max_entities = 0;
+    assert!(World::new(&world_cfg).is_err());
+}
+
+#[test]
+fn test_stop_conditions() {
+    let mut world_cfg = WorldCfg::new("stop_world".to_string());
+    world_cfg.add_script(
+        "counter".to_string(),
+        r#"
+        local count = 0
+        function update(current_time, msgs)
+            count = count + 1
+            world.record_metric("count", count)
+        end
+        function get_state() return {count = count, nested = {values = {count * 10}}} end
+        function set_state(state) count = state.count end
+        "#
+        .to_string(),
+    );
+    world_cfg.entities.push(EntityCfg {
+        id: "counter".to_string(),
+        script_id: "counter".to_string(),
+        tags: vec!["clock".to_string()],
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    let parse = |json: serde_json::Value| -> Vec<StopConditionCfg> { serde_json::from_value(json).unwrap() };
+
+    let conditions = StopConditions::new(
+        &world,
+        parse(serde_json::json!([
+            {"kind": "metric", "name": "count", "op": "ge", "value": 3},
+            {"kind": "entity_state", "entity_id": "counter", "path": "$.nested.values[0]", "op": "eq", "value": 20},
+            {"kind": "lua", "predicate": "world.get_entity_state('counter').count == 1"},
+        ])),
+    )
+    .unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), None);
+
+    world.update(1).unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), Some(2));
+    world.update(1).unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), Some(1));
+    world.update(1).unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), Some(0));
+
+    let conditions = StopConditions::new(
+        &world,
+        parse(serde_json::json!([
+            {"kind": "entity_count", "tag": "clock", "op": "eq", "value": 0},
+            {"kind": "no_pending_messages"},
+        ])),
+    )
+    .unwrap();
+    world
+        .send_message("external", MessageReceiver::Entity { id: "counter".to_string() }, "tick".to_string(), JSONObject::new(), 1)
+        .unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), None);
+    world.update(1).unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), Some(1));
+    world.remove_entity("counter").unwrap();
+    assert_eq!(conditions.check(&world).unwrap(), Some(0));
+
+    assert!(StopConditions::new(&world, parse(serde_json::json!([{"kind": "lua", "predicate": "return +"}]))).is_err());
+
+    // Endless loops in predicates are stopped, the next evaluation gets a new instruction budget
+    let endless = world.create_predicate("(function() while true do end end)()").unwrap();
+    assert!(world.evaluate_predicate(&endless).is_err());
+    assert!(world.evaluate_predicate(&endless).is_err());
+    let counting = world.create_predicate("(function() local i = 0 while i < 100000 do i = i + 1 end return true end)()").unwrap();
+    assert!(world.evaluate_predicate(&counting).unwrap());
+    assert!(world.evaluate_predicate(&counting).unwrap());
+    assert!(
+        StopConditions::new(
+            &world,
+            parse(serde_json::json!([{"kind": "entity_state", "entity_id": "a", "path": "count", "op": "eq", "value": 1}]))
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_update_order() {
+    let mut world_cfg = WorldCfg::new("order_world".to_string());
+    world_cfg.add_script(
+        "sender".to_string(),
+        r#"
+        function update(current_time, msgs)
+            if current_time == 1 then self.send_msg("log", "hello", {}) end
+        end
+        function get_state() return {} end
+        function set_state(state) end
+        "#
+        .to_string(),
+    );
+    world_cfg.add_script(
+        "log".to_string(),
+        r#"
+        local senders = {}
+        function update(current_time, msgs)
+            for _, msg in ipairs(msgs) do table.insert(senders, msg.sender) end
+        end
+        function get_state() return {senders = senders} end
+        function set_state(state) senders = state.senders end
+        "#
+        .to_string(),
+    );
+    // Message IDs follow the update order, which must not depend on hashing
+    for id in ["e7", "e3", "e9", "e1", "e5"] {
+        world_cfg.entities.push(EntityCfg {
+            id: id.to_string(),
+            script_id: "sender".to_string(),
+            ..Default::default()
+        });
+    }
+    world_cfg.entities.push(EntityCfg {
+        id: "log".to_string(),
+        script_id: "log".to_string(),
+        ..Default::default()
+    });
+
+    let mut world = World::new(&world_cfg).unwrap();
+    world.update(1).unwrap();
+    world.update(1).unwrap();
+    let state = world.get_entity_state("log").unwrap();
+    assert_eq!(state["senders"], serde_json::json!(["e1", "e3", "e5", "e7", "e9"]));
+}
+
+#[test]
+fn test_seeded_worlds() {
+    let create_world = |seed: u64| {
+        let mut world_cfg = WorldCfg::new("seeded_world".to_string());
+        world_cfg.seed = Some(seed);
+        world_cfg.add_script(
+            "dice".to_string(),
+            r#"
+            local rolls = {math.random(1000000)}
+            function update(current_time, msgs)
+                table.insert(rolls, math.random(1000000))
+                world.record_metric("roll", rolls[#rolls])
+            end
+            function get_state() return {rolls = rolls} end
+            function set_state(state) end
+            "#
+            .to_string(),
+        );
+        for id in ["a", "b"] {
+            world_cfg.entities.push(EntityCfg {
+                id: id.to_string(),
+                script_id: "dice".to_string(),
+                ..Default::default()
+            });
+        }
+
+        let mut world = World::new(&world_cfg).unwrap();
+        world.update(1).unwrap();
+        world
+    };
+
+    let (first, second, other) = (create_world(7), create_world(7), create_world(8));
+    assert_eq!(first.get_entity_state("a").unwrap(), second.get_entity_state("a").unwrap());
+    assert_ne!(first.get_entity_state("a").unwrap(), first.get_entity_state("b").unwrap());
+    assert_ne!(first.get_entity_state("a").unwrap(), other.get_entity_state("a").unwrap());
+
+    let csv = first.get_metrics_ref().to_csv();
+    assert_eq!(csv.lines().next(), Some("metric,time,value"));
+    assert_eq!(csv.lines().count(), 2);
+}
@@ -1,15 +1,20 @@
 use crate::core::Entity;
+use crate::core::entity::EntityView;
 use crate::core::errors::CoreError;
-use crate::core::messaging::{JSONObject, Message, MessageBus};
+use crate::core::messaging::{JSONObject, Message, MessageBus, MessageReceiver, TIMELINE_ID};
+use crate::core::grid::Grid;
 use crate::core::metrics::Metrics;
-use crate::core::world_config::WorldCfg;
+use crate::core::network::Network;
+use crate::core::scripting::lua::{LuaDirectorController, LuaPredicate, LuaScriptController};
+use crate::core::space::Space;
+use crate::core::population;
+use crate::core::world_config::{DirectorCfg, EntityCfg, EventActionCfg, EventCfg, PopulationCfg, ScriptCfg, WorldCfg, check_entity_id};
 use crate::core::messaging::Command;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::rc::Rc;
 
 use std::{cell::RefCell, collections::HashMap};
 
-const MAX_ENTITIES_PER_WORLD: usize = 10000;
-
 // Represents a simulation world containing entities, message bus, and metrics.
 pub struct World {
     cfg: WorldCfg,
@@ -17,30 +22,90 @@ pub struct World {
     state: Rc<RefCell<WorldState>>,
     metrics: Metrics,
     simulation_time: u64, //TODO: Replace with some shared clock
+    director: Option<LuaDirectorController>,
+    stop_reason: Option<String>, // Set when the run was ended, no further steps are simulated
+    pending_events: VecDeque<EventCfg>, // Events of the timeline not applied yet, ordered by step
+    rejected_messages: usize, // Messages dropped by quotas in the current step
+    rejected_metric_points: usize, // Metric points dropped by max_metric_points in the current step
+    command_errors: Vec<String>, // Failures of commands in the current step that did not stop it
 }
 
 pub struct WorldState {
-    entities: HashMap<String, RefCell<Entity>>,
+    entities: BTreeMap<String, RefCell<Entity>>, // Ordered by ID, entities update in the same order in every run
+    tags: HashMap<String, BTreeSet<String>>, // Tag to IDs of entities with the tag
+    network: Network,
+    grid: Option<Grid>,
+    space: Space,
+    entity_views: Option<HashMap<String, EntityView>>, // Views of entities as of step start, None in message-only worlds
+    globals: JSONObject,
+    max_entities: usize,
+    random_seed: Option<u64>, // Seed of the world, math.random of scripts is seeded from it
 }
 
 pub struct WorldUpdateResult {
     pub delivered_messages: Vec<Message>,
+    pub rejected_messages: usize, // Messages sent during the step but dropped by max_messages_per_entity_per_step or max_pending_messages
+    pub rejected_metric_points: usize, // Metric points recorded during the step but dropped by max_metric_points
+    pub command_errors: Vec<String>, // Commands that failed without stopping the step, e.g. script changes
 }
 
 impl World {
     pub fn new(cfg: &WorldCfg) -> Result<Self, CoreError> {
         cfg.validate()?;
+        Self::build(cfg)
+    }
+
+    // Create a world whose scripts were already checked, e.g. with validate_code, so that they do not run again.
+    // Other settings are validated.
+    pub fn new_with_checked_scripts(cfg: &WorldCfg) -> Result<Self, CoreError> {
+        cfg.validate_settings()?;
+        Self::build(cfg)
+    }
+
+    fn build(cfg: &WorldCfg) -> Result<Self, CoreError> {
+
+        // Explicit entities followed by entities of populations
+        let mut entity_cfgs = cfg.entities.clone();
+        for population_cfg in &cfg.populations {
+            let mut population_cfg = population_cfg.clone();
+            population_cfg.seed = population_cfg.seed.or(cfg.seed.map(|seed| derive_seed(seed, &population_cfg.id_pattern)));
+            entity_cfgs.extend(population::generate_entities(&population_cfg, population_cfg.entity_ids())?);
+        }
+
+        let network = match &cfg.network {
+            Some(network_cfg) => {
+                let mut network_cfg = network_cfg.clone();
+                network_cfg.seed = network_cfg.seed.or(cfg.seed.map(|seed| derive_seed(seed, "network")));
+                let nodes: Vec<String> = entity_cfgs.iter().map(|e| e.id.clone()).collect();
+                Network::from_cfg(&network_cfg, &nodes)?
+            }
+            None => Network::new(false),
+        };
 
-        let mut state = Rc::new(RefCell::new(WorldState {
-            entities : HashMap::new(),
+        let grid = match &cfg.grid {
+            Some(grid_cfg) => Some(Grid::from_cfg(grid_cfg)?),
+            None => None,
+        };
+
+        let state = Rc::new(RefCell::new(WorldState {
+            entities : BTreeMap::new(),
+            tags: HashMap::new(),
+            network,
+            grid,
+            space: Space::new(cfg.space.clone()),
+            entity_views: None,
+            globals: cfg.globals.clone(),
+            max_entities: cfg.limits.max_entities,
+            random_seed: cfg.seed,
         }));
+        let mut msg_bus = MessageBus::new();
 
-        
-        for entity_cfg in &cfg.entities {
+        for entity_cfg in &entity_cfgs {
             let mut entity = Entity::new(
                 entity_cfg.id.clone(),
                 entity_cfg.script_id.clone(),
                 cfg.script_library.get(&entity_cfg.script_id).unwrap().clone(),
+                entity_cfg.parameters.clone(),
                 entity_cfg.initial_state.clone(),
                 state.clone(),
             )
@@ -59,15 +124,61 @@ impl World {
                     })?;
             }
 
+            for topic in &entity_cfg.subscriptions {
+                entity.subscribe(topic);
+                msg_bus.subscribe(topic, &entity_cfg.id);
+            }
+
+            for tag in &entity_cfg.tags {
+                entity.add_tag(tag);
+            }
+
+            if let (Some(cell), Some(grid)) = (entity_cfg.cell, state.borrow_mut().grid.as_mut()) {
+                grid.move_entity(&entity_cfg.id, cell);
+            }
+
+            if let Some((x, y)) = entity_cfg.position {
+                let mut state = state.borrow_mut();
+                state.space.set_position(&entity_cfg.id, x, y);
+                if let Some((vx, vy)) = entity_cfg.velocity {
+                    state.space.set_velocity(&entity_cfg.id, vx, vy);
+                }
+            }
+
             state.borrow_mut().add_entity(entity_cfg.id.clone(), entity)?;
         }
 
+        let director = match &cfg.director {
+            Some(director_cfg) => {
+                let director = LuaDirectorController::new(&director_cfg.script, state.clone()).map_err(|e| {
+                    CoreError::ScriptExecution {
+                        message: format!("Failed to create director: {}", e),
+                    }
+                })?;
+
+                if let Some(initial_state) = &director_cfg.initial_state {
+                    director.set_state(initial_state.clone())?;
+                }
+                Some(director)
+            }
+            None => None,
+        };
+
+        let mut events = cfg.events.clone();
+        events.sort_by_key(|event| event.step);
+
         Ok(World {
             cfg: cfg.clone(),
             simulation_time: 0,
-            msg_bus: MessageBus::new(),
-            state: state,
-            metrics: Metrics::new(),
+            msg_bus,
+            state,
+            metrics: Metrics::new(cfg.limits.max_metric_points),
+            director,
+            stop_reason: None,
+            pending_events: events.into(),
+            rejected_messages: 0,
+            rejected_metric_points: 0,
+            command_errors: Vec::new(),
         })
     }
 
@@ -75,23 +186,60 @@ impl World {
         let mut world = World::new(&snapshot.configuration)?;
 
         world.simulation_time = snapshot.simulation_time;
-        world.metrics = Metrics::new_from_snapshot(&snapshot.metrics);
+        world.stop_reason = snapshot.stop_reason;
+        world.metrics = Metrics::new_from_snapshot(&snapshot.metrics, world.cfg.limits.max_metric_points);
         
-        for message in &snapshot.pending_messages {
-            world.msg_bus.schedule_message(
-                &message.sender,
-                message.receiver.clone(),
-                message.kind.clone(),
-                message.content.clone(),
-                message.receive_step,
-            );
+        world.msg_bus.set_next_message_id(snapshot.next_message_id);
+        for message in snapshot.pending_messages {
+            world.msg_bus.restore_message(message);
         }
 
         Ok(world)
     }
 
+    // Remove an entity and notify the director. A failing hook does not undo the removal, it is reported with the
+    // step or by apply_director_commands.
     pub fn remove_entity(&mut self, id: &str) -> Option<RefCell<Entity>> {
-        self.get_state_mut().entities.remove(id)
+        let removed = self.detach_entity(id);
+
+        if removed.is_some()
+            && let Some(director) = &self.director
+            && let Err(e) = director.on_entity_removed(id)
+        {
+            self.command_errors.push(format!("Director failed on removal of entity '{}': {}", id, e));
+        }
+
+        removed
+    }
+
+    // Apply commands the director issued in hooks outside of a step, e.g. when entities were spawned or removed
+    // through MCP. Returns failures of the hooks and the commands, they do not undo the operation.
+    pub fn apply_director_commands(&mut self) -> Vec<String> {
+        if let Err(e) = self.process_commands(Vec::new()) {
+            self.command_errors.push(format!("Director command failed: {}", e));
+        }
+        std::mem::take(&mut self.command_errors)
+    }
+
+    fn notify_spawned(&mut self, id: &str, script_id: &str) {
+        if let Some(director) = &self.director
+            && let Err(e) = director.on_entity_spawned(id, script_id)
+        {
+            self.command_errors.push(format!("Director failed on spawn of entity '{}': {}", id, e));
+        }
+    }
+
+    // Remove an entity from the world and its environments, without notifying the director
+    fn detach_entity(&mut self, id: &str) -> Option<RefCell<Entity>> {
+        self.msg_bus.unsubscribe_all(id);
+
+        let mut state = self.get_state_mut();
+        state.network.remove_node(id);
+        if let Some(grid) = state.grid.as_mut() {
+            grid.remove_entity(id);
+        }
+        state.space.remove_entity(id);
+        state.remove_entity(id)
     }
 
     pub fn fetch_messages(&mut self) -> Vec<Message> {
@@ -104,26 +252,87 @@ impl World {
     }
 
     pub fn update(&mut self, delta: u64) -> Result<WorldUpdateResult, CoreError> {
+        if let Some(reason) = &self.stop_reason {
+            return Err(CoreError::SimulationStopped { reason: reason.clone() });
+        }
+
         let mut update_result = WorldUpdateResult::new();
+        self.rejected_messages = 0;
+        self.rejected_metric_points = 0;
+        self.command_errors.clear();
 
         // Update simulation time
-        self.update_simulation_time(self.simulation_time + delta);
+        self.update_simulation_time(self.time_after(delta)?);
+        self.apply_due_events();
+
+        // Director acts before delivery, so its messages without delay arrive in this step
+        if let Some(director) = &self.director {
+            self.update_entity_views()?;
+            director.on_step_begin(self.simulation_time)?;
+            self.process_commands(Vec::new())?;
+        }
+
         self.deliver_messages(&mut update_result);
+        self.update_entity_views()?;
 
         let mut commands = Vec::new();
+        let mut rejected_messages = 0;
 
         for entity in self.get_state_ref().entities.values() {
-            let entity_commands = entity.borrow_mut().update(self.simulation_time)?;
+            let mut entity_commands = entity.borrow_mut().update(self.simulation_time)?;
+
+            // Messages over the quota are dropped, the entity's other commands still apply
+            if let Some(limit) = self.cfg.limits.max_messages_per_entity_per_step {
+                let mut sent = 0;
+                entity_commands.retain(|command| {
+                    if !matches!(command, Command::SendMessage { .. }) {
+                        return true;
+                    }
+                    sent += 1;
+                    sent <= limit
+                });
+                rejected_messages += sent.saturating_sub(limit);
+            }
+
             commands.extend(entity_commands);
         }
 
+        self.rejected_messages += rejected_messages;
         self.process_commands(commands)?;
 
+        let mut state = self.get_state_mut();
+        if let Some(grid) = state.grid.as_mut() {
+            grid.step();
+        }
+        state.space.step(delta);
+        drop(state);
+
+        if let Some(director) = &self.director {
+            self.update_entity_views()?;
+            director.on_step_end(self.simulation_time)?;
+            self.process_commands(Vec::new())?;
+        }
+
+        update_result.rejected_messages = self.rejected_messages;
+        update_result.rejected_metric_points = self.rejected_metric_points;
+        update_result.command_errors = std::mem::take(&mut self.command_errors);
         Ok(update_result)
     }
 
     fn process_commands(&mut self, commands: Vec<Command>) -> Result<(), CoreError> {
-        for command in commands {
+        let mut commands = VecDeque::from(commands);
+        loop {
+            // Commands issued by director hooks are processed after the current ones
+            if commands.is_empty()
+                && let Some(director) = &self.director
+            {
+                commands.extend(director.take_commands());
+            }
+
+            let Some(command) = commands.pop_front() else {
+                break;
+            };
+
             match command {
                 Command::SendMessage {
                     sender,
@@ -132,37 +341,356 @@ impl World {
                     content,
                     delay,
                 } => {
-                    self.msg_bus.schedule_message(
-                        &sender,
-                        receiver,
-                        kind,
-                        content,
-                        self.simulation_time + delay,
-                    );
+                    // Messages over the pending quota or beyond the largest time are dropped and reported with the step
+                    let scheduled = self
+                        .time_after(delay)
+                        .and_then(|receive_step| self.schedule_message(&sender, receiver, kind, content, receive_step));
+                    match scheduled {
+                        Err(CoreError::QuotaExceeded { .. } | CoreError::TimeOverflow { .. }) => self.rejected_messages += 1,
+                        result => result?,
+                    }
                 }
                 Command::RemoveEntity { id } => {
                     self.remove_entity(&id);
                 }
                 Command::RecordMetric { name, value } => {
-                    self.metrics.record_metric(self.simulation_time, &name, value);
+                    // Points over the quota are dropped and counted like messages
+                    match self.metrics.record_metric(self.simulation_time, &name, value) {
+                        Err(CoreError::QuotaExceeded { .. }) => self.rejected_metric_points += 1,
+                        result => result?,
+                    }
                 }
                 Command::SpawnEntity { script_id, entity_id, initial_state } => {
-                    // TODO: return error if entity with same ID exists or script is not found
-                    if let Some(script_cfg) = self.cfg.script_library.get(&script_id) {
-                        let entity = Entity::new(
-                            entity_id.clone(),
-                            script_id.clone(),
-                            script_cfg.clone(),
-                            initial_state,
-                            self.state.clone(),
-                        )?;
-
-                        self.get_state_mut().add_entity(entity_id, entity)?;
+                    // Spawns over max_entities are reported with the step
+                    let spawned = self.spawn_entity(EntityCfg {
+                        id: entity_id.clone(),
+                        script_id,
+                        initial_state,
+                        ..Default::default()
+                    });
+                    match spawned {
+                        Err(e @ CoreError::QuotaExceeded { .. }) => {
+                            self.command_errors.push(format!("Entity '{}' was not spawned: {}", entity_id, e));
+                        }
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                Command::SpawnPopulation { population } => {
+                    match self.spawn_population(&population) {
+                        Err(e @ CoreError::QuotaExceeded { .. }) => {
+                            self.command_errors.push(format!("Population '{}' was not spawned: {}", population.id_pattern, e));
+                        }
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                Command::Subscribe { entity_id, topic } => {
+                    if let Some(entity) = self.state.borrow().entities.get(&entity_id) {
+                        entity.borrow_mut().subscribe(&topic);
+                        self.msg_bus.subscribe(&topic, &entity_id);
                     }
                 }
+                Command::Unsubscribe { entity_id, topic } => {
+                    if let Some(entity) = self.state.borrow().entities.get(&entity_id) {
+                        entity.borrow_mut().unsubscribe(&topic);
+                        self.msg_bus.unsubscribe(&topic, &entity_id);
+                    }
+                }
+                Command::AddTag { entity_id, tag } => {
+                    self.get_state_mut().add_tag(&entity_id, &tag);
+                }
+                Command::RemoveTag { entity_id, tag } => {
+                    self.get_state_mut().remove_tag(&entity_id, &tag);
+                }
+                Command::AddEdge { from, to } => {
+                    let mut state = self.get_state_mut();
+                    if state.entities.contains_key(&from) && state.entities.contains_key(&to) {
+                        state.network.add_edge(&from, &to);
+                    }
+                }
+                Command::RemoveEdge { from, to } => {
+                    self.get_state_mut().network.remove_edge(&from, &to);
+                }
+                Command::MoveTo { entity_id, x, y } => {
+                    let mut state = self.get_state_mut();
+                    let exists = state.entities.contains_key(&entity_id);
+                    if let Some(grid) = state.grid.as_mut()
+                        && let Some(cell) = grid.normalize(x, y)
+                        && exists
+                    {
+                        grid.move_entity(&entity_id, cell);
+                    }
+                }
+                Command::SetCell { x, y, field, value } => {
+                    if let Some(grid) = self.get_state_mut().grid.as_mut()
+                        && let Some(cell) = grid.normalize(x, y)
+                    {
+                        grid.set_value(cell, &field, value);
+                    }
+                }
+                Command::SetPosition { entity_id, x, y } => {
+                    let mut state = self.get_state_mut();
+                    if state.entities.contains_key(&entity_id) {
+                        state.space.set_position(&entity_id, x, y);
+                    }
+                }
+                Command::SetVelocity { entity_id, vx, vy } => {
+                    self.get_state_mut().space.set_velocity(&entity_id, vx, vy);
+                }
+                Command::SetEntityState { entity_id, state } => {
+                    if let Some(entity) = self.get_state_ref().entities.get(&entity_id) {
+                        entity.borrow_mut().get_lua_controller_mut().set_state(state)?;
+                    }
+                }
+                Command::StopSimulation { reason } => {
+                    self.stop_reason.get_or_insert(reason);
+                }
+                Command::SetGlobal { key, value } => {
+                    self.get_state_mut().set_global(key, value);
+                }
+                Command::ChangeScript { entity_id, script_id, state } => {
+                    // Entity may have been removed earlier in the step. A failed change (e.g. unknown script) keeps
+                    // the entity as it is and is reported with the step.
+                    if self.get_state_ref().entities.contains_key(&entity_id)
+                        && let Err(e) = self.change_entity_script(&entity_id, &script_id, state)
+                    {
+                        self.command_errors.push(format!("Entity '{}' failed to change script: {}", entity_id, e));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn schedule_message(
+        &mut self,
+        sender: &str,
+        receiver: MessageReceiver,
+        kind: String,
+        content: JSONObject,
+        receive_step: u64,
+    ) -> Result<(), CoreError> {
+        if let Some(limit) = self.cfg.limits.max_pending_messages
+            && self.msg_bus.get_pending_messages_count() >= limit
+        {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_pending_messages".to_string(),
+                limit,
+            });
+        }
+
+        self.msg_bus.schedule_message(sender, receiver, kind, content, self.simulation_time, receive_step);
+        Ok(())
+    }
+
+    // Create an entity with its subscriptions, tags and placement. Fails if the script is unknown, the ID is taken or
+    // the placement is invalid.
+    pub fn spawn_entity(&mut self, entity_cfg: EntityCfg) -> Result<(), CoreError> {
+        self.check_entity(&entity_cfg)?;
+        let (id, script_id) = (entity_cfg.id.clone(), entity_cfg.script_id.clone());
+        self.create_entity(entity_cfg)?;
+        self.notify_spawned(&id, &script_id);
+        Ok(())
+    }
+
+    // Spawn all entities or none of them. All configurations are checked first, the director is notified only
+    // once every entity exists, a failing hook does not undo the spawn. Returns IDs of the entities.
+    pub fn spawn_entities(&mut self, entity_cfgs: Vec<EntityCfg>) -> Result<Vec<String>, CoreError> {
+        let mut ids = BTreeSet::new();
+        for entity_cfg in &entity_cfgs {
+            if !ids.insert(entity_cfg.id.clone()) {
+                return Err(CoreError::EntityCreation {
+                    id: entity_cfg.id.clone(),
+                    message: "Entity ID is listed more than once".to_string(),
+                });
+            }
+            self.check_entity(entity_cfg)?;
+        }
+
+        if entity_cfgs.len() > self.cfg.limits.max_entities.saturating_sub(self.get_entities_count()) {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_entities".to_string(),
+                limit: self.cfg.limits.max_entities,
+            });
+        }
+
+        // Scripts can still fail to initialize, entities created so far are then discarded without notifications
+        let mut spawned: Vec<(String, String)> = Vec::new();
+        for entity_cfg in entity_cfgs {
+            let (id, script_id) = (entity_cfg.id.clone(), entity_cfg.script_id.clone());
+            if let Err(e) = self.create_entity(entity_cfg) {
+                for (spawned_id, _) in &spawned {
+                    self.detach_entity(spawned_id);
+                }
+                return Err(e);
+            }
+            spawned.push((id, script_id));
+        }
+
+        for (id, script_id) in &spawned {
+            self.notify_spawned(id, script_id);
+        }
+
+        Ok(spawned.into_iter().map(|(id, _)| id).collect())
+    }
+
+    // Checks of an entity configuration that do not need its script to run
+    fn check_entity(&self, entity_cfg: &EntityCfg) -> Result<(), CoreError> {
+        check_entity_id(&entity_cfg.id)?;
+        let Some(script_cfg) = self.cfg.script_library.get(&entity_cfg.script_id) else {
+            return Err(CoreError::ScriptNotFound { script_id: entity_cfg.script_id.clone() });
+        };
+
+        if self.get_state_ref().entities.contains_key(&entity_cfg.id) {
+            return Err(CoreError::EntityCreation {
+                id: entity_cfg.id.clone(),
+                message: "Entity with the same ID already exists".to_string(),
+            });
+        }
+
+        script_cfg.validate_parameters(&entity_cfg.parameters)?;
+        entity_cfg.validate_placement(self.cfg.grid.as_ref())
+    }
+
+    // Create a checked entity and place it, without notifying the director
+    fn create_entity(&mut self, entity_cfg: EntityCfg) -> Result<(), CoreError> {
+        let mut entity = Entity::new(
+            entity_cfg.id.clone(),
+            entity_cfg.script_id.clone(),
+            self.cfg.script_library[&entity_cfg.script_id].clone(),
+            entity_cfg.parameters,
+            entity_cfg.initial_state,
+            self.state.clone(),
+        )?;
+
+        for tag in &entity_cfg.tags {
+            entity.add_tag(tag);
+        }
+
+        for topic in &entity_cfg.subscriptions {
+            entity.subscribe(topic);
+        }
+
+        self.get_state_mut().add_entity(entity_cfg.id.clone(), entity)?;
+
+        for topic in &entity_cfg.subscriptions {
+            self.msg_bus.subscribe(topic, &entity_cfg.id);
+        }
+
+        let mut state = self.get_state_mut();
+        if let (Some(cell), Some(grid)) = (entity_cfg.cell, state.grid.as_mut()) {
+            grid.move_entity(&entity_cfg.id, cell);
+        }
+
+        if let Some((x, y)) = entity_cfg.position {
+            state.space.set_position(&entity_cfg.id, x, y);
+            if let Some((vx, vy)) = entity_cfg.velocity {
+                state.space.set_velocity(&entity_cfg.id, vx, vy);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Spawn entities of a population, '{i}' in the ID pattern is replaced by the lowest unused indices. Returns IDs of the entities.
+    pub fn spawn_population(&mut self, population_cfg: &PopulationCfg) -> Result<Vec<String>, CoreError> {
+        if !self.cfg.script_library.contains_key(&population_cfg.script_id) {
+            return Err(CoreError::ScriptNotFound { script_id: population_cfg.script_id.clone() });
+        }
+
+        if population_cfg.count > 1 && !population_cfg.id_pattern.contains("{i}") {
+            return Err(CoreError::DeserializationError(format!("Population '{}' has {} entities but its id_pattern has no '{{i}}'", population_cfg.id_pattern, population_cfg.count)));
+        }
+
+        // Checked before IDs are generated, the count can be arbitrarily large
+        let available = self.cfg.limits.max_entities.saturating_sub(self.get_entities_count());
+        if population_cfg.count > available {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_entities".to_string(),
+                limit: self.cfg.limits.max_entities,
+            });
+        }
+
+        let ids = self.get_state_ref().generate_entity_ids(&population_cfg.id_pattern, population_cfg.count)?;
+        self.spawn_entities(population::generate_entities(population_cfg, ids)?)
+    }
+
+    // Apply events of the timeline scheduled up to the current time
+    // Events apply one at a time and all-or-none. A failed event, e.g. a spawn over the entity quota, is reported
+    // with the step and does not stop it.
+    fn apply_due_events(&mut self) {
+        while let Some(event) = self.pending_events.front()
+            && event.step <= self.simulation_time
+        {
+            let event = self.pending_events.pop_front().unwrap();
+            if let Err(e) = self.apply_event(event.action) {
+                self.command_errors.push(format!("Event at step {} failed: {}", event.step, e));
+            }
+        }
+    }
+
+    fn apply_event(&mut self, action: EventActionCfg) -> Result<(), CoreError> {
+        let commands = match action {
+            EventActionCfg::SendMessage { entity_id, tag, kind, content } => {
+                let receiver = match (entity_id, tag) {
+                    (Some(id), _) => MessageReceiver::Entity { id },
+                    (None, Some(tag)) => MessageReceiver::Tag { tag },
+                    (None, None) => return Ok(()),
+                };
+
+                vec![Command::SendMessage {
+                    sender: TIMELINE_ID.to_string(),
+                    receiver,
+                    kind,
+                    content,
+                    delay: 0,
+                }]
             }
+            EventActionCfg::SetGlobal { key, value } => vec![Command::SetGlobal { key, value }],
+            EventActionCfg::Spawn { script_id, count, id_pattern, initial_state } => {
+                let entity_cfgs = self
+                    .get_state_ref()
+                    .generate_entity_ids(&id_pattern, count)?
+                    .into_iter()
+                    .map(|id| EntityCfg {
+                        id,
+                        script_id: script_id.clone(),
+                        initial_state: initial_state.clone(),
+                        ..Default::default()
+                    })
+                    .collect();
+                self.spawn_entities(entity_cfgs)?;
+
+                // Commands the director issued when notified of the spawns
+                Vec::new()
+            }
+            EventActionCfg::RemoveEntity { entity_id } => vec![Command::RemoveEntity { id: entity_id }],
+        };
+
+        self.process_commands(commands)
+    }
+
+    // Capture views of all entities so that state queries are consistent during the step. Message-only worlds
+    // have no state queries and skip reading the state of every entity.
+    fn update_entity_views(&self) -> Result<(), CoreError> {
+        if self.cfg.message_only {
+            return Ok(());
+        }
+        self.capture_entity_views()
+    }
+
+    fn capture_entity_views(&self) -> Result<(), CoreError> {
+        // Scripts can query the world while their state is read, keep the world state borrowed immutably
+        let mut views = HashMap::new();
+        for (id, entity) in &self.get_state_ref().entities {
+            views.insert(id.clone(), entity.borrow().create_view()?);
         }
 
+        self.get_state_mut().entity_views = Some(views);
         Ok(())
     }
 
@@ -179,7 +707,38 @@ impl World {
                     }
                 }
                 crate::core::messaging::MessageReceiver::Radius2D { x, y, radius } => {
-                    // TODO: Implement radius-based message delivery
+                    let state = self.get_state_ref();
+                    for receiver_id in state.space.query_radius(x as f64, y as f64, radius as f64) {
+                        if receiver_id == msg.sender {
+                            continue;
+                        }
+
+                        if let Some(entity) = state.entities.get(&receiver_id) {
+                            entity.borrow_mut().receive_message(msg.clone());
+                        }
+                    }
+                }
+                crate::core::messaging::MessageReceiver::Topic { ref topic } => {
+                    let state = self.get_state_ref();
+                    for subscriber_id in self.msg_bus.get_subscribers(topic) {
+                        if let Some(entity) = state.entities.get(&subscriber_id) {
+                            entity.borrow_mut().receive_message(msg.clone());
+                        }
+                    }
+                }
+                crate::core::messaging::MessageReceiver::Tag { tag: ref name } | crate::core::messaging::MessageReceiver::Group { ref name } => {
+                    let state = self.get_state_ref();
+                    for receiver_id in state.get_tagged_entities(name) {
+                        state.entities[&receiver_id].borrow_mut().receive_message(msg.clone());
+                    }
+                }
+                crate::core::messaging::MessageReceiver::Neighbors { ref id } => {
+                    let state = self.get_state_ref();
+                    for neighbor_id in state.network.get_neighbors(id) {
+                        if let Some(entity) = state.entities.get(&neighbor_id) {
+                            entity.borrow_mut().receive_message(msg.clone());
+                        }
+                    }
                 }
             }
         }
@@ -197,6 +756,43 @@ impl World {
         self.get_state_ref().get_entity_state(id)
     }
 
+    // Set global variables immediately, null values remove them
+    pub fn set_globals(&mut self, globals: JSONObject) {
+        let mut state = self.get_state_mut();
+        for (key, value) in globals {
+            state.set_global(key, value);
+        }
+    }
+
+    // Schedule a message from outside of the world, e.g. from an external actor. Delivered after the delay from the current time.
+    // Returns the step at which the message is received.
+    pub fn send_message(
+        &mut self,
+        sender: &str,
+        receiver: MessageReceiver,
+        kind: String,
+        content: JSONObject,
+        delay: u64,
+    ) -> Result<u64, CoreError> {
+        if let MessageReceiver::Entity { id } = &receiver
+            && !self.get_state_ref().entities.contains_key(id)
+        {
+            return Err(CoreError::EntityNotFound { id: id.clone() });
+        }
+
+        let receive_step = self.time_after(delay)?;
+        self.schedule_message(sender, receiver, kind, content, receive_step)?;
+        Ok(receive_step)
+    }
+
+    // Simulation time after the delay, an error if it does not fit
+    fn time_after(&self, delay: u64) -> Result<u64, CoreError> {
+        self.simulation_time.checked_add(delay).ok_or(CoreError::TimeOverflow {
+            time: self.simulation_time,
+            delay,
+        })
+    }
+
     fn update_simulation_time(&mut self, new_time: u64) {
         self.simulation_time = new_time;
     }
@@ -209,6 +805,163 @@ impl World {
         self.state.borrow_mut()
     }
 
+    pub fn get_script(&self, script_id: &str) -> Option<&ScriptCfg> {
+        self.cfg.script_library.get(script_id)
+    }
+
+    // Add a script to the script library, entities can use it once added
+    pub fn add_script(&mut self, script_cfg: ScriptCfg) -> Result<(), CoreError> {
+        if self.cfg.script_library.contains_key(&script_cfg.id) {
+            return Err(CoreError::ScriptAlreadyExists { script_id: script_cfg.id.clone() });
+        }
+
+        self.check_script(&script_cfg)?;
+        self.cfg.script_library.insert(script_cfg.id.clone(), script_cfg);
+        Ok(())
+    }
+
+    // Replace a script and migrate state of all entities using it to the new code. Returns IDs of the migrated entities.
+    pub fn update_script(&mut self, script_cfg: ScriptCfg) -> Result<Vec<String>, CoreError> {
+        if !self.cfg.script_library.contains_key(&script_cfg.id) {
+            return Err(CoreError::ScriptNotFound { script_id: script_cfg.id.clone() });
+        }
+
+        self.check_script(&script_cfg)?;
+
+        let mut entity_ids = self
+            .get_state_ref()
+            .filter_entities(|(_, entity)| entity.borrow().get_script_id() == &script_cfg.id);
+        entity_ids.sort();
+
+        // Migrate all entities before changing any of them, so that a failure leaves the world untouched
+        let mut controllers = Vec::new();
+        let mut failures = BTreeMap::new();
+        for id in &entity_ids {
+            match self.migrate_entity(id, &script_cfg) {
+                Ok(controller) => controllers.push((id.clone(), controller)),
+                Err(e) => {
+                    failures.insert(id.clone(), e.to_string());
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            return Err(CoreError::ScriptMigration {
+                script_id: script_cfg.id,
+                failures,
+            });
+        }
+
+        let state = self.get_state_ref();
+        for (id, controller) in controllers {
+            let mut entity = state.entities[&id].borrow_mut();
+            let parameters = entity.get_parameters().clone();
+            entity.change_script(script_cfg.id.clone(), parameters, controller);
+        }
+        drop(state);
+
+        self.cfg.script_library.insert(script_cfg.id.clone(), script_cfg);
+        Ok(entity_ids)
+    }
+
+    // Remove a script from the script library. Fails if entities or pending events still use it.
+    pub fn remove_script(&mut self, script_id: &str) -> Result<ScriptCfg, CoreError> {
+        if !self.cfg.script_library.contains_key(script_id) {
+            return Err(CoreError::ScriptNotFound { script_id: script_id.to_string() });
+        }
+
+        let mut users = self
+            .get_state_ref()
+            .filter_entities(|(_, entity)| entity.borrow().get_script_id() == script_id);
+        users.sort();
+
+        for event in &self.pending_events {
+            if let EventActionCfg::Spawn { script_id: event_script_id, .. } = &event.action
+                && event_script_id == script_id
+            {
+                users.push(format!("event at step {}", event.step));
+            }
+        }
+
+        if !users.is_empty() {
+            return Err(CoreError::ScriptInUse {
+                script_id: script_id.to_string(),
+                users,
+            });
+        }
+
+        Ok(self.cfg.script_library.remove(script_id).unwrap())
+    }
+
+    // Switch the entity to another script of the library. ID, tags, subscriptions and messages not processed yet are kept,
+    // overrides of parameters are kept if the new script declares them. Without a state the new script starts with its own.
+    pub fn change_entity_script(&mut self, id: &str, script_id: &str, state: Option<JSONObject>) -> Result<(), CoreError> {
+        let Some(script_cfg) = self.cfg.script_library.get(script_id) else {
+            return Err(CoreError::ScriptNotFound { script_id: script_id.to_string() });
+        };
+
+        let parameters: JSONObject = match self.get_state_ref().entities.get(id) {
+            Some(entity) => entity
+                .borrow()
+                .get_parameters()
+                .iter()
+                .filter(|(name, _)| script_cfg.parameters.contains_key(*name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            None => return Err(CoreError::EntityNotFound { id: id.to_string() }),
+        };
+        script_cfg.validate_parameters(&parameters)?;
+
+        let mut controller = LuaScriptController::new(
+            id.to_string(),
+            &script_cfg.script,
+            &script_cfg.resolve_parameters(&parameters),
+            self.state.clone(),
+        )
+        .map_err(|e| CoreError::EntityCreation {
+            id: id.to_string(),
+            message: format!("Failed to create LuaScriptController: {}", e),
+        })?;
+
+        if let Some(state) = state {
+            controller.set_state(state)?;
+        }
+
+        self.get_state_ref().entities[id]
+            .borrow_mut()
+            .change_script(script_id.to_string(), parameters, controller);
+        Ok(())
+    }
+
+    // Check that the script compiles, defines the required functions and can be used with globals of the world
+    fn check_script(&self, script_cfg: &ScriptCfg) -> Result<(), CoreError> {
+        script_cfg.validate_defaults()?;
+        script_cfg.check_code(self.get_state_ref().get_globals())
+    }
+
+    // Create controller of the entity with the new script and the state of the current one
+    fn migrate_entity(&self, id: &str, script_cfg: &ScriptCfg) -> Result<LuaScriptController, CoreError> {
+        let (parameters, state) = {
+            let world_state = self.get_state_ref();
+            let entity = world_state.entities[id].borrow();
+            script_cfg.validate_parameters(entity.get_parameters())?;
+            (entity.get_parameters().clone(), entity.get_lua_controller().get_state()?)
+        };
+
+        let mut controller = LuaScriptController::new(
+            id.to_string(),
+            &script_cfg.script,
+            &script_cfg.resolve_parameters(&parameters),
+            self.state.clone(),
+        )
+        .map_err(|e| CoreError::ScriptExecution {
+            message: format!("Failed to create controller: {}", e),
+        })?;
+
+        controller.set_state(state)?;
+        Ok(controller)
+    }
+
     pub fn get_metrics_ref(&self) -> &Metrics {
         &self.metrics
     }
@@ -216,18 +969,42 @@ impl World {
     pub fn create_snapshot(&self) -> Result<crate::core::snapshot::WorldSnapshot, CoreError> {
         let mut world_config = WorldCfg::new(self.cfg.name.clone());
 
-        // Copy scripts
-        for (script_id, script_cfg) in &self.cfg.script_library {
-            world_config.add_script(script_id.clone(), script_cfg.script.clone());
+        // Copy scripts with their parameters
+        world_config.script_library = self.cfg.script_library.clone();
+
+        // Copy entities, their states, subscriptions and cells
+        let state = self.get_state_ref();
+        for (id, entity_cell) in &state.entities {
+            let mut entity_cfg = entity_cell.borrow().create_cfg(id)?;
+            entity_cfg.cell = state.grid.as_ref().and_then(|grid| grid.get_position(id));
+            if let Some(body) = state.space.get_body(id) {
+                entity_cfg.position = Some((body.x, body.y));
+                entity_cfg.velocity = Some((body.vx, body.vy));
+            }
+            world_config.upsert_entity(entity_cfg)?;
         }
 
-        // Copy entities and their states
-        for (id, entity_cell) in &self.get_state_ref().entities {
-            let entity = entity_cell.borrow();
-            let lua_controller = entity.get_lua_controller();
-            let state = lua_controller.get_state()?;
+        // Copy grid with current values of its fields and bounds of the space
+        world_config.grid = state.grid.as_ref().map(|grid| grid.create_cfg());
+        world_config.space = state.space.get_bounds().cloned();
+        world_config.message_only = self.cfg.message_only;
+        world_config.globals = state.globals.clone();
+        world_config.limits = self.cfg.limits.clone();
+        world_config.seed = self.cfg.seed;
+        world_config.events = self.pending_events.iter().cloned().collect();
 
-            world_config.upsert_entity(id, entity.get_script_id(), Some(state))?;
+        // Copy director with its current state
+        if let (Some(director_cfg), Some(director)) = (&self.cfg.director, &self.director) {
+            world_config.director = Some(DirectorCfg {
+                script: director_cfg.script.clone(),
+                initial_state: director.get_state()?,
+            });
+        }
+
+        // Copy current edges of the network
+        let network_cfg = state.network.create_cfg();
+        if self.cfg.network.is_some() || !network_cfg.edges.is_empty() {
+            world_config.network = Some(network_cfg);
         }
 
         let mut messages = Vec::new();
@@ -239,7 +1016,9 @@ impl World {
             world_config,
             self.simulation_time,
             messages,
+            self.msg_bus.get_next_message_id(),
             self.metrics.create_snapshot(),
+            self.stop_reason.clone(),
         ))
     }
 
@@ -251,13 +1030,49 @@ impl World {
         self.simulation_time   
     }
 
+    // Reason why the run was ended, None if it can continue
+    pub fn get_stop_reason(&self) -> Option<&String> {
+        self.stop_reason.as_ref()
+    }
+
     pub fn get_pending_messages_count(&self) -> usize {
         self.msg_bus.get_pending_messages_count()
     }
+
+    // Compile a Lua predicate over this world, see evaluate_predicate
+    pub fn create_predicate(&self, source: &str) -> Result<LuaPredicate, CoreError> {
+        LuaPredicate::new(source, self.state.clone())
+    }
+
+    // Evaluate the predicate against the current state of the world. Predicates can always query states, views are
+    // captured for the evaluation and dropped afterwards unless the world keeps them.
+    pub fn evaluate_predicate(&self, predicate: &LuaPredicate) -> Result<bool, CoreError> {
+        self.capture_entity_views()?;
+        let result = predicate.evaluate();
+        if self.cfg.message_only {
+            self.get_state_mut().entity_views = None;
+        }
+        result
+    }
 }
 
 impl WorldState {
-    pub fn get_entities(&self) -> &HashMap<String, RefCell<Entity>> {
+    // Empty world without entities and environments, used to check scripts in isolation
+    pub fn new_sandbox(globals: JSONObject) -> Self {
+        WorldState {
+            entities: BTreeMap::new(),
+            tags: HashMap::new(),
+            network: Network::new(false),
+            grid: None,
+            space: Space::new(None),
+            entity_views: Some(HashMap::new()),
+            globals,
+            max_entities: 0,
+            random_seed: None,
+        }
+    }
+
+    pub fn get_entities(&self) -> &BTreeMap<String, RefCell<Entity>> {
         &self.entities
     }
 
@@ -272,24 +1087,128 @@ impl WorldState {
             .collect()
     }
 
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn get_grid(&self) -> Option<&Grid> {
+        self.grid.as_ref()
+    }
+
+    pub fn get_space(&self) -> &Space {
+        &self.space
+    }
+
+    // Views of entities as of step start, None if state queries are disabled
+    pub fn get_entity_views(&self) -> Option<&HashMap<String, EntityView>> {
+        self.entity_views.as_ref()
+    }
+
+    pub fn get_globals(&self) -> &JSONObject {
+        &self.globals
+    }
+
+    // Seed of math.random of the script with the ID, None if the world is not seeded
+    pub fn get_random_seed(&self, id: &str) -> Option<u64> {
+        self.random_seed.map(|seed| derive_seed(seed, id))
+    }
+
+    fn set_global(&mut self, key: String, value: serde_json::Value) {
+        if value.is_null() {
+            self.globals.remove(&key);
+        } else {
+            self.globals.insert(key, value);
+        }
+    }
+
+    // IDs for new entities, '{i}' in the pattern is replaced by the lowest indices not used by existing entities.
+    // A pattern without '{i}' is the ID itself, which must not be used yet.
+    pub fn generate_entity_ids(&self, pattern: &str, count: usize) -> Result<Vec<String>, CoreError> {
+        if !pattern.contains("{i}") {
+            if count == 0 {
+                return Ok(Vec::new());
+            }
+            if self.entities.contains_key(pattern) {
+                return Err(CoreError::EntityCreation {
+                    id: pattern.to_string(),
+                    message: "Entity with the same ID already exists".to_string(),
+                });
+            }
+            return Ok(vec![pattern.to_string()]);
+        }
+
+        let mut ids = Vec::new();
+        let mut index = 0;
+        while ids.len() < count {
+            let id = pattern.replace("{i}", &index.to_string());
+            if !self.entities.contains_key(&id) {
+                ids.push(id);
+            }
+            index += 1;
+        }
+        Ok(ids)
+    }
+
     pub fn add_entity(&mut self, id : String, entity: Entity) -> Result<(), CoreError> {
-        if self.entities.len() >= MAX_ENTITIES_PER_WORLD {
-            return Err(CoreError::WorldCapacityExceeded{ capacity: MAX_ENTITIES_PER_WORLD });
+        if self.entities.len() >= self.max_entities {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_entities".to_string(),
+                limit: self.max_entities,
+            });
         }
 
+        for tag in entity.get_tags() {
+            self.tags.entry(tag.clone()).or_default().insert(id.clone());
+        }
         self.entities.insert(id, RefCell::new(entity));
         Ok(())
-    } 
+    }
+
+    fn remove_entity(&mut self, id: &str) -> Option<RefCell<Entity>> {
+        let removed = self.entities.remove(id)?;
+        for tag in removed.borrow().get_tags() {
+            self.untag(id, tag);
+        }
+        Some(removed)
+    }
+
+    // IDs of entities with the tag, in stable order
+    pub fn get_tagged_entities(&self, tag: &str) -> Vec<String> {
+        self.tags.get(tag).map(|ids| ids.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn add_tag(&mut self, id: &str, tag: &str) {
+        if let Some(entity) = self.entities.get(id) {
+            entity.borrow_mut().add_tag(tag);
+            self.tags.entry(tag.to_string()).or_default().insert(id.to_string());
+        }
+    }
+
+    fn remove_tag(&mut self, id: &str, tag: &str) {
+        if let Some(entity) = self.entities.get(id) {
+            entity.borrow_mut().remove_tag(tag);
+            self.untag(id, tag);
+        }
+    }
+
+    fn untag(&mut self, id: &str, tag: &str) {
+        if let Some(ids) = self.tags.get_mut(tag) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.tags.remove(tag);
+            }
+        }
+    }
 
 
     pub fn get_entity_state(&self, id: &str) -> Result<JSONObject, CoreError> {
         match self.entities.get(id) {
-            Some(entity) => match entity.borrow().get_lua_controller().get_state() {
-                Ok(state) => Ok(state),
-                Err(e) => Err(CoreError::ScriptState{
-                    message: format!("Failed to get state for entity '{}': {}", id, e),
-                }),
-            },
+            Some(entity) => entity.borrow().get_lua_controller().get_state().map_err(|e| match e {
+                CoreError::ScriptState { message } => CoreError::ScriptState {
+                    message: format!("Failed to get state for entity '{}': {}", id, message),
+                },
+                e => e,
+            }),
             None => Err(CoreError::EntityNotFound { id: id.to_string() }),
         }
     }
@@ -299,6 +1218,15 @@ impl WorldUpdateResult {
     pub fn new() -> Self {
         WorldUpdateResult {
             delivered_messages: Vec::new(),
+            rejected_messages: 0,
+            rejected_metric_points: 0,
+            command_errors: Vec::new(),
         }
     }
 }
+
+// Seed for a part of the world (entity, population, network), independent of the order of creation
+fn derive_seed(seed: u64, label: &str) -> u64 {
+    // FNV-1a, stable across platforms and releases
+    label.bytes().fold(0xcbf29ce484222325 ^ seed, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
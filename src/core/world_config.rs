@@ -1,9 +1,23 @@
+use crate::core::scripting::lua::{DiagnosticSeverity, ScriptDiagnostic, validate_script};
 use rmcp::schemars;
-use crate::core::{errors::CoreError, messaging::JSONObject};
-use std::collections::HashMap;
+use crate::core::errors::CoreError;
+use crate::core::messaging::{JSONObject, RESERVED_SENDER_IDS};
+use std::collections::{BTreeMap, HashMap};
 
+const DEFAULT_MAX_ENTITIES: usize = 10000;
+// Upper bound of cells of a grid, e.g. 1024 x 1024
+const MAX_GRID_CELLS: usize = 1 << 20;
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+// Messages of an entity carry its ID as sender, which must not be mistaken for the director or the timeline
+pub fn check_entity_id(id: &str) -> Result<(), CoreError> {
+    if RESERVED_SENDER_IDS.contains(&id) {
+        return Err(CoreError::DeserializationError(format!("Entity ID '{}' is reserved, choose another ID", id)));
+    }
+    Ok(())
+}
+
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 #[schemars(description = "Configuration for an entity in the simulation world.")]
 pub struct EntityCfg {
     #[schemars(description = "The unique ID of the entity")]
@@ -12,6 +26,48 @@ pub struct EntityCfg {
     pub script_id: String,
     #[schemars(description = "Optional initial state for the entity as a JSON object")]
     pub initial_state: Option<JSONObject>,
+    #[serde(default, skip_serializing_if = "JSONObject::is_empty")]
+    #[schemars(description = "Overrides of parameters declared by the script, as a JSON object")]
+    pub parameters: JSONObject,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Topics the entity is subscribed to. Messages published to these topics are delivered to the entity.")]
+    pub subscriptions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Tags of the entity (e.g. roles or groups). Messages can be sent to all entities with a tag.")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional [x, y] cell of the grid occupied by the entity")]
+    pub cell: Option<(u32, u32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional [x, y] position of the entity in continuous 2D space")]
+    pub position: Option<(f64, f64)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional [vx, vy] velocity of the entity in continuous 2D space, per unit of simulation time. Requires position.")]
+    pub velocity: Option<(f64, f64)>,
+}
+
+impl EntityCfg {
+    // Check the cell against the grid of the world and that a velocity comes with a finite position
+    pub fn validate_placement(&self, grid: Option<&GridCfg>) -> Result<(), CoreError> {
+        if let Some((x, y)) = self.cell {
+            match grid {
+                Some(grid) if x < grid.width && y < grid.height => {}
+                Some(_) => return Err(CoreError::DeserializationError(format!("Entity '{}' cell ({}, {}) is outside of the grid", self.id, x, y))),
+                None => return Err(CoreError::DeserializationError(format!("Entity '{}' has a cell but the world has no grid", self.id))),
+            }
+        }
+
+        let is_finite = |(x, y): (f64, f64)| x.is_finite() && y.is_finite();
+        if self.position.is_some_and(|position| !is_finite(position)) || self.velocity.is_some_and(|velocity| !is_finite(velocity)) {
+            return Err(CoreError::DeserializationError(format!("Entity '{}' has a non-finite position or velocity", self.id)));
+        }
+
+        if self.velocity.is_some() && self.position.is_none() {
+            return Err(CoreError::DeserializationError(format!("Entity '{}' has a velocity but no position", self.id)));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -23,6 +79,275 @@ pub struct WorldCfg {
     pub script_library: HashMap<String, ScriptCfg>,
     #[schemars(description = "The entities to initialize in the new world")]
     pub entities: Vec<EntityCfg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional network (graph) connecting entities. Entities can send messages to their neighbors.")]
+    pub network: Option<NetworkCfg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional discrete grid environment where entities occupy cells and cells hold numeric fields")]
+    pub grid: Option<GridCfg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional bounds of continuous 2D space. Space is unbounded if omitted.")]
+    pub space: Option<SpaceCfg>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schemars(description = "Disable reading states of other entities from scripts (world.get_entity_state, world.find_entities), entities learn about each other only through messages. Also saves capturing the states of all entities every step.")]
+    pub message_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional world-level director script driving the scenario (shocks, interventions, ending the run)")]
+    pub director: Option<DirectorCfg>,
+    #[serde(default, skip_serializing_if = "JSONObject::is_empty")]
+    #[schemars(description = "Global variables shared by all entities (e.g. prices, weather, policy levels) as a JSON object")]
+    pub globals: JSONObject,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Timeline of external events (shocks, interventions) applied automatically at given simulation times")]
+    pub events: Vec<EventCfg>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Populations of entities generated from templates, added after the explicit entities")]
+    pub populations: Vec<PopulationCfg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Seed for reproducible runs. Seeds math.random of every entity and the director, and the populations and network generator that have no seed of their own. Snapshots do not keep the state of math.random, it starts over from the seed on restore. Random if omitted.")]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    #[schemars(description = "Capacity and resource quotas of the world")]
+    pub limits: LimitsCfg,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Capacity and resource quotas of a world. Messages over quotas are dropped and reported by the step, exceeding other quotas fails the operation or the simulation step.")]
+pub struct LimitsCfg {
+    #[serde(default = "default_max_entities")]
+    #[schemars(description = "Maximum number of entities. Defaults to 10000.")]
+    pub max_entities: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Maximum number of messages waiting for delivery. Unlimited if omitted.")]
+    pub max_pending_messages: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Maximum number of messages an entity can send in one step. Unlimited if omitted.")]
+    pub max_messages_per_entity_per_step: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Maximum number of recorded metric points over all metrics. Unlimited if omitted.")]
+    pub max_metric_points: Option<usize>,
+}
+
+impl Default for LimitsCfg {
+    fn default() -> Self {
+        LimitsCfg {
+            max_entities: DEFAULT_MAX_ENTITIES,
+            max_pending_messages: None,
+            max_messages_per_entity_per_step: None,
+            max_metric_points: None,
+        }
+    }
+}
+
+fn default_max_entities() -> usize {
+    DEFAULT_MAX_ENTITIES
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Template of a population of entities sharing a script.")]
+pub struct PopulationCfg {
+    #[schemars(description = "The ID of the script to use for the entities")]
+    pub script_id: String,
+    #[schemars(description = "Number of entities to create")]
+    pub count: usize,
+    #[schemars(description = "Pattern of entity IDs, '{i}' is replaced by the index of the entity, e.g. 'sheep_{i}'")]
+    pub id_pattern: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(description = "Generators of initial state values. The key is the name of the state variable.")]
+    pub initial_state: BTreeMap<String, ValueGeneratorCfg>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Tags of every entity of the population")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "JSONObject::is_empty")]
+    #[schemars(description = "Overrides of parameters declared by the script, shared by every entity of the population")]
+    pub parameters: JSONObject,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Seed of the random generator. Random seed is used if omitted.")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[schemars(description = "Generator of a state value")]
+pub enum ValueGeneratorCfg {
+    #[schemars(description = "Same value for every entity")]
+    Constant { value: serde_json::Value },
+    #[schemars(description = "Uniformly distributed number between min and max, an integer from min to max inclusive if integer is true")]
+    Uniform {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        integer: bool,
+    },
+    #[schemars(description = "Normally distributed number")]
+    Normal { mean: f64, std_dev: f64 },
+    #[schemars(description = "One of the values, with optional relative weights")]
+    Choice {
+        values: Vec<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weights: Option<Vec<f64>>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "External event applied at the beginning of a step, before messages are delivered.")]
+pub struct EventCfg {
+    #[schemars(description = "Simulation time at which the event is applied")]
+    pub step: u64,
+    #[serde(flatten)]
+    pub action: EventActionCfg,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+#[schemars(description = "Action of an event")]
+pub enum EventActionCfg {
+    #[schemars(description = "Send a message to an entity (entity_id) or to all entities with a tag (tag). Messages have sender 'timeline' and are delivered in the same step.")]
+    SendMessage {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        entity_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        kind: String,
+        #[serde(default)]
+        content: JSONObject,
+    },
+    #[schemars(description = "Set a global variable, null value removes it")]
+    SetGlobal { key: String, value: serde_json::Value },
+    #[schemars(description = "Spawn count entities with the script. '{i}' in id_pattern is replaced by the lowest index not used by an existing entity, e.g. 'sheep_{i}'.")]
+    Spawn {
+        script_id: String,
+        #[serde(default = "default_spawn_count")]
+        count: usize,
+        id_pattern: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initial_state: Option<JSONObject>,
+    },
+    #[schemars(description = "Remove an entity")]
+    RemoveEntity { entity_id: String },
+}
+
+fn default_spawn_count() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Configuration of the director, a world-level script that is not an entity. It can define hooks on_step_begin(time), on_step_end(time), on_entity_spawned(id, script_id) and on_entity_removed(id), all optional.")]
+pub struct DirectorCfg {
+    #[schemars(description = "The Lua script content")]
+    pub script: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional initial state passed to set_state function of the director")]
+    pub initial_state: Option<JSONObject>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Configuration of a network (graph) environment. Nodes of the network are entities.")]
+pub struct NetworkCfg {
+    #[serde(default)]
+    #[schemars(description = "Whether edges are directed. Defaults to false.")]
+    pub directed: bool,
+    #[serde(default)]
+    #[schemars(description = "Explicit list of edges as [from_entity_id, to_entity_id] pairs")]
+    pub edges: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional generator connecting the entities of the world in their configuration order. Generated edges are added to explicit edges.")]
+    pub generator: Option<NetworkGeneratorCfg>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Seed of the random generator. Random seed is used if omitted.")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[schemars(description = "Random network generator. Except for erdos_renyi, generated links are undirected, a directed network gets both directions of each link.")]
+pub enum NetworkGeneratorCfg {
+    #[schemars(description = "Erdos-Renyi random graph, each pair of entities is connected with the given probability (0 to 1). In a directed network each direction is drawn separately.")]
+    ErdosRenyi { probability: f64 },
+    #[schemars(description = "Watts-Strogatz small world, ring of entities connected to k nearest neighbors (k must be even), each edge is rewired with probability beta (0 to 1)")]
+    WattsStrogatz { k: usize, beta: f64 },
+    #[schemars(description = "Barabasi-Albert scale-free graph, each new entity attaches to m existing entities")]
+    BarabasiAlbert { m: usize },
+    #[schemars(description = "2D lattice with given width, entities are placed row by row and connected to up to 4 neighbors. Periodic lattice wraps around edges.")]
+    Lattice {
+        width: usize,
+        #[serde(default)]
+        periodic: bool,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Configuration of a discrete 2D grid environment.")]
+pub struct GridCfg {
+    #[schemars(description = "Number of cells along the x axis")]
+    pub width: u32,
+    #[schemars(description = "Number of cells along the y axis")]
+    pub height: u32,
+    #[serde(default)]
+    #[schemars(description = "Whether the grid wraps around its edges (torus). Defaults to false.")]
+    pub wrap: bool,
+    #[serde(default)]
+    #[schemars(description = "Neighborhood of a cell used for neighbor queries and diffusion. Defaults to 'moore'.")]
+    pub neighborhood: Neighborhood,
+    #[serde(default)]
+    #[schemars(description = "Numeric fields held by each cell (e.g. grass, pheromone). The key is the field name.")]
+    pub fields: BTreeMap<String, FieldCfg>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighborhood {
+    #[default]
+    #[schemars(description = "8 surrounding cells")]
+    Moore,
+    #[schemars(description = "4 orthogonally adjacent cells")]
+    VonNeumann,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Configuration of a numeric field of grid cells.")]
+pub struct FieldCfg {
+    #[serde(default)]
+    #[schemars(description = "Initial value of the field in every cell. Defaults to 0.")]
+    pub initial: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Optional values of all cells, row by row (width * height values). Overrides initial value.")]
+    pub values: Option<Vec<f64>>,
+    #[serde(default)]
+    #[schemars(description = "Fraction of the value each cell spreads evenly to its neighbors every step (0 to 1)")]
+    pub diffusion: f64,
+    #[serde(default)]
+    #[schemars(description = "Fraction of the value lost by each cell every step (0 to 1)")]
+    pub decay: f64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Bounds of continuous 2D space, spanning from [0, 0] to [width, height].")]
+pub struct SpaceCfg {
+    #[schemars(description = "Size of the space along the x axis")]
+    pub width: f64,
+    #[schemars(description = "Size of the space along the y axis")]
+    pub height: f64,
+    #[serde(default)]
+    #[schemars(description = "What happens to entities reaching the bounds. Defaults to 'clamp'.")]
+    pub boundary: Boundary,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Size of the cells indexing positions for spatial queries. Best close to the usual query radius. Defaults to 1/32 of the longer side."
+    )]
+    pub cell_size: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    #[default]
+    #[schemars(description = "Entities stop at the bounds")]
+    Clamp,
+    #[schemars(description = "Entities leaving the bounds enter from the opposite side")]
+    Wrap,
+    #[schemars(description = "Entities are reflected from the bounds")]
+    Bounce,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
@@ -34,6 +359,183 @@ pub struct ScriptCfg {
     pub kind: String,
     #[schemars(description = "The script content")]
     pub script: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(description = "Tunable parameters of the script with their defaults, read-only in Lua as self.params. The key is the parameter name.")]
+    pub parameters: BTreeMap<String, ParameterCfg>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[schemars(description = "Declaration of a script parameter, similar to JSON schema.")]
+pub struct ParameterCfg {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Type of the value. Any value is accepted if omitted.")]
+    pub value_type: Option<ParameterType>,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    #[schemars(description = "Default value used if the entity does not override it")]
+    pub default: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Description of the parameter")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Minimum of a numeric value")]
+    pub minimum: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Maximum of a numeric value")]
+    pub maximum: Option<f64>,
+    #[serde(rename = "enum", default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Allowed values")]
+    pub allowed_values: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    Number,
+    Integer,
+    String,
+    Boolean,
+    Object,
+    Array,
+}
+
+impl ParameterCfg {
+    // Check that the value conforms to the declaration
+    pub fn check(&self, value: &serde_json::Value) -> Result<(), String> {
+        let type_matches = match &self.value_type {
+            None => true,
+            Some(ParameterType::Number) => value.is_number(),
+            Some(ParameterType::Integer) => value.is_i64() || value.is_u64(),
+            Some(ParameterType::String) => value.is_string(),
+            Some(ParameterType::Boolean) => value.is_boolean(),
+            Some(ParameterType::Object) => value.is_object(),
+            Some(ParameterType::Array) => value.is_array(),
+        };
+        if !type_matches {
+            return Err(format!("expected {:?}, got {}", self.value_type.as_ref().unwrap(), value));
+        }
+
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = self.minimum
+                && number < minimum
+            {
+                return Err(format!("{} is less than minimum {}", number, minimum));
+            }
+            if let Some(maximum) = self.maximum
+                && number > maximum
+            {
+                return Err(format!("{} is greater than maximum {}", number, maximum));
+            }
+        }
+
+        if let Some(allowed_values) = &self.allowed_values
+            && !allowed_values.contains(value)
+        {
+            return Err(format!("{} is not one of the allowed values", value));
+        }
+
+        Ok(())
+    }
+}
+
+impl ScriptCfg {
+    // Check the code in isolation with default parameters and given globals
+    pub fn validate_code(&self, globals: &JSONObject) -> Vec<ScriptDiagnostic> {
+        validate_script(&self.script, &self.resolve_parameters(&JSONObject::new()), globals)
+    }
+
+    // Fail with all diagnostics of the code if any of them is an error
+    pub fn check_code(&self, globals: &JSONObject) -> Result<(), CoreError> {
+        let diagnostics = self.validate_code(globals);
+        if diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error) {
+            return Err(CoreError::ScriptValidation {
+                script_id: self.id.clone(),
+                diagnostics,
+            });
+        }
+        Ok(())
+    }
+
+    // Check that defaults of the parameters conform to their declarations
+    pub fn validate_defaults(&self) -> Result<(), CoreError> {
+        for (name, parameter) in &self.parameters {
+            if !parameter.default.is_null() {
+                parameter.check(&parameter.default).map_err(|e| {
+                    CoreError::DeserializationError(format!("Invalid default of parameter '{}' of script '{}': {}", name, self.id, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Check that overrides reference declared parameters and conform to their declarations
+    pub fn validate_parameters(&self, overrides: &JSONObject) -> Result<(), CoreError> {
+        for (name, value) in overrides {
+            let parameter = self.parameters.get(name).ok_or_else(|| {
+                CoreError::DeserializationError(format!("Script '{}' has no parameter '{}'", self.id, name))
+            })?;
+
+            parameter.check(value).map_err(|e| {
+                CoreError::DeserializationError(format!("Invalid value of parameter '{}' of script '{}': {}", name, self.id, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    // Defaults of the parameters merged with the overrides
+    pub fn resolve_parameters(&self, overrides: &JSONObject) -> JSONObject {
+        let mut parameters: JSONObject = self
+            .parameters
+            .iter()
+            .filter(|(_, parameter)| !parameter.default.is_null())
+            .map(|(name, parameter)| (name.clone(), parameter.default.clone()))
+            .collect();
+
+        for (name, value) in overrides {
+            parameters.insert(name.clone(), value.clone());
+        }
+        parameters
+    }
+}
+
+impl GridCfg {
+    // Check dimensions and fields of the grid. Returns number of cells.
+    pub fn validate(&self) -> Result<usize, CoreError> {
+        let cells_count = (self.width as usize).saturating_mul(self.height as usize);
+        if cells_count == 0 || cells_count > MAX_GRID_CELLS {
+            return Err(CoreError::DeserializationError(format!(
+                "Grid width and height must be greater than 0, with at most {} cells in total",
+                MAX_GRID_CELLS
+            )));
+        }
+
+        for (name, field) in &self.fields {
+            if !(0.0..=1.0).contains(&field.diffusion) || !(0.0..=1.0).contains(&field.decay) {
+                return Err(CoreError::DeserializationError(format!("Grid field '{}' must have diffusion and decay between 0 and 1", name)));
+            }
+
+            if let Some(values) = &field.values
+                && values.len() != cells_count
+            {
+                return Err(CoreError::DeserializationError(format!(
+                    "Grid field '{}' has {} values, expected {}",
+                    name,
+                    values.len(),
+                    cells_count
+                )));
+            }
+        }
+
+        Ok(cells_count)
+    }
+}
+
+impl PopulationCfg {
+    // IDs of entities of the population created with the world
+    pub fn entity_ids(&self) -> Vec<String> {
+        (0..self.count)
+            .map(|i| self.id_pattern.replace("{i}", &i.to_string()))
+            .collect()
+    }
 }
 
 impl WorldCfg {
@@ -42,41 +544,56 @@ impl WorldCfg {
             name,
             script_library: HashMap::new(),
             entities: Vec::new(),
+            network: None,
+            grid: None,
+            space: None,
+            message_only: false,
+            director: None,
+            globals: JSONObject::new(),
+            events: Vec::new(),
+            populations: Vec::new(),
+            seed: None,
+            limits: LimitsCfg::default(),
         }
     }
 
+    #[cfg(test)]
     pub fn add_script(&mut self, id: String, script: String) {
-        self.script_library.insert(id.clone(), ScriptCfg { id, kind: "lua".to_string(), script: script });
-    }
-
-    pub fn add_entity(&mut self, id: String, script_id: String) -> Result<(), CoreError> {
-        // Is script defined?
-        if !self.script_library.contains_key(&script_id) {
-            return Err(CoreError::DeserializationError(format!("Script ID '{}' not found in script library", script_id)));
-        }
-
-        self.entities.push(EntityCfg { id, script_id, initial_state: None });
-        Ok(())
+        self.script_library.insert(id.clone(), ScriptCfg { id, kind: "lua".to_string(), script, parameters: BTreeMap::new() });
     }
 
     // Update or insert entity
-    pub fn upsert_entity(&mut self, id: &String, script_id: &String, initial_state: Option<JSONObject>) -> Result<(), CoreError> {
+    pub fn upsert_entity(&mut self, entity: EntityCfg) -> Result<(), CoreError> {
         // Is script defined?
-        if !self.script_library.contains_key(script_id) {
-            return Err(CoreError::DeserializationError(format!("Script ID '{}' not found in script library", script_id)));
+        if !self.script_library.contains_key(&entity.script_id) {
+            return Err(CoreError::ScriptNotFound { script_id: entity.script_id.clone() });
         }
 
-        if let Some(entity_cfg) = self.entities.iter_mut().find(|e| e.id.eq(id)) {
-            entity_cfg.script_id = script_id.clone();
-            entity_cfg.initial_state = initial_state;
+        if let Some(entity_cfg) = self.entities.iter_mut().find(|e| e.id.eq(&entity.id)) {
+            *entity_cfg = entity;
         } else {
-            self.entities.push(EntityCfg { id: id.clone(), script_id: script_id.clone(), initial_state: initial_state });
+            self.entities.push(entity);
         }
 
         Ok(())
     }
 
+    // Check the configuration, including a dry run of every script
     pub fn validate(&self) -> Result<(), CoreError> {
+        self.check_scripts()?;
+        self.validate_settings()
+    }
+
+    // Dry run of every script, fails with the diagnostics of the first script with errors
+    pub fn check_scripts(&self) -> Result<(), CoreError> {
+        for script in self.script_library.values() {
+            script.check_code(&self.globals)?;
+        }
+        Ok(())
+    }
+
+    // Checks of the configuration that do not run scripts
+    pub fn validate_settings(&self) -> Result<(), CoreError> {
         let mut script_ids = std::collections::HashSet::new();
         for id in self.script_library.keys() {
             if !script_ids.insert(id) {
@@ -85,14 +602,96 @@ impl WorldCfg {
         }
 
         for entity in &self.entities {
+            check_entity_id(&entity.id)?;
             if !script_ids.contains(&entity.script_id) {
                 return Err(CoreError::DeserializationError(format!("Entity '{}' references undefined script ID: {}", entity.id, entity.script_id)));
             }
         }
 
+        for script in self.script_library.values() {
+            script.validate_defaults()?;
+        }
+
+        for entity in &self.entities {
+            self.script_library[&entity.script_id].validate_parameters(&entity.parameters)?;
+        }
+
+        // Checked before IDs of populations are generated, counts can be arbitrarily large
+        let total_entities = self.populations.iter().fold(self.entities.len(), |total, population| total.saturating_add(population.count));
+        if total_entities > self.limits.max_entities {
+            return Err(CoreError::QuotaExceeded {
+                quota: "max_entities".to_string(),
+                limit: self.limits.max_entities,
+            });
+        }
+
+        let mut entity_ids: std::collections::HashSet<String> = self.entities.iter().map(|e| e.id.clone()).collect();
+        for population in &self.populations {
+            if !script_ids.contains(&population.script_id) {
+                return Err(CoreError::DeserializationError(format!("Population '{}' references undefined script ID: {}", population.id_pattern, population.script_id)));
+            }
+
+            if population.count > 1 && !population.id_pattern.contains("{i}") {
+                return Err(CoreError::DeserializationError(format!("Population '{}' has {} entities but its id_pattern has no '{{i}}'", population.id_pattern, population.count)));
+            }
+
+            self.script_library[&population.script_id].validate_parameters(&population.parameters)?;
+
+            for id in population.entity_ids() {
+                check_entity_id(&id)?;
+                if !entity_ids.insert(id.clone()) {
+                    return Err(CoreError::DeserializationError(format!("Population '{}' generates duplicate entity ID: {}", population.id_pattern, id)));
+                }
+            }
+        }
+
+        if let Some(grid) = &self.grid {
+            grid.validate()?;
+        }
+
+        for entity in &self.entities {
+            entity.validate_placement(self.grid.as_ref())?;
+        }
+
+        if let Some(space) = &self.space {
+            let positive = |value: f64| value.is_finite() && value > 0.0;
+            if !positive(space.width) || !positive(space.height) {
+                return Err(CoreError::DeserializationError("Space width and height must be finite and greater than 0".to_string()));
+            }
+            if space.cell_size.is_some_and(|cell_size| !positive(cell_size)) {
+                return Err(CoreError::DeserializationError("Space cell_size must be finite and greater than 0".to_string()));
+            }
+        }
+
+        for event in &self.events {
+            match &event.action {
+                EventActionCfg::SendMessage { entity_id, tag, .. } if entity_id.is_some() == tag.is_some() => {
+                    return Err(CoreError::DeserializationError(format!("Event at step {} must send message either to entity_id or to tag", event.step)));
+                }
+                EventActionCfg::Spawn { script_id, .. } if !script_ids.contains(script_id) => {
+                    return Err(CoreError::DeserializationError(format!("Event at step {} references undefined script ID: {}", event.step, script_id)));
+                }
+                EventActionCfg::Spawn { count, id_pattern, .. } if *count > 1 && !id_pattern.contains("{i}") => {
+                    return Err(CoreError::DeserializationError(format!("Event at step {} spawns {} entities but id_pattern '{}' has no '{{i}}'", event.step, count, id_pattern)));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(network) = &self.network {
+            for (from, to) in &network.edges {
+                for id in [from, to] {
+                    if !entity_ids.contains(id) {
+                        return Err(CoreError::DeserializationError(format!("Network edge ({}, {}) references undefined entity: {}", from, to, id)));
+                    }
+                }
+            }
+        }
+
         Ok(())
     }
 
+    // Read a configuration without validating it, it is validated when a world is created from it
     pub fn from_yaml_file(path: &str) -> Result<Self, CoreError> {
         let config_data = std::fs::read_to_string(path)
             .map_err(|e| CoreError::DeserializationError(format!("Failed to read world config file: {}", e)))?;
@@ -100,10 +699,10 @@ impl WorldCfg {
         let cfg : WorldCfg = serde_yaml::from_str(&config_data)
             .map_err(|e| CoreError::DeserializationError(format!("Failed to parse world config YAML: {}", e)))?;
 
-        cfg.validate()?;
         Ok(cfg)
     }
 
+    // Read a configuration without validating it, as from_yaml_file
     pub fn from_json_file(path: &str) -> Result<Self, CoreError> {
         let config_data = std::fs::read_to_string(path)
             .map_err(|e| CoreError::DeserializationError(format!("Failed to read world config file: {}", e)))?;
@@ -111,7 +710,6 @@ impl WorldCfg {
         let cfg : WorldCfg = serde_json::from_str(&config_data)
             .map_err(|e| CoreError::DeserializationError(format!("Failed to parse world config JSON: {}", e)))?;
 
-        cfg.validate()?;
         Ok(cfg)
     }
 }
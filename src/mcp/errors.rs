@@ -1,22 +1,36 @@
-use std::fmt::Debug;
-
 use crate::core::errors::CoreError;
 use rmcp::model::ErrorData as McpError;
 
 
 impl From<CoreError> for McpError {
     fn from(err: CoreError) -> Self {
+        // Failures of individual entities are also returned as structured data
+        let data = match &err {
+            CoreError::ScriptMigration { failures, .. } => Some(serde_json::json!({ "failures": failures })),
+            CoreError::ScriptValidation { diagnostics, .. } => Some(serde_json::json!({ "diagnostics": diagnostics })),
+            _ => None,
+        };
+
         let code = match err {
             CoreError::WorldNotFound { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
             CoreError::EntityNotFound {.. } => rmcp::model::ErrorCode::INVALID_PARAMS,
-            CoreError::WorldAlreadyExists {.. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::WorldAlreadyExists => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::QuotaExceeded { .. } => rmcp::model::ErrorCode::INVALID_REQUEST,
+            CoreError::TimeOverflow { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::ScriptNotFound { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::ScriptAlreadyExists { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::ScriptInUse { .. } => rmcp::model::ErrorCode::INVALID_REQUEST,
+            CoreError::ScriptValidation { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::ScriptMigration { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::JobNotFound { .. } => rmcp::model::ErrorCode::INVALID_PARAMS,
+            CoreError::InvalidJobState { .. } => rmcp::model::ErrorCode::INVALID_REQUEST,
             _ => rmcp::model::ErrorCode::INTERNAL_ERROR,
         };
 
         McpError::new(
             code,
             format!("Core error: {}", err),
-            None,
+            data,
         )
     }
 }
\ No newline at end of file
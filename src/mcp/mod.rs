@@ -1,3 +1,4 @@
+mod progress;
 mod server;
 mod tools;
 mod errors;
@@ -0,0 +1,145 @@
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+use rmcp::service::RequestContext;
+use rmcp::RoleServer;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// Minimum time between two progress notifications of a request
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+// Sends progress notifications of a long running tool call, if the client asked for them with a progress token.
+// Steps run on a blocking or a world thread, a single task sends the notifications in the order of the steps.
+pub struct ProgressReporter {
+    token: Option<ProgressToken>,
+    interval: Duration,
+    last_report: Mutex<Option<Instant>>,
+    sender: Mutex<Option<mpsc::UnboundedSender<ProgressNotificationParam>>>,
+    delivery: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ProgressReporter {
+    pub fn new(context: &RequestContext<RoleServer>) -> Self {
+        let peer = context.peer.clone();
+        Self::start(context.meta.get_progress_token(), PROGRESS_INTERVAL, move |param| {
+            let peer = peer.clone();
+            async move {
+                let _ = peer.notify_progress(param).await;
+            }
+        })
+    }
+
+    // Start the task sending notifications with notify, one at a time
+    fn start<F, Fut>(token: Option<ProgressToken>, interval: Duration, notify: F) -> Self
+    where
+        F: Fn(ProgressNotificationParam) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, delivery) = match token {
+            Some(_) => {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                (Some(sender), Some(tokio::spawn(Self::deliver(notify, receiver))))
+            }
+            None => (None, None),
+        };
+
+        ProgressReporter {
+            token,
+            interval,
+            last_report: Mutex::new(None),
+            sender: Mutex::new(sender),
+            delivery: Mutex::new(delivery),
+        }
+    }
+
+    async fn deliver<F, Fut>(notify: F, mut receiver: mpsc::UnboundedReceiver<ProgressNotificationParam>)
+    where
+        F: Fn(ProgressNotificationParam) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        while let Some(param) = receiver.recv().await {
+            notify(param).await;
+        }
+    }
+
+    // Wait until all reported steps are sent, so that they reach the client before the result of the tool call
+    pub async fn finish(&self) {
+        self.sender.lock().unwrap().take();
+        let delivery = self.delivery.lock().unwrap().take();
+        if let Some(delivery) = delivery {
+            let _ = delivery.await;
+        }
+    }
+
+    // Report the step, notifications are throttled except for the last step
+    pub fn report_step(&self, step: u32, total_steps: u32, simulation_time: u64) {
+        let sender = self.sender.lock().unwrap();
+        let (Some(token), Some(sender)) = (&self.token, sender.as_ref()) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let mut last_report = self.last_report.lock().unwrap();
+        let is_due = last_report.is_none_or(|last| now.duration_since(last) >= self.interval);
+        if !is_due && step < total_steps {
+            return;
+        }
+        *last_report = Some(now);
+
+        let param = ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress: step as f64,
+            total: Some(total_steps as f64),
+            message: Some(format!("Step {}/{}, simulation time {}", step, total_steps, simulation_time)),
+        };
+        let _ = sender.send(param);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::NumberOrString;
+    use std::sync::{Arc, Mutex};
+
+    // Report steps from a blocking thread as the tools do, returns the progress of the sent notifications
+    async fn report_steps(token: Option<ProgressToken>, interval: Duration, total_steps: u32) -> Vec<f64> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sent_clone = sent.clone();
+        let progress = Arc::new(ProgressReporter::start(token, interval, move |param| {
+            let sent = sent_clone.clone();
+            async move {
+                tokio::task::yield_now().await;
+                sent.lock().unwrap().push(param.progress);
+            }
+        }));
+
+        let step_progress = progress.clone();
+        tokio::task::spawn_blocking(move || {
+            for step in 1..=total_steps {
+                step_progress.report_step(step, total_steps, step as u64);
+            }
+        })
+        .await
+        .unwrap();
+        progress.finish().await;
+
+        Arc::try_unwrap(sent).unwrap().into_inner().unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_report_steps() {
+        let token = Some(ProgressToken(NumberOrString::Number(1)));
+
+        // All notifications are sent before finish returns, in the order of the steps
+        let expected: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(report_steps(token.clone(), Duration::ZERO, 100).await, expected);
+
+        // Throttled steps are skipped, the last step is always reported
+        assert_eq!(report_steps(token, PROGRESS_INTERVAL, 100).await, vec![1.0, 100.0]);
+
+        // Nothing is sent without a progress token
+        assert!(report_steps(None, Duration::ZERO, 100).await.is_empty());
+    }
+}
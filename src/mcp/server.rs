@@ -1,16 +1,23 @@
+use crate::mcp::progress::ProgressReporter;
 use crate::mcp::tools::world;
 use rmcp::{
-    ErrorData as McpError, ServerHandler,
+    ErrorData as McpError, RoleServer, ServerHandler,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
     model::{ServerCapabilities, ServerInfo},
+    service::RequestContext,
     tool, tool_handler, tool_router,
 };
+use std::ops::ControlFlow;
+use std::sync::Arc;
 
 const SERVER_INSTRUCTIONS: &str = include_str!("../../docs/mcp/instructions.md");
 
+// Sessions of the HTTP transport share the registry and jobs, each session has its own clone of the server
+#[derive(Clone)]
 pub struct VivariumToolServer {
     pub tool_router: ToolRouter<Self>,
-    world_registry: crate::core::registry::Registry,
+    world_registry: Arc<crate::core::registry::Registry>,
+    job_manager: Arc<crate::core::jobs::JobManager>,
 }
 
 #[tool_router]
@@ -20,7 +27,8 @@ impl VivariumToolServer {
 
         VivariumToolServer {
             tool_router,
-            world_registry,
+            world_registry: Arc::new(world_registry),
+            job_manager: Arc::new(crate::core::jobs::JobManager::new()),
         }
     }
 
@@ -30,11 +38,8 @@ impl VivariumToolServer {
         Parameters(config): Parameters<crate::core::world_config::WorldCfg>,
     ) -> Result<rmcp::Json<world::CreateWorldResponse>, McpError> {
         self.world_registry.create(config).map_err(|e| {
-            McpError::new(
-                rmcp::model::ErrorCode::INTERNAL_ERROR,
-                format!("Failed to create world: {}", e),
-                None,
-            )
+            let message = format!("Failed to create world: {}", e);
+            McpError::new(rmcp::model::ErrorCode::INTERNAL_ERROR, message, McpError::from(e).data)
         })?;
 
         Ok(rmcp::Json(world::CreateWorldResponse {
@@ -83,13 +88,92 @@ impl VivariumToolServer {
     }
 
     #[tool(
-        description = "Advance the simulation by running multiple time steps. Each step processes pending messages and executes entity update() functions. Use step_duration to control simulation time granularity."
+        description = "Advance the simulation by running multiple time steps. Each step processes pending messages and executes entity update() functions. Use step_duration to control simulation time granularity. Reports progress if requested and returns the steps run so far when cancelled."
     )]
-    fn advance_simulation(
+    async fn advance_simulation(
         &self,
         Parameters(request): Parameters<world::RunSimulationRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<rmcp::Json<world::AdvanceSimulationResponse>, McpError> {
-        world::advance_simulation(&self.world_registry, Parameters(request))
+        let num_steps = request.num_steps;
+        let progress = ProgressReporter::new(&context);
+        let (result, progress) = self.run_blocking(move |server| {
+            let result = world::advance_simulation(&server.world_registry, Parameters(request), |step, simulation_time| {
+                progress.report_step(step, num_steps, simulation_time);
+                Self::check_cancelled(&context)
+            });
+            (result, progress)
+        })
+        .await?;
+
+        progress.finish().await;
+        result
+    }
+
+    #[tool(
+        description = "Advance the simulation until a condition holds or max_steps is reached. Conditions compare a metric, a value in an entity state selected by a JSONPath or the number of entities, wait for no pending messages, or evaluate a Lua predicate over the world. Reports the number of steps and why the run stopped. Reports progress if requested and can be cancelled."
+    )]
+    async fn run_until(
+        &self,
+        Parameters(request): Parameters<world::RunUntilRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<rmcp::Json<world::RunUntilResponse>, McpError> {
+        let max_steps = request.max_steps;
+        // The run happens on the thread of the world, which reports the steps
+        let progress = Arc::new(ProgressReporter::new(&context));
+        let step_progress = progress.clone();
+        let result = self.run_blocking(move |server| {
+            world::run_until(&server.world_registry, request, move |step, simulation_time| {
+                step_progress.report_step(step, max_steps, simulation_time);
+                Self::check_cancelled(&context)
+            })
+        })
+        .await?;
+
+        progress.finish().await;
+        result
+    }
+
+    #[tool(
+        description = "Start running a simulation world in the background and return a job ID. Runs the given number of steps, optionally until a stop condition holds. Use get_run_status to poll the job."
+    )]
+    fn start_run(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::runs::StartRunRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::runs::StartRunResponse>, McpError> {
+        crate::mcp::tools::runs::start_run(&self.world_registry, &self.job_manager, request)
+    }
+
+    #[tool(description = "Get the state and progress of a background run.")]
+    fn get_run_status(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::runs::RunJobRequest>,
+    ) -> Result<rmcp::Json<crate::core::jobs::JobStatus>, McpError> {
+        crate::mcp::tools::runs::get_run_status(&self.job_manager, request)
+    }
+
+    #[tool(description = "Pause a running background run before its next step.")]
+    fn pause_run(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::runs::RunJobRequest>,
+    ) -> Result<rmcp::Json<crate::core::jobs::JobStatus>, McpError> {
+        crate::mcp::tools::runs::pause_run(&self.job_manager, request)
+    }
+
+    #[tool(description = "Resume a paused background run.")]
+    fn resume_run(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::runs::RunJobRequest>,
+    ) -> Result<rmcp::Json<crate::core::jobs::JobStatus>, McpError> {
+        crate::mcp::tools::runs::resume_run(&self.job_manager, request)
+    }
+
+    #[tool(description = "Cancel a running or paused background run before its next step. Steps already run are kept.")]
+    fn cancel_run(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::runs::RunJobRequest>,
+    ) -> Result<rmcp::Json<crate::core::jobs::JobStatus>, McpError> {
+        crate::mcp::tools::runs::cancel_run(&self.job_manager, request)
     }
 
     #[tool(description = "List the names of all available metrics in the simulation world.")]
@@ -126,6 +210,126 @@ impl VivariumToolServer {
         world::set_entity_state(&self.world_registry, request)
     }
 
+    #[tool(
+        description = "Set global variables of the world shared by all entities (e.g. prices, weather, policy levels). Changes apply immediately, null values remove variables."
+    )]
+    pub fn set_globals(
+        &self,
+        Parameters(request): Parameters<world::SetGlobalsRequest>,
+    ) -> Result<rmcp::Json<world::SetGlobalsResponse>, McpError> {
+        world::set_globals(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Send a message into the world as an external actor. The receiver is an entity, a tag, a topic, network neighbors of an entity or a radius in continuous space. The message is delivered after the delay, during the next simulation step at the earliest."
+    )]
+    pub fn send_message(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::messages::SendMessageRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::messages::SendMessageResponse>, McpError> {
+        crate::mcp::tools::messages::send_message(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Spawn a new entity in the world. Its script must be in the script library of the world, the ID must be unused. Returns the ID of the created entity."
+    )]
+    pub fn spawn_entity(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::entities::SpawnEntityRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::entities::SpawnEntitiesResponse>, McpError> {
+        crate::mcp::tools::entities::spawn_entity(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Spawn multiple entities in the world at once. Either all entities are created or none of them. Returns IDs of the created entities."
+    )]
+    pub fn spawn_entities(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::entities::SpawnEntitiesRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::entities::SpawnEntitiesResponse>, McpError> {
+        crate::mcp::tools::entities::spawn_entities(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Remove an entity from the world, including its subscriptions, network edges and position. Pending messages addressed to it are dropped on delivery."
+    )]
+    pub fn remove_entity(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::entities::RemoveEntityRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::entities::RemoveEntityResponse>, McpError> {
+        crate::mcp::tools::entities::remove_entity(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Switch an entity to another script of the script library, e.g. for life stages or role changes. ID, tags, subscriptions and unprocessed messages are kept. The optional state is passed to set_state of the new script."
+    )]
+    pub fn change_entity_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::entities::ChangeEntityScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::entities::ChangeEntityScriptResponse>, McpError> {
+        crate::mcp::tools::entities::change_entity_script(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Spawn a population of entities from a template. '{i}' in the ID pattern is replaced by the lowest unused indices. Initial state values can be generated (constant, uniform, normal, choice). Returns IDs of the created entities."
+    )]
+    pub fn spawn_population(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::entities::SpawnPopulationRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::entities::SpawnPopulationResponse>, McpError> {
+        crate::mcp::tools::entities::spawn_population(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Describe a script of the world: its tunable parameters with types, defaults and descriptions, IDs of entities using it and its source."
+    )]
+    pub fn describe_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::scripts::DescribeScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::scripts::DescribeScriptResponse>, McpError> {
+        crate::mcp::tools::scripts::describe_script(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Check a Lua script before using it: compiles it in a sandbox, checks that update, get_state and set_state exist, round-trips get_state/set_state and runs update once without messages in an empty world. Returns diagnostics with line, column and message."
+    )]
+    pub fn validate_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::scripts::ValidateScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::scripts::ValidateScriptResponse>, McpError> {
+        crate::mcp::tools::scripts::validate_script(request)
+    }
+
+    #[tool(
+        description = "Add a script to the script library of a running world. The script must compile and define update, get_state and set_state. Entities can then be spawned with it."
+    )]
+    pub fn add_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::scripts::AddScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::scripts::AddScriptResponse>, McpError> {
+        crate::mcp::tools::scripts::add_script(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Replace a script of a running world. State of every entity using it is migrated with get_state of the old version and set_state of the new one. If any entity fails to migrate, nothing is changed and failures are reported per entity."
+    )]
+    pub fn update_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::scripts::UpdateScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::scripts::UpdateScriptResponse>, McpError> {
+        crate::mcp::tools::scripts::update_script(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Remove a script from the script library. Fails if entities or pending timeline events still use it."
+    )]
+    pub fn remove_script(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::scripts::RemoveScriptRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::scripts::RemoveScriptResponse>, McpError> {
+        crate::mcp::tools::scripts::remove_script(&self.world_registry, request)
+    }
+
     #[tool(description = "Get the current state of a specific entity by its ID.")]
     pub fn get_entity_state(
         &self,
@@ -135,7 +339,17 @@ impl VivariumToolServer {
     }
 
     #[tool(
-        description = "Get the overall state of the simulation world, including simulation time, entity count, and pending message count."
+        description = "Get values of numeric fields of grid cells (e.g. grass, pheromone) and the number of entities in each cell. Only available for worlds with a grid."
+    )]
+    pub fn get_grid_fields(
+        &self,
+        Parameters(request): Parameters<crate::mcp::tools::grid::GetGridFieldsRequest>,
+    ) -> Result<rmcp::Json<crate::mcp::tools::grid::GetGridFieldsResponse>, McpError> {
+        crate::mcp::tools::grid::get_grid_fields(&self.world_registry, request)
+    }
+
+    #[tool(
+        description = "Get the overall state of the simulation world, including simulation time, entity count, pending message count and global variables."
     )]
     pub fn get_world_state(
         &self,
@@ -179,6 +393,24 @@ impl VivariumToolServer {
     }
 }
 
+impl VivariumToolServer {
+    // Simulation runs take long, run them on a blocking thread so that the async workers keep serving other requests
+    async fn run_blocking<R: Send + 'static>(&self, run: impl FnOnce(&Self) -> R + Send + 'static) -> Result<R, McpError> {
+        let server = self.clone();
+        tokio::task::spawn_blocking(move || run(&server))
+            .await
+            .map_err(|e| McpError::internal_error(format!("Simulation run failed: {}", e), None))
+    }
+
+    // Runs stop after the current step once the client cancels the request
+    fn check_cancelled(context: &RequestContext<RoleServer>) -> ControlFlow<()> {
+        match context.ct.is_cancelled() {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    }
+}
+
 #[tool_handler]
 impl ServerHandler for VivariumToolServer {
     fn get_info(&self) -> ServerInfo {
@@ -189,6 +421,3 @@ impl ServerHandler for VivariumToolServer {
         }
     }
 }
-
-unsafe impl Send for VivariumToolServer {}
-unsafe impl Sync for VivariumToolServer {}
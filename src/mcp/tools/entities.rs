@@ -0,0 +1,156 @@
+use crate::core::errors::CoreError;
+use crate::core::messaging::JSONObject;
+use crate::core::world_config::{EntityCfg, PopulationCfg};
+use rmcp::Json;
+use rmcp::{ErrorData as McpError, schemars};
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SpawnPopulationRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "Template of the population to spawn")]
+    pub population: PopulationCfg,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SpawnEntityRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "Configuration of the entity, its script must be in the script library of the world")]
+    pub entity: EntityCfg,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SpawnEntitiesRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "Configurations of the entities, either all of them are created or none")]
+    pub entities: Vec<EntityCfg>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RemoveEntityRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The unique ID of the entity to remove")]
+    pub entity_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ChangeEntityScriptRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The unique ID of the entity")]
+    pub entity_id: String,
+    #[schemars(description = "The ID of the new script in the script library")]
+    pub script_id: String,
+    #[serde(default)]
+    #[schemars(description = "Optional state passed to set_state of the new script, otherwise the script starts with its own initial state")]
+    pub state: Option<JSONObject>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct ChangeEntityScriptResponse {
+    #[schemars(description = "State of the entity with the new script")]
+    pub state: JSONObject,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct SpawnEntitiesResponse {
+    #[schemars(description = "IDs of the created entities")]
+    pub entity_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Failures of director hooks notified of the change and of the commands they issued, the change applies nonetheless")]
+    pub command_errors: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct RemoveEntityResponse {
+    #[schemars(description = "Success message")]
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Failures of director hooks notified of the change and of the commands they issued, the change applies nonetheless")]
+    pub command_errors: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct SpawnPopulationResponse {
+    #[schemars(description = "IDs of the created entities")]
+    pub entity_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Failures of director hooks notified of the change and of the commands they issued, the change applies nonetheless")]
+    pub command_errors: Vec<String>,
+}
+
+pub fn spawn_population(
+    registry: &crate::core::registry::Registry,
+    request: SpawnPopulationRequest,
+) -> Result<Json<SpawnPopulationResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    world.with(move |world| -> Result<_, McpError> {
+        let entity_ids = world.spawn_population(&request.population)?;
+        let command_errors = world.apply_director_commands();
+
+        Ok(Json(SpawnPopulationResponse { entity_ids, command_errors }))
+    })?
+}
+
+pub fn spawn_entity(
+    registry: &crate::core::registry::Registry,
+    request: SpawnEntityRequest,
+) -> Result<Json<SpawnEntitiesResponse>, McpError> {
+    spawn_entities(
+        registry,
+        SpawnEntitiesRequest {
+            world_name: request.world_name,
+            entities: vec![request.entity],
+        },
+    )
+}
+
+pub fn spawn_entities(
+    registry: &crate::core::registry::Registry,
+    request: SpawnEntitiesRequest,
+) -> Result<Json<SpawnEntitiesResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    world.with(move |world| -> Result<_, McpError> {
+        let entity_ids = world.spawn_entities(request.entities)?;
+        let command_errors = world.apply_director_commands();
+
+        Ok(Json(SpawnEntitiesResponse { entity_ids, command_errors }))
+    })?
+}
+
+pub fn remove_entity(
+    registry: &crate::core::registry::Registry,
+    request: RemoveEntityRequest,
+) -> Result<Json<RemoveEntityResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    world.with(move |world| -> Result<_, McpError> {
+        if world.remove_entity(&request.entity_id).is_none() {
+            return Err(CoreError::EntityNotFound { id: request.entity_id }.into());
+        }
+
+        Ok(Json(RemoveEntityResponse {
+            message: format!("Entity '{}' removed", request.entity_id),
+            command_errors: world.apply_director_commands(),
+        }))
+    })?
+}
+
+pub fn change_entity_script(
+    registry: &crate::core::registry::Registry,
+    request: ChangeEntityScriptRequest,
+) -> Result<Json<ChangeEntityScriptResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    world.with(move |world| -> Result<_, McpError> {
+        world.change_entity_script(&request.entity_id, &request.script_id, request.state)?;
+
+        Ok(Json(ChangeEntityScriptResponse {
+            state: world.get_entity_state(&request.entity_id)?,
+        }))
+    })?
+}
@@ -0,0 +1,77 @@
+use rmcp::Json;
+use rmcp::{ErrorData as McpError, schemars};
+use std::collections::BTreeMap;
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct GetGridFieldsRequest {
+    #[schemars(description = "The name of the simulation world to query")]
+    pub world_name: String,
+    #[serde(default)]
+    #[schemars(description = "Names of the fields to retrieve. All fields are returned if empty.")]
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct GetGridFieldsResponse {
+    #[schemars(description = "Number of cells along the x axis")]
+    pub width: u32,
+    #[schemars(description = "Number of cells along the y axis")]
+    pub height: u32,
+    #[schemars(description = "Values of the fields as rows of cells (values[y][x]). The key is the field name.")]
+    pub fields: BTreeMap<String, Vec<Vec<f64>>>,
+    #[schemars(description = "Number of entities occupying each cell as rows of cells (occupants[y][x])")]
+    pub occupants: Vec<Vec<usize>>,
+}
+
+pub fn get_grid_fields(
+    registry: &crate::core::registry::Registry,
+    request: GetGridFieldsRequest,
+) -> Result<Json<GetGridFieldsResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    world.with(move |world| -> Result<_, McpError> {
+        let state = world.get_state_ref();
+
+        let grid = state.get_grid().ok_or_else(|| {
+            McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("World '{}' has no grid", request.world_name),
+                None,
+            )
+        })?;
+
+        let field_names = if request.fields.is_empty() {
+            grid.list_field_names()
+        } else {
+            request.fields
+        };
+
+        let width = grid.get_width() as usize;
+        let mut fields = BTreeMap::new();
+        for name in field_names {
+            let values = grid.get_field(&name).ok_or_else(|| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INVALID_PARAMS,
+                    format!("Grid field '{}' not found in world '{}'", name, request.world_name),
+                    None,
+                )
+            })?;
+
+            fields.insert(name, values.chunks(width).map(|row| row.to_vec()).collect());
+        }
+
+        let occupants = (0..grid.get_height())
+            .map(|y| {
+                (0..grid.get_width())
+                    .map(|x| grid.get_occupants((x, y)).len())
+                    .collect()
+            })
+            .collect();
+
+        Ok(Json(GetGridFieldsResponse {
+            width: grid.get_width(),
+            height: grid.get_height(),
+            fields,
+            occupants,
+        }))
+    })?
+}
@@ -0,0 +1,189 @@
+use crate::core::messaging::{JSONObject, Message, MessageReceiver};
+use rmcp::Json;
+use rmcp::{ErrorData as McpError, schemars};
+
+// Number of delivered messages returned if the request does not limit them
+pub const DEFAULT_MAX_MESSAGES: usize = 100;
+
+fn default_sender() -> String {
+    "external".to_string()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DeliveredMessage {
+    #[schemars(description = "ID of the message, unique within the world")]
+    pub id: u64,
+    #[schemars(description = "ID of the sending entity, or 'director', 'timeline' or the label of an external sender")]
+    pub sender: String,
+    #[schemars(description = "Receiver the message was addressed to")]
+    pub receiver: MessageReceiver,
+    #[schemars(description = "Kind of the message")]
+    pub kind: String,
+    #[schemars(description = "Content of the message")]
+    pub content: JSONObject,
+    #[schemars(description = "Simulation time at which the message was sent")]
+    pub sent_step: u64,
+    #[schemars(description = "Simulation time at which the message was due")]
+    pub receive_step: u64,
+    #[schemars(description = "Simulation time of the step that delivered the message")]
+    pub delivered_step: u64,
+}
+
+impl DeliveredMessage {
+    pub fn new(message: Message, delivered_step: u64) -> Self {
+        DeliveredMessage {
+            id: message.id,
+            sender: message.sender,
+            receiver: message.receiver,
+            kind: message.kind,
+            content: message.content,
+            sent_step: message.sent_step,
+            receive_step: message.receive_step,
+            delivered_step,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, schemars::JsonSchema)]
+pub struct MessageFilter {
+    #[serde(default)]
+    #[schemars(description = "Only messages of these kinds")]
+    pub kinds: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(description = "Only messages from these senders")]
+    pub senders: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(description = "Only messages addressed to these entity IDs, tags or topics")]
+    pub receivers: Option<Vec<String>>,
+    #[serde(default)]
+    #[schemars(description = "Only messages delivered at or after this simulation time")]
+    pub from_step: Option<u64>,
+    #[serde(default)]
+    #[schemars(description = "Only messages delivered at or before this simulation time")]
+    pub to_step: Option<u64>,
+}
+
+impl MessageFilter {
+    pub fn matches(&self, message: &DeliveredMessage) -> bool {
+        let receiver = match &message.receiver {
+            MessageReceiver::Entity { id } | MessageReceiver::Neighbors { id } => Some(id),
+            MessageReceiver::Tag { tag } | MessageReceiver::Group { name: tag } => Some(tag),
+            MessageReceiver::Topic { topic } => Some(topic),
+            MessageReceiver::Radius2D { .. } => None,
+        };
+
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(&message.kind))
+            && self.senders.as_ref().is_none_or(|senders| senders.contains(&message.sender))
+            && self
+                .receivers
+                .as_ref()
+                .is_none_or(|receivers| receiver.is_some_and(|receiver| receivers.contains(receiver)))
+            && self.from_step.is_none_or(|step| message.delivered_step >= step)
+            && self.to_step.is_none_or(|step| message.delivered_step <= step)
+    }
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SendMessageRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[serde(default = "default_sender")]
+    #[schemars(description = "Label of the sender seen by receivers, 'external' by default")]
+    pub sender: String,
+    #[schemars(
+        description = "Receiver of the message, e.g. {\"Entity\": {\"id\": \"sheep_1\"}}, {\"Tag\": {\"tag\": \"wolves\"}}, {\"Group\": {\"name\": \"wolves\"}}, {\"Topic\": {\"topic\": \"market\"}}, {\"Neighbors\": {\"id\": \"node_1\"}} or {\"Radius2D\": {\"x\": 0, \"y\": 0, \"radius\": 5}}"
+    )]
+    pub receiver: MessageReceiver,
+    #[schemars(description = "Kind of the message")]
+    pub kind: String,
+    #[serde(default)]
+    #[schemars(description = "Content of the message as a JSON object")]
+    pub content: JSONObject,
+    #[serde(default)]
+    #[schemars(description = "Number of time units after the current simulation time when the message is delivered")]
+    pub delay: u64,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct SendMessageResponse {
+    #[schemars(description = "Simulation time at which the message is delivered")]
+    pub receive_step: u64,
+    #[schemars(description = "Number of messages waiting for delivery")]
+    pub pending_messages: usize,
+}
+
+pub fn send_message(
+    registry: &crate::core::registry::Registry,
+    request: SendMessageRequest,
+) -> Result<Json<SendMessageResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    world.with(move |world| -> Result<_, McpError> {
+        let receive_step = world.send_message(
+            &request.sender,
+            request.receiver,
+            request.kind,
+            request.content,
+            request.delay,
+        )?;
+
+        Ok(Json(SendMessageResponse {
+            receive_step,
+            pending_messages: world.get_pending_messages_count(),
+        }))
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivered(sender: &str, receiver: MessageReceiver, kind: &str, delivered_step: u64) -> DeliveredMessage {
+        DeliveredMessage {
+            id: 1,
+            sender: sender.to_string(),
+            receiver,
+            kind: kind.to_string(),
+            content: JSONObject::new(),
+            sent_step: 0,
+            receive_step: delivered_step,
+            delivered_step,
+        }
+    }
+
+    #[test]
+    fn test_message_filter() {
+        let to_sheep = delivered("wolf_1", MessageReceiver::Entity { id: "sheep_1".to_string() }, "hunt", 3);
+        let to_flock = delivered("director", MessageReceiver::Tag { tag: "flock".to_string() }, "shock", 5);
+        let broadcast = delivered("wolf_1", MessageReceiver::Radius2D { x: 0.0, y: 0.0, radius: 1.0 }, "howl", 7);
+        let matching = |filter: MessageFilter| -> Vec<String> {
+            [&to_sheep, &to_flock, &broadcast]
+                .into_iter()
+                .filter(|message| filter.matches(message))
+                .map(|message| message.kind.clone())
+                .collect()
+        };
+
+        assert_eq!(matching(MessageFilter::default()), vec!["hunt", "shock", "howl"]);
+        assert_eq!(
+            matching(MessageFilter { kinds: Some(vec!["shock".to_string(), "howl".to_string()]), ..Default::default() }),
+            vec!["shock", "howl"]
+        );
+        assert_eq!(matching(MessageFilter { senders: Some(vec!["wolf_1".to_string()]), ..Default::default() }), vec!["hunt", "howl"]);
+
+        // Receivers match entity IDs and tags, broadcasts by radius have no receiver to match
+        assert_eq!(
+            matching(MessageFilter { receivers: Some(vec!["sheep_1".to_string(), "flock".to_string()]), ..Default::default() }),
+            vec!["hunt", "shock"]
+        );
+
+        // Step range is inclusive and applies to the delivery step
+        assert_eq!(matching(MessageFilter { from_step: Some(5), to_step: Some(7), ..Default::default() }), vec!["shock", "howl"]);
+        assert_eq!(matching(MessageFilter { to_step: Some(3), ..Default::default() }), vec!["hunt"]);
+
+        // Criteria are combined
+        assert_eq!(
+            matching(MessageFilter { senders: Some(vec!["wolf_1".to_string()]), from_step: Some(4), ..Default::default() }),
+            vec!["howl"]
+        );
+    }
+}
@@ -34,10 +34,11 @@ pub fn list_metrics(
 ) -> Result<Json<ListMetricsResponse>, McpError> {
     let world = registry.get(&request.world_name)?;
 
-    let world_guard = world.read().unwrap();
-    let metrics = world_guard.get_metrics_ref().list_metric_names();
+    world.with(move |world| -> Result<_, McpError> {
+        let metrics = world.get_metrics_ref().list_metric_names();
 
-    Ok(Json(ListMetricsResponse { metrics }))
+        Ok(Json(ListMetricsResponse { metrics }))
+    })?
 }
 
 pub fn get_metric(
@@ -47,15 +48,16 @@ pub fn get_metric(
 ) -> Result<Json<crate::core::metrics::MetricStats>, McpError> {
     let world = registry.get(&world_name)?;
 
-    let world_guard = world.read().unwrap();
-    let metric_stats = world_guard.get_metrics_ref().compute_metric_stats(&metric_name)
-        .ok_or_else(|| McpError::new(
-            rmcp::model::ErrorCode::INVALID_PARAMS,
-            format!("Metric '{}' not found in world '{}'", metric_name, world_name),
-            None,
-        ))?;
+    world.with(move |world| -> Result<_, McpError> {
+        let metric_stats = world.get_metrics_ref().compute_metric_stats(&metric_name)
+            .ok_or_else(|| McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("Metric '{}' not found in world '{}'", metric_name, world_name),
+                None,
+            ))?;
 
-    Ok(Json(metric_stats))
+        Ok(Json(metric_stats))
+    })?
 }
 
 pub fn get_metrics(
@@ -64,24 +66,25 @@ pub fn get_metrics(
 ) -> Result<Json<GetMetricsResponse>, McpError> {
     let world = registry.get(&request.world_name)?;
 
-    let world_guard = world.read().unwrap();
-    let mut metrics = Vec::new();
+    world.with(move |world| -> Result<_, McpError> {
+        let mut metrics = Vec::new();
 
-    for metric_name in request.metrics {
-        match world_guard.get_metrics_ref().compute_metric_stats(&metric_name) {
-            Some(stats) => metrics.push(stats),
-            None => {
-                return Err(McpError::new(
-                    rmcp::model::ErrorCode::INVALID_PARAMS,
-                    format!(
-                        "Metric '{}' not found in world '{}'",
-                        metric_name, request.world_name
-                    ),
-                    None,
-                ));
+        for metric_name in request.metrics {
+            match world.get_metrics_ref().compute_metric_stats(&metric_name) {
+                Some(stats) => metrics.push(stats),
+                None => {
+                    return Err(McpError::new(
+                        rmcp::model::ErrorCode::INVALID_PARAMS,
+                        format!(
+                            "Metric '{}' not found in world '{}'",
+                            metric_name, request.world_name
+                        ),
+                        None,
+                    ));
+                }
             }
         }
-    }
 
-    Ok(Json(GetMetricsResponse { metrics }))
+        Ok(Json(GetMetricsResponse { metrics }))
+    })?
 }
@@ -1,3 +1,8 @@
+pub mod entities;
+pub mod grid;
+pub mod messages;
 pub mod metrics;
+pub mod runs;
+pub mod scripts;
 pub mod world;
 pub mod snapshots;
\ No newline at end of file
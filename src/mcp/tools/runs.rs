@@ -0,0 +1,51 @@
+use crate::core::jobs::{JobManager, JobStatus, RunOptions};
+use rmcp::ErrorData as McpError;
+use rmcp::Json;
+use rmcp::schemars;
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct StartRunRequest {
+    #[schemars(description = "The name of the simulation world to run")]
+    pub world_name: String,
+    #[serde(flatten)]
+    pub options: RunOptions,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct StartRunResponse {
+    #[schemars(description = "ID of the started job, used to check and control the run")]
+    pub job_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunJobRequest {
+    #[schemars(description = "ID of the job returned by start_run")]
+    pub job_id: String,
+}
+
+pub fn start_run(
+    registry: &crate::core::registry::Registry,
+    jobs: &JobManager,
+    request: StartRunRequest,
+) -> Result<Json<StartRunResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    let job_id = jobs.start(&request.world_name, world, request.options)?;
+
+    Ok(Json(StartRunResponse { job_id }))
+}
+
+pub fn get_run_status(jobs: &JobManager, request: RunJobRequest) -> Result<Json<JobStatus>, McpError> {
+    Ok(Json(jobs.get_status(&request.job_id)?))
+}
+
+pub fn pause_run(jobs: &JobManager, request: RunJobRequest) -> Result<Json<JobStatus>, McpError> {
+    Ok(Json(jobs.pause(&request.job_id)?))
+}
+
+pub fn resume_run(jobs: &JobManager, request: RunJobRequest) -> Result<Json<JobStatus>, McpError> {
+    Ok(Json(jobs.resume(&request.job_id)?))
+}
+
+pub fn cancel_run(jobs: &JobManager, request: RunJobRequest) -> Result<Json<JobStatus>, McpError> {
+    Ok(Json(jobs.cancel(&request.job_id)?))
+}
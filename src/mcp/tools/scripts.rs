@@ -0,0 +1,184 @@
+use crate::core::messaging::JSONObject;
+use crate::core::{DiagnosticSeverity, ScriptDiagnostic};
+use crate::core::world_config::{ParameterCfg, ScriptCfg};
+use rmcp::Json;
+use rmcp::{ErrorData as McpError, schemars};
+use std::collections::BTreeMap;
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct DescribeScriptRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The ID of the script in the script library")]
+    pub script_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AddScriptRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The script to add, its ID must not exist in the script library yet")]
+    pub script: ScriptCfg,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct UpdateScriptRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The new version of the script, its ID must exist in the script library")]
+    pub script: ScriptCfg,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RemoveScriptRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "The ID of the script in the script library")]
+    pub script_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ValidateScriptRequest {
+    #[schemars(description = "The Lua script content")]
+    pub script: String,
+    #[serde(default)]
+    #[schemars(description = "Parameters declared by the script, the defaults are available as self.params")]
+    pub parameters: BTreeMap<String, ParameterCfg>,
+    #[serde(default)]
+    #[schemars(description = "Global variables available to the script during the check")]
+    pub globals: JSONObject,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct ValidateScriptResponse {
+    #[schemars(description = "Whether the script has no errors, warnings do not prevent using it")]
+    pub valid: bool,
+    #[schemars(description = "Problems found in the script")]
+    pub diagnostics: Vec<ScriptDiagnostic>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct AddScriptResponse {
+    #[schemars(description = "Success message")]
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct UpdateScriptResponse {
+    #[schemars(description = "IDs of entities migrated to the new version of the script")]
+    pub migrated_entity_ids: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct RemoveScriptResponse {
+    #[schemars(description = "Success message")]
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct DescribeScriptResponse {
+    #[schemars(description = "The ID of the script")]
+    pub script_id: String,
+    #[schemars(description = "The kind of script (e.g., 'lua')")]
+    pub kind: String,
+    #[schemars(description = "Tunable parameters of the script. Entities override them using 'parameters' of their configuration.")]
+    pub parameters: BTreeMap<String, ParameterCfg>,
+    #[schemars(description = "IDs of entities using the script")]
+    pub entity_ids: Vec<String>,
+    #[schemars(description = "The script content")]
+    pub script: String,
+}
+
+pub fn describe_script(
+    registry: &crate::core::registry::Registry,
+    request: DescribeScriptRequest,
+) -> Result<Json<DescribeScriptResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    world.with(move |world| -> Result<_, McpError> {
+        let script_cfg = world.get_script(&request.script_id).ok_or_else(|| {
+            McpError::new(
+                rmcp::model::ErrorCode::INVALID_PARAMS,
+                format!("Script '{}' not found in world '{}'", request.script_id, request.world_name),
+                None,
+            )
+        })?;
+
+        let mut entity_ids = world
+            .get_state_ref()
+            .filter_entities(|(_, entity)| entity.borrow().get_script_id() == &request.script_id);
+        entity_ids.sort();
+
+        Ok(Json(DescribeScriptResponse {
+            script_id: request.script_id.clone(),
+            kind: script_cfg.kind.clone(),
+            parameters: script_cfg.parameters.clone(),
+            entity_ids,
+            script: script_cfg.script.clone(),
+        }))
+    })?
+}
+
+pub fn validate_script(request: ValidateScriptRequest) -> Result<Json<ValidateScriptResponse>, McpError> {
+    let script_cfg = ScriptCfg {
+        id: "script".to_string(),
+        kind: "lua".to_string(),
+        script: request.script,
+        parameters: request.parameters,
+    };
+
+    let mut diagnostics = Vec::new();
+    if let Err(e) = script_cfg.validate_defaults() {
+        diagnostics.push(ScriptDiagnostic {
+            severity: DiagnosticSeverity::Error,
+            check: "parameters".to_string(),
+            line: None,
+            column: None,
+            message: e.to_string(),
+        });
+    }
+    diagnostics.extend(script_cfg.validate_code(&request.globals));
+
+    Ok(Json(ValidateScriptResponse {
+        valid: !diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error),
+        diagnostics,
+    }))
+}
+
+pub fn add_script(
+    registry: &crate::core::registry::Registry,
+    request: AddScriptRequest,
+) -> Result<Json<AddScriptResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+    let script_id = request.script.id.clone();
+
+    world.with(move |world| world.add_script(request.script))??;
+
+    Ok(Json(AddScriptResponse {
+        message: format!("Script '{}' added", script_id),
+    }))
+}
+
+pub fn update_script(
+    registry: &crate::core::registry::Registry,
+    request: UpdateScriptRequest,
+) -> Result<Json<UpdateScriptResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    let migrated_entity_ids = world.with(move |world| world.update_script(request.script))??;
+
+    Ok(Json(UpdateScriptResponse { migrated_entity_ids }))
+}
+
+pub fn remove_script(
+    registry: &crate::core::registry::Registry,
+    request: RemoveScriptRequest,
+) -> Result<Json<RemoveScriptResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    let script_id = request.script_id.clone();
+    world.with(move |world| world.remove_script(&script_id))??;
+
+    Ok(Json(RemoveScriptResponse {
+        message: format!("Script '{}' removed", request.script_id),
+    }))
+}
@@ -1,6 +1,12 @@
+use crate::core::condition::{StopConditionCfg, StopConditions};
 use crate::core::messaging::JSONObject;
+use crate::mcp::tools::messages::{DEFAULT_MAX_MESSAGES, DeliveredMessage, MessageFilter};
 use rmcp::Json;
 use rmcp::{ErrorData as McpError, handler::server::wrapper::Parameters, schemars};
+use std::ops::ControlFlow;
+
+// Number of failed commands listed in responses of runs
+const MAX_COMMAND_ERRORS: usize = 20;
 
 #[derive(Debug, serde::Serialize, schemars::JsonSchema)]
 pub struct CreateWorldResponse {
@@ -32,6 +38,12 @@ pub struct SetEntityStateResponse {
     pub message: String,
 }
 
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct SetGlobalsResponse {
+    #[schemars(description = "Global variables of the world after the change")]
+    pub globals: JSONObject,
+}
+
 #[derive(Debug, serde::Serialize, schemars::JsonSchema)]
 pub struct GetEntityStateResponse {
     #[schemars(description = "The entity state as a JSON object")]
@@ -59,6 +71,59 @@ pub struct RunSimulationRequest {
     #[serde(default)]
     #[schemars(description = "Whether to include delivered messages in the response")]
     pub include_delivered_messages: bool,
+    #[serde(default)]
+    #[schemars(description = "Filter of the included delivered messages")]
+    pub message_filter: MessageFilter,
+    #[serde(default)]
+    #[schemars(description = "Maximum number of included delivered messages, 100 by default. Further matching messages are omitted.")]
+    pub max_messages: Option<usize>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RunUntilRequest {
+    #[schemars(description = "The name of the simulation world to advance")]
+    pub world_name: String,
+    #[schemars(description = "The duration of each step in seconds")]
+    pub step_duration: u64,
+    #[schemars(description = "Conditions checked before the first step and after each step, the run stops when any of them holds")]
+    pub conditions: Vec<StopConditionCfg>,
+    #[schemars(description = "Maximum number of steps to run if no condition holds")]
+    pub max_steps: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StoppedBy {
+    Condition, // A condition holds
+    MaxSteps,  // No condition held within max_steps
+    Director,  // The director ended the run
+    Cancelled, // The client cancelled the request
+}
+
+#[derive(Debug, serde::Serialize, schemars::JsonSchema)]
+pub struct RunUntilResponse {
+    #[schemars(description = "Number of steps run")]
+    pub steps: u32,
+    #[schemars(description = "Simulation time after the run")]
+    pub simulation_time: u64,
+    #[schemars(description = "What ended the run: condition, max_steps, director or cancelled")]
+    pub stopped_by: StoppedBy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Index of the condition that holds, if the run was stopped by a condition")]
+    pub condition_index: Option<usize>,
+    #[schemars(description = "Description of why the run stopped")]
+    pub reason: String,
+    #[schemars(description = "Total number of delivered messages during the run")]
+    pub number_of_messages: usize,
+    #[serde(default)]
+    #[schemars(description = "Number of messages dropped because they exceeded max_messages_per_entity_per_step or max_pending_messages")]
+    pub rejected_messages: usize,
+    #[serde(default)]
+    #[schemars(description = "Number of metric points dropped because they exceeded max_metric_points")]
+    pub rejected_metric_points: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Commands that failed without stopping the run, e.g. self.become with an unknown script. At most the first 20 are listed.")]
+    pub command_errors: Vec<String>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -71,6 +136,14 @@ pub struct SetEntityStateRequest {
     pub state: JSONObject,
 }
 
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SetGlobalsRequest {
+    #[schemars(description = "The name of the simulation world")]
+    pub world_name: String,
+    #[schemars(description = "Global variables to set as a JSON object. Other variables are kept, null values remove variables.")]
+    pub globals: JSONObject,
+}
+
 #[derive(Debug, serde::Serialize, schemars::JsonSchema)]
 pub struct Entity {
     #[schemars(description = "The unique ID of the entity")]
@@ -81,10 +154,40 @@ pub struct Entity {
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct AdvanceSimulationResponse {
-    #[schemars(description = "List of delivered messages during the simulation steps")]
-    pub delivered_messages: Vec<String>,
+    #[serde(default)]
+    #[schemars(description = "Number of steps run, fewer than requested if the run was cancelled or ended by the director")]
+    pub steps: u32,
+    #[serde(default)]
+    #[schemars(description = "Simulation time after the run")]
+    pub simulation_time: u64,
+    #[serde(default)]
+    #[schemars(description = "Whether the client cancelled the request, the response covers the steps run until then")]
+    pub cancelled: bool,
+    #[schemars(description = "Delivered messages matching the filter, in order of delivery")]
+    pub delivered_messages: Vec<DeliveredMessage>,
     #[schemars(description = "Total number of delivered messages during the simulation steps")]
     pub number_of_messages: usize,
+    #[serde(default)]
+    #[schemars(description = "Number of messages dropped because they exceeded max_messages_per_entity_per_step or max_pending_messages")]
+    pub rejected_messages: usize,
+    #[serde(default)]
+    #[schemars(description = "Number of metric points dropped because they exceeded max_metric_points")]
+    pub rejected_metric_points: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(description = "Commands that failed without stopping the run, e.g. self.become with an unknown script. At most the first 20 are listed.")]
+    pub command_errors: Vec<String>,
+    #[serde(default)]
+    #[schemars(description = "Number of delivered messages matching the filter")]
+    pub matching_messages: usize,
+    #[serde(default)]
+    #[schemars(description = "Whether matching messages were omitted because of max_messages")]
+    pub truncated: bool,
+    #[serde(default)]
+    #[schemars(description = "Number of matching messages omitted because of max_messages")]
+    pub omitted_messages: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Reason why the run was ended by the director, remaining steps were not simulated")]
+    pub stop_reason: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -105,6 +208,11 @@ pub struct GetWorldStateResponse {
     pub simulation_time: u64,
     pub entities_count: usize,
     pub pending_messages_count: usize,
+    #[schemars(description = "Global variables of the world")]
+    pub globals: JSONObject,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Reason why the run was ended, present only if the simulation cannot continue")]
+    pub stop_reason: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize, schemars::JsonSchema)]
@@ -123,57 +231,82 @@ pub fn list_entities(
 
     let world = registry.get(&request.world_name)?;
 
-    let world = world.read().unwrap();
-
-    if !request.include_states {
-        for (id, _entity) in world.get_state_ref().get_entities() {
-            resp.entities.push(Entity {
-                id: id.clone(),
-                state: JSONObject::new(),
-            });
-        }
-
-        return Ok(Json(resp));
-    }
-
-    for (id, entity) in world.get_state_ref().get_entities() {
-        match entity.borrow().get_lua_controller().get_state() {
-            Ok(state) => {
+    world.with(move |world| -> Result<_, McpError> {
+        if !request.include_states {
+            for id in world.get_state_ref().get_entities().keys() {
                 resp.entities.push(Entity {
                     id: id.clone(),
-                    state: state.clone(),
+                    state: JSONObject::new(),
                 });
             }
-            Err(e) => {
-                return Err(McpError::new(
-                    rmcp::model::ErrorCode::INTERNAL_ERROR,
-                    format!("Failed to serialize state for entity '{}': {}", id, e),
-                    None,
-                ));
+
+            return Ok(Json(resp));
+        }
+
+        for (id, entity) in world.get_state_ref().get_entities() {
+            match entity.borrow().get_lua_controller().get_state() {
+                Ok(state) => {
+                    resp.entities.push(Entity {
+                        id: id.clone(),
+                        state: state.clone(),
+                    });
+                }
+                Err(e) => {
+                    return Err(McpError::new(
+                        rmcp::model::ErrorCode::INTERNAL_ERROR,
+                        format!("Failed to serialize state for entity '{}': {}", id, e),
+                        None,
+                    ));
+                }
             }
         }
-    }
 
-    Ok(Json(resp))
+        Ok(Json(resp))
+    })?
 }
 
+// on_step is called after each step with the number of steps run and the simulation time, it can end the run early
 pub fn advance_simulation(
     registry: &crate::core::registry::Registry,
     Parameters(request): Parameters<RunSimulationRequest>,
+    mut on_step: impl FnMut(u32, u64) -> ControlFlow<()>,
 ) -> Result<Json<AdvanceSimulationResponse>, McpError> {
-    let mut delivered_messages: Vec<String> = Vec::new();
+    let mut steps = 0;
+    let mut cancelled = false;
+    let mut delivered_messages: Vec<DeliveredMessage> = Vec::new();
     let mut number_of_messages = 0;
+    let mut rejected_messages = 0;
+    let mut rejected_metric_points = 0;
+    let mut command_errors = Vec::new();
+    let mut matching_messages = 0;
+    let max_messages = request.max_messages.unwrap_or(DEFAULT_MAX_MESSAGES);
 
     let world = registry.get(&request.world_name)?;
 
     for _ in 0..request.num_steps {
-        match world.write().unwrap().update(request.step_duration) {
+        let step_duration = request.step_duration;
+        let (result, simulation_time, stopped) = world.with(move |world| {
+            let result = world.update(step_duration);
+            (result, world.get_simulation_time(), world.get_stop_reason().is_some())
+        })?;
+        match result {
             Ok(result) => {
                 number_of_messages += result.delivered_messages.len();
+                rejected_messages += result.rejected_messages;
+                rejected_metric_points += result.rejected_metric_points;
+                collect_command_errors(&mut command_errors, result.command_errors);
 
                 if request.include_delivered_messages {
                     for msg in result.delivered_messages {
-                        delivered_messages.push(format!("{:?}", msg));
+                        let msg = DeliveredMessage::new(msg, simulation_time);
+                        if !request.message_filter.matches(&msg) {
+                            continue;
+                        }
+
+                        matching_messages += 1;
+                        if delivered_messages.len() < max_messages {
+                            delivered_messages.push(msg);
+                        }
                     }
                 }
             }
@@ -185,14 +318,120 @@ pub fn advance_simulation(
                 ));
             }
         };
+
+        steps += 1;
+        if stopped {
+            break;
+        }
+
+        if on_step(steps, simulation_time).is_break() {
+            cancelled = true;
+            break;
+        }
     }
 
+    let (simulation_time, stop_reason) =
+        world.with(|world| (world.get_simulation_time(), world.get_stop_reason().cloned()))?;
+    let omitted_messages = matching_messages - delivered_messages.len();
+
     Ok(Json(AdvanceSimulationResponse {
+        steps,
+        simulation_time,
+        cancelled,
         delivered_messages,
         number_of_messages,
+        rejected_messages,
+        rejected_metric_points,
+        command_errors,
+        matching_messages,
+        truncated: omitted_messages > 0,
+        omitted_messages,
+        stop_reason,
     }))
 }
 
+// on_step is called after each step as in advance_simulation
+pub fn run_until(
+    registry: &crate::core::registry::Registry,
+    request: RunUntilRequest,
+    mut on_step: impl FnMut(u32, u64) -> ControlFlow<()> + Send + 'static,
+) -> Result<Json<RunUntilResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    // Lua predicates share the world state, the whole run happens on the thread of the world
+    world.with(move |world| -> Result<_, McpError> {
+        let conditions = StopConditions::new(world, request.conditions)?;
+
+        let mut steps = 0;
+        let mut number_of_messages = 0;
+        let mut rejected_messages = 0;
+        let mut rejected_metric_points = 0;
+        let mut command_errors = Vec::new();
+        let mut stopped_by = StoppedBy::MaxSteps;
+        let mut cancelled = false;
+
+        let condition = loop {
+            if let Some(index) = conditions.check(world)? {
+                break Some(index);
+            }
+            if world.get_stop_reason().is_some() {
+                stopped_by = StoppedBy::Director;
+                break None;
+            }
+            if cancelled {
+                stopped_by = StoppedBy::Cancelled;
+                break None;
+            }
+            if steps >= request.max_steps {
+                break None;
+            }
+
+            let result = world.update(request.step_duration).map_err(|e| {
+                McpError::new(
+                    rmcp::model::ErrorCode::INTERNAL_ERROR,
+                    format!("Error during simulation step: {}", e),
+                    None,
+                )
+            })?;
+            steps += 1;
+            number_of_messages += result.delivered_messages.len();
+            rejected_messages += result.rejected_messages;
+            rejected_metric_points += result.rejected_metric_points;
+            collect_command_errors(&mut command_errors, result.command_errors);
+
+            cancelled = on_step(steps, world.get_simulation_time()).is_break();
+        };
+
+        let reason = match (condition.and_then(|index| conditions.get_cfg(index)), stopped_by) {
+            (Some(cfg), _) => {
+                stopped_by = StoppedBy::Condition;
+                format!("Condition holds: {}", cfg.describe())
+            }
+            (None, StoppedBy::Director) => format!("Stopped by director: {}", world.get_stop_reason().cloned().unwrap_or_default()),
+            (None, StoppedBy::Cancelled) => format!("Cancelled by the client after {} steps", steps),
+            _ => format!("No condition held within {} steps", request.max_steps),
+        };
+
+        Ok(Json(RunUntilResponse {
+            steps,
+            simulation_time: world.get_simulation_time(),
+            stopped_by,
+            condition_index: condition,
+            reason,
+            number_of_messages,
+            rejected_messages,
+            rejected_metric_points,
+            command_errors,
+        }))
+    })?
+}
+
+// Keep the first errors of a run, they usually repeat every step
+fn collect_command_errors(command_errors: &mut Vec<String>, errors: Vec<String>) {
+    let available = MAX_COMMAND_ERRORS.saturating_sub(command_errors.len());
+    command_errors.extend(errors.into_iter().take(available));
+}
+
 pub fn list_worlds(registry: &crate::core::registry::Registry) -> Result<Json<ListWorldsResponse>, McpError> {
     let worlds = registry.list();
     Ok(Json(ListWorldsResponse { worlds }))
@@ -204,13 +443,29 @@ pub fn set_entity_state(
 ) -> Result<Json<SetEntityStateResponse>, McpError> {
     let world = registry.get(&request.world_name)?;
 
-    world.write().unwrap().set_entity_state(&request.entity_id, request.state)?;
+    let entity_id = request.entity_id.clone();
+    world.with(move |world| world.set_entity_state(&entity_id, request.state))??;
 
     Ok(Json(SetEntityStateResponse {
         message: format!("State set for entity '{}' in world '{}'", request.entity_id, request.world_name),
     }))
 }
 
+pub fn set_globals(
+    registry: &crate::core::registry::Registry,
+    request: SetGlobalsRequest,
+) -> Result<Json<SetGlobalsResponse>, McpError> {
+    let world = registry.get(&request.world_name)?;
+
+    world.with(move |world| -> Result<_, McpError> {
+        world.set_globals(request.globals);
+
+        Ok(Json(SetGlobalsResponse {
+            globals: world.get_state_ref().get_globals().clone(),
+        }))
+    })?
+}
+
 pub fn get_entity_state(
     registry: &crate::core::registry::Registry,
     world_name: String,
@@ -218,7 +473,7 @@ pub fn get_entity_state(
 ) -> Result<Json<GetEntityStateResponse>, McpError> {
     let world = registry.get(&world_name)?;
 
-    let state = world.read().unwrap().get_entity_state(&entity_id)?;
+    let state = world.with(move |world| world.get_entity_state(&entity_id))??;
 
     Ok(Json(GetEntityStateResponse { state }))
 }
@@ -247,13 +502,75 @@ pub fn get_world_state(
 ) -> Result<Json<GetWorldStateResponse>, McpError> {
     let world_rc = registry.get(&request.world_name)?;
 
-    let world = world_rc.read().unwrap();
+    world_rc.with(move |world| -> Result<_, McpError> {
     
-    let response = GetWorldStateResponse {
-        simulation_time: world.get_simulation_time(),
-        entities_count: world.get_entities_count(),
-        pending_messages_count: world.get_pending_messages_count(),
-    };
+        let response = GetWorldStateResponse {
+            simulation_time: world.get_simulation_time(),
+            entities_count: world.get_entities_count(),
+            pending_messages_count: world.get_pending_messages_count(),
+            globals: world.get_state_ref().get_globals().clone(),
+            stop_reason: world.get_stop_reason().cloned(),
+        };
 
-    Ok(Json(response))
-}
\ No newline at end of file
+        Ok(Json(response))
+    })?
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::registry::{Registry, RegistryLimits};
+    use crate::core::world_config::{EntityCfg, WorldCfg};
+
+    #[test]
+    fn test_advance_simulation_max_messages() {
+        let mut world_cfg = WorldCfg::new("chatter_world".to_string());
+        world_cfg.add_script(
+            "chatter".to_string(),
+            r#"
+            function update(current_time, msgs)
+                self.send_msg(self.id, "ping", {})
+                self.send_msg(self.id, "ping", {})
+                self.send_msg(self.id, "pong", {})
+            end
+            function get_state() return {} end
+            function set_state(state) end
+            "#
+            .to_string(),
+        );
+        world_cfg.entities.push(EntityCfg {
+            id: "chatter".to_string(),
+            script_id: "chatter".to_string(),
+            ..Default::default()
+        });
+        let registry = Registry::new(RegistryLimits::default());
+        registry.create(world_cfg).unwrap();
+
+        let advance = |max_messages: Option<usize>| {
+            let request = RunSimulationRequest {
+                world_name: "chatter_world".to_string(),
+                step_duration: 1,
+                num_steps: 3,
+                include_delivered_messages: true,
+                message_filter: MessageFilter {
+                    kinds: Some(vec!["ping".to_string()]),
+                    ..Default::default()
+                },
+                max_messages,
+            };
+            advance_simulation(&registry, Parameters(request), |_, _| ControlFlow::Continue(())).unwrap().0
+        };
+
+        // Messages sent in a step are delivered in the next one, two of each three match the filter
+        let response = advance(Some(3));
+        assert_eq!((response.number_of_messages, response.matching_messages), (6, 4));
+        assert_eq!(response.delivered_messages.len(), 3);
+        assert!(response.truncated);
+        assert_eq!(response.omitted_messages, 1);
+
+        let response = advance(None);
+        assert_eq!((response.number_of_messages, response.matching_messages), (9, 6));
+        assert_eq!(response.delivered_messages.len(), 6);
+        assert!(!response.truncated);
+        assert_eq!(response.omitted_messages, 0);
+    }
+}